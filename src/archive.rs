@@ -0,0 +1,109 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chromium::unzip_file,
+    extract::extract_archive,
+    firefox::firefox_content_dir,
+    metadata::{mark_complete, mark_incomplete, InstallMetadata},
+    paths::Paths,
+    platform::Platform,
+    timings::{self, Phase},
+    utils::{move_dir, sha256_file},
+};
+
+/// `--keep-archive` 保留的压缩包旁边的记录文件（`<archive>.json`），重新解压前据此校验。
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveRecord {
+    sha256: String,
+    metadata: InstallMetadata,
+}
+
+fn record_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+/// 保留的压缩包的文件名，带上平台，同时获取多个 `--os` 时不会互相覆盖。
+pub(crate) fn kept_archive_name(
+    browser: &str,
+    version: &str,
+    platform: Platform,
+    extension: &str,
+) -> String {
+    format!(
+        "{browser}-{version}-{}-{}.{extension}",
+        platform.os().as_str(),
+        platform.arch().as_str()
+    )
+}
+
+pub(crate) fn keep_archive(archive: &Path, metadata: &InstallMetadata) -> Result<()> {
+    let record = ArchiveRecord {
+        sha256: sha256_file(archive)?,
+        metadata: metadata.clone(),
+    };
+    std::fs::write(record_path(archive), serde_json::to_string_pretty(&record)?)?;
+    status!("==> kept archive: {}", archive.display());
+    Ok(())
+}
+
+pub(crate) fn extract_kept_archive(archive: &Path, paths: &Paths) -> Result<PathBuf> {
+    let record_path = record_path(archive);
+    let record: ArchiveRecord = serde_json::from_reader(
+        File::open(&record_path)
+            .map_err(|err| anyhow!("Reading {} failed: {err}", record_path.display()))?,
+    )?;
+    let digest = sha256_file(archive)?;
+    if digest != record.sha256 {
+        return Err(anyhow!(
+            "Digest mismatch for {}: expected {}, got {digest}",
+            archive.display(),
+            record.sha256
+        ));
+    }
+    status!("==> verified {}: {digest}", archive.display());
+
+    let metadata = record.metadata;
+    let dir_name = format!("{}-{}", metadata.browser, metadata.version);
+    let temp_path = paths.temp_dir.join(format!(".tmp-{dir_name}"));
+    if temp_path.exists() {
+        std::fs::remove_dir_all(&temp_path)?;
+    }
+    std::fs::create_dir_all(&temp_path)?;
+    let format = timings::time(Phase::Extract, || match metadata.browser.as_str() {
+        "chromium" | "chromium-asan" => unzip_file(
+            archive,
+            &temp_path,
+            metadata.locales.as_deref().unwrap_or_default(),
+            false,
+        )
+        .map(|_| None),
+        _ => extract_archive(Bytes::from(std::fs::read(archive)?), &temp_path).map(Some),
+    })?;
+
+    let content_path = match (metadata.browser.as_str(), format) {
+        ("firefox" | "firefox-devedition", Some(format)) => firefox_content_dir(&temp_path, format),
+        _ => temp_path.clone(),
+    };
+    mark_incomplete(&content_path)?;
+    metadata.write(&content_path)?;
+    let install_path = paths.install_dir(&metadata.browser, &metadata.version);
+    if install_path.exists() {
+        std::fs::remove_dir_all(&install_path)?;
+    }
+    std::fs::create_dir_all(&paths.output_dir)?;
+    move_dir(&content_path, &install_path)?;
+    mark_complete(&install_path)?;
+    if temp_path.exists() {
+        std::fs::remove_dir_all(&temp_path)?;
+    }
+    Ok(install_path)
+}
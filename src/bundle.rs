@@ -0,0 +1,325 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    extract::{check_parents, create_symlink, stays_inside},
+    metadata::{find_installs, mark_complete, mark_incomplete, InstallMetadata, SCHEMA_VERSION},
+    utils::{retry_locked, sha256_file, walk_files, walk_symlinks},
+    version::VersionSpec,
+};
+
+const MANIFEST_NAME: &str = "bundle.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleManifest {
+    schema_version: u32,
+    installs: Vec<BundleInstall>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleInstall {
+    dir: String,
+    metadata: InstallMetadata,
+    files: Vec<BundleFile>,
+    /// 符号链接只记录在清单中，例如 macOS 应用中的 `Versions/Current`。
+    #[serde(default)]
+    links: Vec<BundleLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleLink {
+    path: String,
+    target: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundleFile {
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+pub(crate) fn create_bundle(
+    bundle_path: &Path,
+    selectors: &[String],
+    source_dir: &Path,
+) -> Result<()> {
+    let installs = find_installs(source_dir)?;
+    let mut selected = Vec::new();
+    for selector in selectors {
+        let spec = VersionSpec::parse(selector);
+        let matched: Vec<_> = installs
+            .iter()
+            .filter(|(dir, metadata)| {
+                dir.file_name().and_then(|name| name.to_str()) == Some(selector.as_str())
+                    || spec.matches(&metadata.version)
+            })
+            .collect();
+        if matched.is_empty() {
+            return Err(anyhow!("No installed browser matches {selector}"));
+        }
+        for install in matched {
+            if !selected.iter().any(|s: &&(_, _)| s.0 == install.0) {
+                selected.push(install);
+            }
+        }
+    }
+
+    let mut zip = ZipWriter::new(File::create(bundle_path)?);
+    let mut manifest = BundleManifest {
+        schema_version: SCHEMA_VERSION,
+        installs: Vec::new(),
+    };
+    for (dir, metadata) in selected {
+        let dir_name = dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("Invalid install directory: {}", dir.display()))?
+            .to_owned();
+        status!("==> bundling {dir_name}");
+        let mut files = Vec::new();
+        for file_path in walk_files(dir)? {
+            let relative = slash_path(file_path.strip_prefix(dir)?);
+            let size = file_path.metadata()?.len();
+            let options = FileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .large_file(size >= u32::MAX as u64)
+                .unix_permissions(file_mode(&file_path)?);
+            zip.start_file(format!("{dir_name}/{relative}"), options)?;
+
+            let mut hasher = Sha256::new();
+            let mut reader = File::open(&file_path)?;
+            let mut buf = vec![0; 64 * 1024];
+            loop {
+                let len = reader.read(&mut buf)?;
+                if len == 0 {
+                    break;
+                }
+                hasher.update(&buf[..len]);
+                zip.write_all(&buf[..len])?;
+            }
+            files.push(BundleFile {
+                path: relative,
+                size,
+                sha256: format!("{:x}", hasher.finalize()),
+            });
+        }
+        let links = walk_symlinks(dir)?
+            .into_iter()
+            .map(|link| {
+                Ok(BundleLink {
+                    path: slash_path(link.strip_prefix(dir)?),
+                    target: slash_path(&std::fs::read_link(&link)?),
+                })
+            })
+            .collect::<Result<_>>()?;
+        manifest.installs.push(BundleInstall {
+            dir: dir_name,
+            metadata: metadata.clone(),
+            files,
+            links,
+        });
+    }
+    zip.start_file(MANIFEST_NAME, FileOptions::default())?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    zip.finish()?;
+
+    status!(
+        "==> bundle {} created, sha256: {}",
+        bundle_path.display(),
+        sha256_file(bundle_path)?
+    );
+    Ok(())
+}
+
+pub(crate) fn install_bundle(bundle_path: &Path, target_dir: &Path) -> Result<Vec<PathBuf>> {
+    status!(
+        "==> installing bundle {}, sha256: {}",
+        bundle_path.display(),
+        sha256_file(bundle_path)?
+    );
+    let mut zip = ZipArchive::new(File::open(bundle_path)?)?;
+    let manifest: BundleManifest = serde_json::from_reader(zip.by_name(MANIFEST_NAME)?)?;
+    for install in &manifest.installs {
+        status!(
+            "==> unpacking {} {} into {}",
+            install.metadata.browser,
+            install.metadata.version,
+            install.dir
+        );
+        let install_dir = target_dir.join(install_dir_name(&install.dir)?);
+        mark_incomplete(&install_dir)?;
+        for file in &install.files {
+            let mut entry = zip.by_name(&format!("{}/{}", install.dir, file.path))?;
+            let relative = entry
+                .enclosed_name()
+                .filter(|relative| relative.starts_with(&install.dir))
+                .ok_or_else(|| anyhow!("Invalid path in bundle: {}", file.path))?
+                .to_owned();
+            let path = target_dir.join(relative);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut hasher = Sha256::new();
+            let mut writer = retry_locked(|| File::create(&path))?;
+            let mut buf = vec![0; 64 * 1024];
+            loop {
+                let len = entry.read(&mut buf)?;
+                if len == 0 {
+                    break;
+                }
+                hasher.update(&buf[..len]);
+                writer.write_all(&buf[..len])?;
+            }
+            if format!("{:x}", hasher.finalize()) != file.sha256 {
+                return Err(anyhow!(
+                    "Checksum mismatch for {}, the bundle is corrupted.",
+                    path.display()
+                ));
+            }
+            set_file_mode(&path, entry.unix_mode())?;
+        }
+        // 链接在所有文件之后创建，文件不会经由链接写到别处。
+        for link in &install.links {
+            install_link(&install_dir, link)?;
+        }
+        mark_complete(&install_dir)?;
+    }
+    Ok(manifest
+        .installs
+        .iter()
+        .map(|install| target_dir.join(&install.dir))
+        .collect())
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 与解压时相同，链接的路径和目标都不能离开安装目录，也不能位于另一个链接之下。
+fn install_link(install_dir: &Path, link: &BundleLink) -> Result<()> {
+    let relative = Path::new(&link.path);
+    let parent = relative.parent().unwrap_or(Path::new(""));
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        || !stays_inside(parent, Path::new(&link.target))
+    {
+        return Err(anyhow!(
+            "Invalid symlink in bundle: {} -> {}",
+            link.path,
+            link.target
+        ));
+    }
+    check_parents(install_dir, relative)?;
+    let path = install_dir.join(relative);
+    std::fs::create_dir_all(path.parent().unwrap_or(install_dir))?;
+    create_symlink(&link.target, &path)
+}
+
+/// `bundle.json` 来自不可信的离线包，安装目录名只能是一级普通目录，不能是绝对路径或 `..`。
+fn install_dir_name(dir: &str) -> Result<&str> {
+    let mut components = Path::new(dir).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(dir),
+        _ => Err(anyhow!("Invalid install directory in bundle: {dir}")),
+    }
+}
+
+#[cfg(unix)]
+fn file_mode(path: &Path) -> Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Ok(path.metadata()?.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_path: &Path) -> Result<u32> {
+    Ok(0o644)
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: Option<u32>) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(_path: &Path, _mode: Option<u32>) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_symlinks_are_bundled_as_links() {
+        let base = std::env::temp_dir().join(format!("fetchbrowser-bundle-{}", std::process::id()));
+        let install = base.join("source").join("chromium-114.0.5735.106");
+        let versions = install.join("Framework.framework").join("Versions");
+        std::fs::create_dir_all(versions.join("114")).unwrap();
+        std::fs::write(versions.join("114").join("lib"), "lib").unwrap();
+        std::os::unix::fs::symlink("114", versions.join("Current")).unwrap();
+        let metadata = InstallMetadata {
+            browser: "chromium".to_owned(),
+            version: "114.0.5735.106".to_owned(),
+            ..Default::default()
+        };
+        metadata.write(&install).unwrap();
+
+        let bundle = base.join("bundle.zip");
+        let created = create_bundle(&bundle, &["114".to_owned()], &base.join("source"));
+        let target = base.join("target");
+        let installed = created.and_then(|_| install_bundle(&bundle, &target));
+        let link = target.join("chromium-114.0.5735.106/Framework.framework/Versions/Current");
+        let link_target = std::fs::read_link(&link);
+        let through_link = std::fs::read_to_string(link.join("lib"));
+        let _ = std::fs::remove_dir_all(&base);
+
+        installed.unwrap();
+        assert_eq!(link_target.unwrap(), Path::new("114"));
+        assert_eq!(through_link.unwrap(), "lib");
+    }
+
+    #[test]
+    fn links_must_stay_inside_the_install() {
+        let dir =
+            std::env::temp_dir().join(format!("fetchbrowser-bundle-link-{}", std::process::id()));
+        for (path, target) in [("a", "../outside"), ("a/b", "/etc"), ("../a", "b")] {
+            let link = BundleLink {
+                path: path.to_owned(),
+                target: target.to_owned(),
+            };
+            assert!(install_link(&dir, &link).is_err(), "{path} -> {target}");
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn install_dirs_must_be_plain_names() {
+        assert_eq!(
+            install_dir_name("chromium-114.0.5735.106").unwrap(),
+            "chromium-114.0.5735.106"
+        );
+        for dir in ["", "..", "../outside", "/etc", "a/b", "./a"] {
+            assert!(install_dir_name(dir).is_err(), "{dir} was accepted");
+        }
+    }
+}
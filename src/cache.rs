@@ -0,0 +1,534 @@
+use std::{
+    fmt,
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+
+use crate::{
+    chromium::{self, DRIVER_CACHE},
+    common::{BuildVariant, ReleaseChannel},
+    downloads::ARCHIVE_CACHE,
+    firefox,
+    http::Http,
+    paths::{legacy_cache_dir, Paths},
+    platform::{Arch, Os, Platform},
+    queue::QUEUE_DIR,
+    utils::walk_files,
+};
+
+/// 记录上次校验缓存的时间，用于配置文件中的 `verify-cache-days`。
+const LAST_VERIFIED_FILE: &str = ".last-verified";
+
+/// 版本列表缓存默认的有效期。
+const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 版本列表缓存的有效期和签名密钥，随 [`Paths`] 传递。
+#[derive(Clone)]
+pub(crate) struct CacheSettings {
+    /// 来自 `--cache-ttl` 或配置文件中的 `cache-ttl`，为 `None` 时永不过期。
+    pub(crate) ttl: Option<Duration>,
+    /// 来自 `FETCHBROWSER_CACHE_KEY` 或配置文件中的 `cache-signing-key`。
+    pub(crate) signing_key: Option<Vec<u8>>,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            ttl: Some(DEFAULT_TTL),
+            signing_key: None,
+        }
+    }
+}
+
+/// 不输出签名密钥。
+impl fmt::Debug for CacheSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheSettings")
+            .field("ttl", &self.ttl)
+            .field(
+                "signing_key",
+                &self.signing_key.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+fn age(path: &Path) -> Option<Duration> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
+
+fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+fn hmac(key: &[u8], content: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(content);
+    mac
+}
+
+impl CacheSettings {
+    /// `ttl` 为 0 时缓存永不过期。
+    pub(crate) fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = (!ttl.is_zero()).then_some(ttl);
+    }
+
+    /// 空的密钥等同于不签名。
+    pub(crate) fn set_signing_key(&mut self, key: Option<String>) {
+        self.signing_key = key.filter(|key| !key.is_empty()).map(String::into_bytes);
+    }
+
+    /// 缓存文件写入后已超过有效期，调用方应重新获取。
+    pub(crate) fn expired(&self, path: &Path) -> bool {
+        let Some(ttl) = self.ttl else {
+            return false;
+        };
+        let expired = age(path).is_some_and(|age| age > ttl);
+        if expired {
+            status!(
+                "==> {} is older than the cache ttl ({}s), refreshing it",
+                path.display(),
+                ttl.as_secs()
+            );
+        }
+        expired
+    }
+
+    /// 读取未过期的缓存，见 [`CacheSettings::read_verified`]。
+    pub(crate) fn read_usable<T: DeserializeOwned>(&self, path: &Path) -> Result<Option<T>> {
+        match self.expired(path) {
+            true => Ok(None),
+            false => self.read_verified(path),
+        }
+    }
+
+    /// 读取缓存文件并校验签名，校验和解析使用同一份内容，避免校验之后文件被替换。
+    /// 文件不存在或签名无效（已删除）时返回 `None`。
+    pub(crate) fn read_verified<T: DeserializeOwned>(&self, path: &Path) -> Result<Option<T>> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        match self.content_signed(path, &content) {
+            true => Ok(Some(serde_json::from_slice(&content)?)),
+            false => Ok(None),
+        }
+    }
+
+    /// 重新获取过期的缓存失败时（例如离线），继续使用过期的缓存；没有缓存时返回原来的错误。
+    pub(crate) fn read_stale<T: DeserializeOwned>(
+        &self,
+        path: &Path,
+        err: anyhow::Error,
+    ) -> Result<T> {
+        let Some(stale) = self.read_verified(path)? else {
+            return Err(err);
+        };
+        status!(
+            "==> warning[cache-stale]: refreshing {} failed, using the expired cache: {err}",
+            path.display()
+        );
+        Ok(stale)
+    }
+
+    /// 配置了签名密钥时，为缓存文件写入 HMAC-SHA256 签名（旁边的 `.sig` 文件，base64 编码）。
+    pub(crate) fn sign(&self, path: &Path) -> Result<()> {
+        let Some(key) = &self.signing_key else {
+            return Ok(());
+        };
+        let signature = hmac(key, &std::fs::read(path)?).finalize().into_bytes();
+        std::fs::write(signature_path(path), base64::encode(signature))?;
+        Ok(())
+    }
+
+    /// 写入缓存文件并签名。
+    pub(crate) fn write_signed(&self, path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
+        std::fs::write(path, content)?;
+        self.sign(path)
+    }
+
+    /// 配置了签名密钥时校验缓存文件的签名。
+    ///
+    /// 缓存目录可能由多个用户或机器共享，签名缺失或不一致说明文件可能被篡改，
+    /// 此时删除该文件并返回 `false`，调用方按没有缓存处理、重新获取。没有配置密钥时总是返回 `true`。
+    /// 需要使用文件内容时用 [`CacheSettings::read_verified`]，只读一次文件。
+    pub(crate) fn signature_valid(&self, path: &Path) -> bool {
+        if self.signing_key.is_none() {
+            return true;
+        }
+        self.content_signed(path, &std::fs::read(path).unwrap_or_default())
+    }
+
+    /// 校验 `content`（`path` 的内容）的签名，无效时删除缓存文件。
+    fn content_signed(&self, path: &Path, content: &[u8]) -> bool {
+        let Some(key) = &self.signing_key else {
+            return true;
+        };
+        let valid = std::fs::read_to_string(signature_path(path))
+            .ok()
+            .and_then(|signature| base64::decode(signature.trim()).ok())
+            .is_some_and(|signature| hmac(key, content).verify_slice(&signature).is_ok());
+        if !valid {
+            status!(
+                "==> warning[cache-signature]: {} has a missing or invalid signature, refetching it",
+                path.display()
+            );
+            let _ = std::fs::remove_file(path);
+            let _ = std::fs::remove_file(signature_path(path));
+        }
+        valid
+    }
+}
+
+pub(crate) struct CacheFilter {
+    pub(crate) chrome: bool,
+    pub(crate) firefox: bool,
+    pub(crate) os: Option<Os>,
+    pub(crate) channel: Option<ReleaseChannel>,
+}
+
+/// 缓存目录中的一个文件。
+enum CacheEntry {
+    Releases(Platform, ReleaseChannel),
+    Builds(Platform, BuildVariant),
+    FirefoxReleases,
+}
+
+impl CacheEntry {
+    /// 不同架构可能共用同一个缓存文件，以文件名去重。
+    fn file_name(&self) -> Option<String> {
+        match self {
+            CacheEntry::Releases(platform, channel) => Some(format!(
+                "releases-{}-{}.json",
+                platform.arg_name(),
+                channel.as_constant()
+            )),
+            CacheEntry::Builds(platform, variant) => variant
+                .prefix(*platform)
+                .map(|prefix| format!("builds-{prefix}.json")),
+            CacheEntry::FirefoxReleases => Some("firefox-releases.json".to_owned()),
+        }
+    }
+
+    async fn verify(&self, client: &Http, paths: &Paths) -> Result<CacheStatus> {
+        match self {
+            CacheEntry::Releases(platform, channel) => {
+                chromium::verify_cached_releases(*platform, *channel, client, paths).await
+            }
+            CacheEntry::Builds(platform, variant) => {
+                chromium::verify_cached_builds(*platform, *variant, client, paths).await
+            }
+            CacheEntry::FirefoxReleases => firefox::verify_cached_releases(client, paths).await,
+        }
+    }
+}
+
+/// 缓存文件与上游比较的结果。
+pub(crate) enum CacheStatus {
+    /// 没有缓存，下次使用时会重新获取。
+    Missing,
+    Fresh,
+    /// 能够正常解析，但与上游不一致（过期或被截断）。
+    Diverged(String),
+}
+
+impl CacheFilter {
+    fn entries(&self) -> Vec<(String, CacheEntry)> {
+        let mut entries = Vec::new();
+        if self.chrome {
+            let os_list = self
+                .os
+                .map(|os| vec![os])
+                .unwrap_or_else(|| Os::ALL.to_vec());
+            let channels = self.channel.unwrap_or(ReleaseChannel::Any).concrete();
+            for os in os_list {
+                for arch in Arch::ALL {
+                    let platform = Platform::new(os, arch);
+                    for channel in &channels {
+                        entries.push(CacheEntry::Releases(platform, *channel));
+                    }
+                    for variant in [BuildVariant::Snapshot, BuildVariant::Asan] {
+                        entries.push(CacheEntry::Builds(platform, variant));
+                    }
+                }
+            }
+        }
+        if self.firefox {
+            entries.push(CacheEntry::FirefoxReleases);
+        }
+        let mut entries: Vec<_> = entries
+            .into_iter()
+            .filter_map(|entry| Some((entry.file_name()?, entry)))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.dedup_by(|a, b| a.0 == b.0);
+        entries
+    }
+}
+
+pub(crate) fn invalidate(filter: &CacheFilter, paths: &Paths) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for (name, _) in filter.entries() {
+        let path = paths.cache_dir.join(&name);
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+            let _ = std::fs::remove_file(signature_path(&path));
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+/// 缓存目录中由缓存管理的文件：版本列表、chromedriver 和压缩包缓存，以及它们的签名。
+/// 缓存目录中还有配置文件和安装记录，这些不属于缓存。
+fn cache_files(paths: &Paths) -> Result<Vec<PathBuf>> {
+    let Ok(entries) = std::fs::read_dir(&paths.cache_dir) else {
+        return Ok(Vec::new());
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let name = name.strip_suffix(".sig").unwrap_or(&name);
+        if name == ARCHIVE_CACHE || name == DRIVER_CACHE || name == QUEUE_DIR {
+            if path.is_dir() {
+                files.extend(walk_files(&path)?);
+            }
+        } else if name.starts_with("releases-")
+            || name.starts_with("builds-")
+            || name == "firefox-releases.json"
+            || name == "firefox-devedition-releases.json"
+            || name == format!("{DRIVER_CACHE}.json")
+            || name == LAST_VERIFIED_FILE
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `fb cache list`：列出缓存的文件、大小和写入时间，过期的版本列表加以标记。
+pub(crate) fn list(paths: &Paths) -> Result<()> {
+    let ttl = paths.cache.ttl;
+    let files = cache_files(paths)?;
+    let mut total = 0;
+    for path in files.iter().filter(|path| {
+        path.extension().is_none_or(|ext| ext != "sig")
+            && path
+                .file_name()
+                .is_some_and(|name| name != LAST_VERIFIED_FILE)
+    }) {
+        let size = std::fs::metadata(path)?.len();
+        total += size;
+        let age = age(path).unwrap_or_default();
+        let metadata_file = path.parent() == Some(paths.cache_dir.as_path());
+        let expired = metadata_file && ttl.is_some_and(|ttl| age > ttl);
+        println!(
+            "{:>10.1} MB  {:>6}h ago  {}{}",
+            size as f64 / 1024.0 / 1024.0,
+            age.as_secs() / 60 / 60,
+            path.strip_prefix(&paths.cache_dir)
+                .unwrap_or(path)
+                .display(),
+            if expired { "  (expired)" } else { "" }
+        );
+    }
+    status!(
+        "==> {:.1} MB cached in {}",
+        total as f64 / 1024.0 / 1024.0,
+        paths.cache_dir.display()
+    );
+    Ok(())
+}
+
+/// `fb cache clear`：删除所有缓存的文件，返回删除的文件数。
+pub(crate) fn clear(paths: &Paths) -> Result<usize> {
+    let files = cache_files(paths)?;
+    for path in &files {
+        std::fs::remove_file(path)?;
+    }
+    for dir in [ARCHIVE_CACHE, DRIVER_CACHE] {
+        let dir = paths.cache_dir.join(dir);
+        if dir.is_dir() {
+            std::fs::remove_dir_all(dir)?;
+        }
+    }
+    Ok(files.len())
+}
+
+/// 与上游比较已缓存的文件，返回不一致的文件数。
+pub(crate) async fn verify(filter: &CacheFilter, client: &Http, paths: &Paths) -> Result<usize> {
+    let mut diverged = 0;
+    for (name, entry) in filter.entries() {
+        match entry.verify(client, paths).await? {
+            CacheStatus::Missing => {}
+            CacheStatus::Fresh => status!("==> {name} is up to date"),
+            CacheStatus::Diverged(reason) => {
+                diverged += 1;
+                status!(
+                    "==> warning[cache-divergence]: {name}: {reason}. Run `fb cache invalidate` to refetch it."
+                );
+            }
+        }
+    }
+    std::fs::write(paths.cached_file(LAST_VERIFIED_FILE)?, "")?;
+    Ok(diverged)
+}
+
+/// 迁移旧版本留下的缓存：
+///
+/// - 旧的 `fetchchromium` 目录中仍然有效的版本列表移到缓存目录（缓存目录中已有同名文件时丢弃），然后删除整个目录；
+/// - 以操作系统命名的 `builds-<os>.json`（如 `builds-win.json`）按其中快照的目录改名为 `builds-<prefix>.json`，
+///   无法解析的删除。
+///
+/// 迁移的文件保留原来的修改时间，过期的仍按 `cache-ttl` 重新获取。
+pub(crate) fn migrate_legacy(paths: &Paths) -> Result<()> {
+    let legacy_dir = legacy_cache_dir()?;
+    if legacy_dir.is_dir() && legacy_dir != paths.cache_dir {
+        let (mut migrated, mut removed) = (0, 0);
+        for entry in std::fs::read_dir(&legacy_dir)?.flatten() {
+            // 只处理认识的版本列表，目录中的其他文件可能是用户自己放的，保持原样。
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !is_version_list(&name) || !entry.path().is_file() {
+                continue;
+            }
+            let target = paths.cache_dir.join(&name);
+            if !target.exists() && migrate_file(&entry.path(), &target, &paths.cache)? {
+                migrated += 1;
+            } else {
+                remove_cache_file(&entry.path())?;
+                removed += 1;
+            }
+        }
+        // 目录中还有其他文件时 remove_dir 会失败，保留目录，之后每次启动都只是空跑一遍。
+        let kept = std::fs::remove_dir(&legacy_dir).is_err();
+        if migrated + removed > 0 {
+            status!(
+                "==> migrated the legacy cache {}: {migrated} file(s) kept, {removed} removed{}",
+                legacy_dir.display(),
+                if kept {
+                    ", other files left in place"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+    if paths.cache_dir.is_dir() {
+        // 当前命名的快照列表可能很大，不必解析。
+        let current: Vec<_> = Os::ALL
+            .into_iter()
+            .flat_map(|os| Arch::ALL.map(|arch| Platform::new(os, arch)))
+            .flat_map(|platform| {
+                [BuildVariant::Snapshot, BuildVariant::Asan]
+                    .into_iter()
+                    .filter_map(move |variant| variant.prefix(platform))
+            })
+            .map(|prefix| format!("builds-{prefix}.json"))
+            .collect();
+        for entry in std::fs::read_dir(&paths.cache_dir)?.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with("builds-") || !name.ends_with(".json") || current.contains(&name) {
+                continue;
+            }
+            let path = entry.path();
+            let Some(builds) = std::fs::read(&path)
+                .ok()
+                .and_then(|content| serde_json::from_slice::<Vec<String>>(&content).ok())
+            else {
+                status!(
+                    "==> removing the unreadable legacy cache {}",
+                    path.display()
+                );
+                remove_cache_file(&path)?;
+                continue;
+            };
+            // 快照是 `<prefix>/<rev>/`，ASAN 构建是 `<prefix>/asan-....zip`。
+            let Some(prefix) = builds.first().and_then(|build| build.split('/').next()) else {
+                continue;
+            };
+            let expected = format!("builds-{prefix}.json");
+            if name == expected {
+                continue;
+            }
+            let target = paths.cache_dir.join(&expected);
+            // 不区分大小写的文件系统上 `builds-win.json` 与 `builds-Win.json` 是同一个文件。
+            if name.eq_ignore_ascii_case(&expected) || !target.exists() {
+                status!("==> renaming the legacy cache {name} to {expected}");
+                migrate_file(&path, &target, &paths.cache)?;
+            } else {
+                status!("==> removing the legacy cache {name}, {expected} is newer");
+                remove_cache_file(&path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 旧版本缓存的发布历史、快照列表和 Firefox 版本列表。
+fn is_version_list(name: &str) -> bool {
+    (name.starts_with("releases-")
+        || name.starts_with("builds-")
+        || name == "firefox-releases.json")
+        && name.ends_with(".json")
+}
+
+/// 内容是有效的 JSON 时重新签名写入 `target` 并保留修改时间，否则返回 `false`。
+fn migrate_file(source: &Path, target: &Path, settings: &CacheSettings) -> Result<bool> {
+    let content = std::fs::read(source)?;
+    if serde_json::from_slice::<serde_json::Value>(&content).is_err() {
+        return Ok(false);
+    }
+    let modified = std::fs::metadata(source)?.modified()?;
+    std::fs::create_dir_all(target.parent().unwrap())?;
+    // 先写入临时文件，`source` 与 `target` 在不区分大小写的文件系统上可能是同一个文件。
+    let temp = target.with_extension("json.migrating");
+    std::fs::write(&temp, &content)?;
+    File::options()
+        .write(true)
+        .open(&temp)?
+        .set_modified(modified)?;
+    if source.exists() {
+        remove_cache_file(source)?;
+    }
+    std::fs::rename(&temp, target)?;
+    settings.sign(target)?;
+    Ok(true)
+}
+
+/// 删除缓存文件及其签名。
+fn remove_cache_file(path: &Path) -> Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let signature = signature_path(path);
+    if signature.exists() {
+        std::fs::remove_file(signature)?;
+    }
+    Ok(())
+}
+
+/// 距上次校验已超过 `days` 天（或从未校验过）。
+pub(crate) fn verification_due(days: u64, paths: &Paths) -> bool {
+    let last_verified = std::fs::metadata(paths.cache_dir.join(LAST_VERIFIED_FILE))
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    last_verified
+        .and_then(|time| SystemTime::now().duration_since(time).ok())
+        .is_none_or(|elapsed| elapsed > Duration::from_secs(days * 24 * 60 * 60))
+}
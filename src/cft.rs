@@ -0,0 +1,402 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use futures::{stream, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize};
+use tokio::sync::OnceCell;
+
+use crate::{
+    common::{BrowserReleaseItem, BrowserReleases, DownloadPlan, ReleaseChannel, ReleaseMatches},
+    downloads::{url_file_name, DownloadRequest, Downloads},
+    error::FetchError,
+    extract::extract_archive,
+    http::Http,
+    lock::with_install_lock,
+    metadata::{mark_complete, mark_incomplete, InstallMetadata},
+    paths::Paths,
+    platform::{Arch, Os, Platform},
+    timings::{self, Phase},
+    utils::{blocking, move_dir},
+    version::VersionSpec,
+};
+
+const LAST_KNOWN_GOOD_URL: &str = "https://googlechromelabs.github.io/chrome-for-testing/last-known-good-versions-with-downloads.json";
+const KNOWN_GOOD_URL: &str =
+    "https://googlechromelabs.github.io/chrome-for-testing/known-good-versions-with-downloads.json";
+
+/// Chromium 版本的下载来源。
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub(crate) enum ChromiumSource {
+    /// chromium-browser-snapshots 中与发布分支位置最接近的快照。
+    #[default]
+    Snapshots,
+    /// Chrome for Testing 按版本发布的构建，版本号完全一致。
+    ChromeForTesting,
+}
+
+/// Chrome for Testing 发布的程序，`chrome` 之外的需要另外请求。
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum CftBinary {
+    Chrome,
+    Chromedriver,
+    ChromeHeadlessShell,
+}
+
+impl CftBinary {
+    /// JSON 中 `downloads` 的键，同时用作安装目录的名称。
+    fn name(&self) -> &'static str {
+        match self {
+            CftBinary::Chrome => "chrome",
+            CftBinary::Chromedriver => "chromedriver",
+            CftBinary::ChromeHeadlessShell => "chrome-headless-shell",
+        }
+    }
+
+    fn executable(&self, os: Os) -> &'static str {
+        match (self, os) {
+            (CftBinary::Chrome, Os::Windows) => "chrome.exe",
+            (CftBinary::Chrome, Os::Linux) => "chrome",
+            (CftBinary::Chrome, Os::Mac) => {
+                "Google Chrome for Testing.app/Contents/MacOS/Google Chrome for Testing"
+            }
+            (CftBinary::Chromedriver, Os::Windows) => "chromedriver.exe",
+            (CftBinary::Chromedriver, _) => "chromedriver",
+            (CftBinary::ChromeHeadlessShell, Os::Windows) => "chrome-headless-shell.exe",
+            (CftBinary::ChromeHeadlessShell, _) => "chrome-headless-shell",
+        }
+    }
+}
+
+/// 版本号位置上的渠道别名（`stable`、`beta`、`dev`、`canary`），`any`、Firefox 的 `esr`
+/// 和 Chrome for Testing 没有发布的 `extended` 不是别名。
+pub(crate) fn channel_alias(version: &str) -> Option<ReleaseChannel> {
+    ReleaseChannel::from_str(version, true)
+        .ok()
+        .filter(|channel| {
+            !matches!(
+                channel,
+                ReleaseChannel::Any | ReleaseChannel::Esr | ReleaseChannel::Extended
+            )
+        })
+}
+
+#[derive(Debug, Deserialize)]
+struct LastKnownGoodVersions {
+    channels: HashMap<String, CftVersion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KnownGoodVersions {
+    versions: Vec<CftVersion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CftVersion {
+    version: String,
+    revision: String,
+    downloads: HashMap<String, Vec<CftDownload>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CftDownload {
+    platform: String,
+    url: String,
+}
+
+/// Chrome for Testing 发布的构建：渠道别名解析为该渠道最近一个通过测试的版本，
+/// 其他版本号在完整的版本列表中匹配。这些版本保证有可下载的压缩包。
+pub(crate) struct CftReleases {
+    platform: Platform,
+    client: Http,
+    /// 除 `chrome` 外同时安装的程序。
+    extras: Vec<CftBinary>,
+    last_known_good: OnceCell<LastKnownGoodVersions>,
+    known_good: OnceCell<KnownGoodVersions>,
+    downloads: Downloads,
+    paths: Paths,
+}
+
+impl CftReleases {
+    pub(crate) fn init(
+        platform: Platform,
+        client: Http,
+        extras: Vec<CftBinary>,
+        downloads: Downloads,
+        paths: Paths,
+    ) -> Result<Self> {
+        cft_platform(platform).ok_or_else(|| {
+            FetchError::UnsupportedPlatform(format!(
+                "Chrome for Testing is not published for {} {}.",
+                platform.os().as_str(),
+                platform.arch().as_str()
+            ))
+        })?;
+        Ok(Self {
+            platform,
+            client,
+            extras,
+            last_known_good: OnceCell::new(),
+            known_good: OnceCell::new(),
+            downloads,
+            paths,
+        })
+    }
+
+    /// 版本列表随时在更新，别名需要解析到最新的版本，因此都不缓存，只在第一次匹配时获取。
+    async fn load<'c, T: DeserializeOwned>(
+        &self,
+        cell: &'c OnceCell<T>,
+        name: &str,
+        url: &str,
+    ) -> Result<&'c T> {
+        cell.get_or_try_init(|| async {
+            status!("==> retrieving chrome for testing {name} ...");
+            timings::time_async(Phase::History, async {
+                let response = self.client.get(url).await?;
+                if !response.is_success() {
+                    return Err(FetchError::Network(format!(
+                        "Retrieving {url} failed: {}",
+                        response.status()
+                    ))
+                    .into());
+                }
+                response.json::<T>().await
+            })
+            .await
+        })
+        .await
+    }
+
+    async fn find_channel(&self, alias: &str) -> Result<CftReleaseItem> {
+        let channel = channel_alias(alias)
+            .ok_or_else(|| anyhow!("{alias} is not a Chrome for Testing channel."))?
+            .as_constant();
+        let info = self
+            .load(
+                &self.last_known_good,
+                "last known good versions",
+                LAST_KNOWN_GOOD_URL,
+            )
+            .await?;
+        let info = info
+            .channels
+            .get(channel)
+            .ok_or_else(|| anyhow!("No last known good version for channel {channel}."))?;
+        self.item(info, Some(channel))
+    }
+
+    async fn find_versions(&self, version: &str) -> Result<Vec<CftReleaseItem>> {
+        let known_good = self
+            .load(&self.known_good, "known good versions", KNOWN_GOOD_URL)
+            .await?;
+        let spec = VersionSpec::parse(version);
+        // 早期版本只有 chrome 没有当前平台的压缩包，跳过这些版本。
+        Ok(known_good
+            .versions
+            .iter()
+            .filter(|info| spec.matches(&info.version))
+            .filter_map(|info| self.item(info, None).ok())
+            .collect())
+    }
+
+    fn item(&self, info: &CftVersion, channel: Option<&str>) -> Result<CftReleaseItem> {
+        let cft_platform = cft_platform(self.platform).unwrap_or_default();
+        let url = |binary: CftBinary| {
+            info.downloads
+                .get(binary.name())
+                .into_iter()
+                .flatten()
+                .find(|download| download.platform == cft_platform)
+                .map(|download| download.url.clone())
+        };
+        let chrome_url = url(CftBinary::Chrome).ok_or_else(|| {
+            anyhow!(
+                "Chrome for Testing {} has no download for {cft_platform}.",
+                info.version
+            )
+        })?;
+        let extras = self
+            .extras
+            .iter()
+            .map(|binary| (*binary, url(*binary)))
+            .collect();
+        Ok(CftReleaseItem {
+            version: info.version.clone(),
+            revision: info.revision.parse().ok(),
+            channel: channel.map(str::to_owned),
+            platform: self.platform,
+            chrome_url,
+            extras,
+            downloads: self.downloads.clone(),
+            paths: self.paths.clone(),
+        })
+    }
+}
+
+impl BrowserReleases for CftReleases {
+    fn match_version<'r>(&'r self, version: &str) -> ReleaseMatches<'r> {
+        let version = version.to_owned();
+        let matches = async move {
+            let items = match channel_alias(&version) {
+                Some(_) => self.find_channel(&version).await.map(|item| vec![item]),
+                None => self.find_versions(&version).await,
+            };
+            let matches: Vec<Result<Box<dyn BrowserReleaseItem>>> = match items {
+                Ok(items) => items
+                    .into_iter()
+                    .map(|item| Ok(Box::new(item) as Box<dyn BrowserReleaseItem>))
+                    .collect(),
+                Err(err) => vec![Err(err)],
+            };
+            stream::iter(matches)
+        };
+        stream::once(matches).flatten().boxed()
+    }
+}
+
+fn cft_platform(platform: Platform) -> Option<&'static str> {
+    match (platform.os(), platform.arch()) {
+        (Os::Windows, Arch::X86) => Some("win32"),
+        (Os::Windows, Arch::X86_64) => Some("win64"),
+        (Os::Linux, Arch::X86_64) => Some("linux64"),
+        (Os::Mac, Arch::X86_64) => Some("mac-x64"),
+        (Os::Mac, Arch::Arm64) => Some("mac-arm64"),
+        _ => None,
+    }
+}
+
+pub(crate) struct CftReleaseItem {
+    version: String,
+    revision: Option<usize>,
+    /// 通过渠道别名解析时的渠道。
+    channel: Option<String>,
+    platform: Platform,
+    chrome_url: String,
+    /// 同时安装的程序及其下载地址，该版本没有发布时为 `None`。
+    extras: Vec<(CftBinary, Option<String>)>,
+    downloads: Downloads,
+    paths: Paths,
+}
+
+#[async_trait]
+impl BrowserReleaseItem for CftReleaseItem {
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    async fn prefetch_requests(&self) -> Result<Vec<DownloadRequest>> {
+        let extras = self
+            .extras
+            .iter()
+            .filter_map(|(binary, url)| Some(self.request(*binary, url.as_deref()?)));
+        Ok(
+            std::iter::once(self.request(CftBinary::Chrome, &self.chrome_url))
+                .chain(extras)
+                .collect(),
+        )
+    }
+
+    async fn download(&self) -> Result<PathBuf> {
+        with_install_lock(&self.paths, "chrome", &self.version, self.install()).await
+    }
+
+    async fn plan(&self) -> Result<DownloadPlan> {
+        Ok(DownloadPlan {
+            requests: self.prefetch_requests().await?,
+            revision: None,
+            install_dir: self.paths.install_dir("chrome", &self.version),
+        })
+    }
+}
+
+impl CftReleaseItem {
+    async fn install(&self) -> Result<PathBuf> {
+        match &self.channel {
+            Some(channel) => status!(
+                "==> matched chrome for testing {} from channel {channel}",
+                self.version
+            ),
+            None => status!("==> matched chrome for testing {}", self.version),
+        }
+        let base_path = self
+            .install_binary(CftBinary::Chrome, &self.chrome_url)
+            .await?;
+        for (binary, url) in &self.extras {
+            let Some(url) = url else {
+                status!(
+                    "==> warning[cft]: chrome for testing {} has no {} for {}, skipping it",
+                    self.version,
+                    binary.name(),
+                    cft_platform(self.platform).unwrap_or_default()
+                );
+                continue;
+            };
+            let path = self.install_binary(*binary, url).await?;
+            status!("==> installed {} into {}", binary.name(), path.display());
+        }
+        Ok(base_path)
+    }
+
+    fn request(&self, binary: CftBinary, url: &str) -> DownloadRequest {
+        DownloadRequest::new(url).with_cache_key(
+            binary.name(),
+            &self.version,
+            cft_platform(self.platform).unwrap_or_default(),
+            url_file_name(url),
+        )
+    }
+
+    /// 下载并安装其中一个程序到 `<name>-<version>` 目录。
+    async fn install_binary(&self, binary: CftBinary, url: &str) -> Result<PathBuf> {
+        let name = binary.name();
+        let content = self
+            .downloads
+            .fetch(&self.request(binary, url))
+            .await?
+            .bytes()?;
+
+        let temp_path = self.paths.temp_install_dir(name, &self.version);
+        if temp_path.exists() {
+            std::fs::remove_dir_all(&temp_path)?;
+        }
+        std::fs::create_dir_all(&temp_path)?;
+        {
+            let temp_path = temp_path.clone();
+            blocking(move || {
+                timings::time(Phase::Extract, || extract_archive(content, &temp_path))
+            })
+            .await?;
+        }
+
+        // 压缩包中的文件位于 <name>-<platform> 目录下。
+        let mut entries = std::fs::read_dir(&temp_path)?.collect::<Result<Vec<_>, _>>()?;
+        let content_path = match entries.as_slice() {
+            [entry] if entry.file_type()?.is_dir() => entries.remove(0).path(),
+            _ => temp_path.clone(),
+        };
+        let metadata = InstallMetadata {
+            platform: cft_platform(self.platform).map(str::to_owned),
+            arch: Some(self.platform.arch().as_str().to_owned()),
+            channel: self.channel.clone(),
+            revision: self.revision,
+            executable: Some(binary.executable(self.platform.os()).to_owned()),
+            ..InstallMetadata::new(name, &self.version)
+        };
+        mark_incomplete(&content_path)?;
+        metadata.write(&content_path)?;
+
+        let base_path = self.paths.install_dir(name, &self.version);
+        if base_path.exists() {
+            std::fs::remove_dir_all(&base_path)?;
+        }
+        std::fs::create_dir_all(&self.paths.output_dir)?;
+        move_dir(&content_path, &base_path)?;
+        mark_complete(&base_path)?;
+        if temp_path.exists() {
+            std::fs::remove_dir_all(&temp_path)?;
+        }
+        Ok(base_path)
+    }
+}
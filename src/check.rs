@@ -0,0 +1,202 @@
+use std::{
+    io::Read,
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+
+use crate::metadata::InstallMetadata;
+
+const CHECK_URL: &str = "data:text/html,<title>fetchbrowser</title>";
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 常见的启动失败特征及对应的建议。
+const FAILURE_SIGNATURES: &[(&str, &str)] = &[
+    (
+        r"error while loading shared libraries: ([^\s:]+)",
+        "missing shared library {1}, install the package providing it (e.g. `apt-file search {1}`).",
+    ),
+    (
+        r"No usable sandbox|Running as root without --no-sandbox",
+        "the sandbox is unavailable, run as a non-root user, enable user namespaces, or pass --no-sandbox.",
+    ),
+    (
+        r"Missing X server or \$DISPLAY|cannot open display",
+        "no display is available, make sure the browser runs headless or start it under xvfb-run.",
+    ),
+    (
+        r"Exec format error|cannot execute binary file",
+        "the executable was built for another OS or architecture, check --os.",
+    ),
+    (
+        r"Permission denied",
+        "the executable is not runnable, check its permissions (chmod +x) and noexec mounts.",
+    ),
+];
+
+/// 以无头模式启动安装目录中的浏览器，检查能否正常运行。
+pub(crate) fn check_install(install_dir: &Path) -> Result<()> {
+    let metadata = InstallMetadata::read(install_dir).map_err(|err| {
+        anyhow!(
+            "Reading the metadata of {} failed: {err}",
+            install_dir.display()
+        )
+    })?;
+    let executable = metadata
+        .executable
+        .as_deref()
+        .map(|executable| install_dir.join(executable))
+        .ok_or_else(|| anyhow!("No executable recorded in the metadata."))?;
+    // Firefox 默认把截图写到当前目录，这里写到临时目录，检查完删除，不留在安装目录中。
+    let screenshot =
+        std::env::temp_dir().join(format!("fetchbrowser-check-{}.png", std::process::id()));
+    let args: Vec<_> = match metadata.browser.as_str() {
+        "firefox" | "firefox-devedition" => vec![
+            "-headless".into(),
+            "-screenshot".into(),
+            screenshot.clone().into_os_string(),
+            CHECK_URL.into(),
+        ],
+        _ => ["--headless", "--disable-gpu", "--dump-dom", CHECK_URL]
+            .map(Into::into)
+            .into(),
+    };
+    status!(
+        "==> launching {} {}",
+        executable.display(),
+        args.iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+
+    let child = Command::new(&executable)
+        .args(&args)
+        .current_dir(install_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            // 没有权限、格式不对等情况在启动时就会失败，同样给出建议。
+            for suggestion in suggestions(&err.to_string()) {
+                status!("==> hint: {suggestion}");
+            }
+            return Err(anyhow!("Launching {} failed: {err}", executable.display()));
+        }
+    };
+    let result = wait_with_timeout(child, CHECK_TIMEOUT);
+    let _ = std::fs::remove_file(&screenshot);
+    let (status, stdout, stderr) = result?;
+    let success = status.is_some_and(|status| status.success());
+    status!(
+        "==> status: {}",
+        status.map_or_else(
+            || format!("timed out after {}s", CHECK_TIMEOUT.as_secs()),
+            |status| status.to_string(),
+        )
+    );
+    for (name, output) in [("stdout", &stdout), ("stderr", &stderr)] {
+        let lines: Vec<_> = output.lines().collect();
+        if !lines.is_empty() {
+            status!("==> {name}:");
+            for line in &lines[lines.len().saturating_sub(20)..] {
+                status!("    {line}");
+            }
+        }
+    }
+
+    let suggestions = suggestions(&format!("{stdout}\n{stderr}"));
+    for suggestion in &suggestions {
+        status!("==> hint: {suggestion}");
+    }
+    if success && suggestions.is_empty() {
+        status!("==> {} {} starts fine.", metadata.browser, metadata.version);
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} {} failed to start headless.",
+            metadata.browser,
+            metadata.version
+        ))
+    }
+}
+
+/// 按 [`FAILURE_SIGNATURES`] 匹配输出或启动错误，返回对应的建议。
+fn suggestions(output: &str) -> Vec<String> {
+    FAILURE_SIGNATURES
+        .iter()
+        .filter_map(|(pattern, suggestion)| {
+            let captures = Regex::new(pattern).ok()?.captures(output)?;
+            Some(suggestion.replace(
+                "{1}",
+                captures.get(1).map(|m| m.as_str()).unwrap_or_default(),
+            ))
+        })
+        .collect()
+}
+
+fn wait_with_timeout(
+    mut child: Child,
+    timeout: Duration,
+) -> Result<(Option<std::process::ExitStatus>, String, String)> {
+    let read_all = |mut reader: Box<dyn Read + Send>| {
+        thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = reader.read_to_end(&mut output);
+            String::from_utf8_lossy(&output).into_owned()
+        })
+    };
+    let stdout = child.stdout.take().map(|r| read_all(Box::new(r)));
+    let stderr = child.stderr.take().map(|r| read_all(Box::new(r)));
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if start.elapsed() > timeout {
+            child.kill()?;
+            child.wait()?;
+            break None;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+    let join = |handle: Option<thread::JoinHandle<String>>| {
+        handle
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default()
+    };
+    Ok((status, join(stdout), join(stderr)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_errors_get_hints() {
+        let permission = std::io::Error::from_raw_os_error(13).to_string();
+        assert_eq!(
+            suggestions(&permission),
+            [FAILURE_SIGNATURES[4].1.to_owned()]
+        );
+        let format = std::io::Error::from_raw_os_error(8).to_string();
+        assert_eq!(suggestions(&format), [FAILURE_SIGNATURES[3].1.to_owned()]);
+    }
+
+    #[test]
+    fn missing_libraries_are_named() {
+        let output = "chrome: error while loading shared libraries: libnss3.so: cannot open shared object file";
+        assert_eq!(
+            suggestions(output),
+            ["missing shared library libnss3.so, install the package providing it (e.g. `apt-file search libnss3.so`)."]
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fs::File,
+    io::Read,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha512};
+
+use crate::{
+    metadata::INCOMPLETE_MARKER,
+    utils::{sha256_file, walk_files},
+};
+
+/// `--no-verify` 关闭下载后的摘要校验。
+static VERIFY: AtomicBool = AtomicBool::new(true);
+
+pub(crate) fn set_verify(verify: bool) {
+    VERIFY.store(verify, Ordering::Relaxed);
+}
+
+pub(crate) fn verify_enabled() -> bool {
+    VERIFY.load(Ordering::Relaxed)
+}
+
+/// 上游发布的下载文件摘要。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Checksum {
+    /// 十六进制，来自 Firefox 发布目录下的 SHA512SUMS。
+    Sha512(String),
+    /// base64 编码的大端序 CRC32C，来自 GCS 存储对象的 `crc32c` 字段。
+    Crc32c(String),
+}
+
+impl Checksum {
+    /// 校验下载的文件，不一致时返回说明。
+    pub(crate) fn mismatch(&self, path: &Path) -> Result<Option<String>> {
+        // 十六进制不区分大小写，base64 区分大小写。
+        let (name, expected, actual, matches) = match self {
+            Checksum::Sha512(expected) => {
+                let mut hasher = Sha512::new();
+                std::io::copy(&mut File::open(path)?, &mut hasher)?;
+                let actual = format!("{:x}", hasher.finalize());
+                let matches = actual.eq_ignore_ascii_case(expected);
+                ("sha512", expected, actual, matches)
+            }
+            Checksum::Crc32c(expected) => {
+                let actual = base64::encode(crc32c_file(path)?.to_be_bytes());
+                let matches = actual == *expected;
+                ("crc32c", expected, actual, matches)
+            }
+        };
+        Ok(match matches {
+            true => None,
+            false => Some(format!("expected {name} {expected}, got {actual}")),
+        })
+    }
+}
+
+fn crc32c_file(path: &Path) -> Result<u32> {
+    // Castagnoli 多项式（反转形式）。
+    let table: Vec<u32> = (0..256u32)
+        .map(|mut crc| {
+            for _ in 0..8 {
+                crc = match crc & 1 {
+                    1 => (crc >> 1) ^ 0x82f6_3b78,
+                    _ => crc >> 1,
+                };
+            }
+            crc
+        })
+        .collect();
+    let mut crc = !0u32;
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        for byte in &buf[..len] {
+            crc = table[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8);
+        }
+    }
+    Ok(!crc)
+}
+
+/// 解析 `sha512sum` 格式的 SHA512SUMS，返回 `path` 对应的摘要。
+pub(crate) fn find_checksum(sums: &str, path: &str) -> Option<Checksum> {
+    sums.lines().find_map(|line| {
+        let (hash, file) = line.split_once("  ")?;
+        (file == path).then(|| Checksum::Sha512(hash.to_owned()))
+    })
+}
+
+/// 安装目录中记录各文件摘要的文件，格式与 `sha256sum` 的输出相同。
+const CHECKSUMS_FILE: &str = "SHA256SUMS";
+
+/// 安装完成后仍可能被修改的文件，不记录摘要。
+const UNTRACKED_FILES: [&str; 3] = [CHECKSUMS_FILE, "metadata.json", INCOMPLETE_MARKER];
+
+/// 记录安装目录中普通文件的摘要。符号链接（例如 macOS 应用中的 `Versions/Current`）不是文件内容，
+/// 跟随它会把目标目录再计算一遍，不记录。
+pub(crate) fn write_checksums(dir: &Path) -> Result<()> {
+    let mut lines = String::new();
+    for path in walk_files(dir)? {
+        let relative = relative_path(dir, &path)?;
+        if UNTRACKED_FILES.contains(&relative.as_str()) {
+            continue;
+        }
+        lines.push_str(&format!("{}  {relative}\n", sha256_file(&path)?));
+    }
+    std::fs::write(dir.join(CHECKSUMS_FILE), lines)?;
+    Ok(())
+}
+
+/// 从 SHA256SUMS 中随机抽取 `count` 个文件重新计算摘要，不一致或文件缺失时返回错误。
+///
+/// 没有 SHA256SUMS 的旧安装无法检查，视为通过。
+pub(crate) fn spot_check(dir: &Path, count: usize) -> Result<()> {
+    let path = dir.join(CHECKSUMS_FILE);
+    if !path.exists() {
+        status!(
+            "==> {} has no {CHECKSUMS_FILE}, skipping the spot check",
+            dir.display()
+        );
+        return Ok(());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    let mut entries: Vec<(&str, &str)> = content
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .collect();
+    // 不引入随机数依赖，以当前时间为种子做一次部分洗牌即可。
+    let mut seed = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+    let count = count.min(entries.len());
+    for i in 0..count {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let j = i + (seed >> 33) as usize % (entries.len() - i);
+        entries.swap(i, j);
+    }
+    for (expected, relative) in &entries[..count] {
+        let file = dir.join(relative);
+        if !file.exists() {
+            return Err(anyhow!("{relative} is missing"));
+        }
+        let actual = sha256_file(&file)?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(anyhow!(
+                "{relative} has sha256 {actual}, {expected} was recorded"
+            ));
+        }
+    }
+    status!(
+        "==> spot-checked {count} of {} files in {}",
+        entries.len(),
+        dir.display()
+    );
+    Ok(())
+}
+
+fn relative_path(dir: &Path, path: &Path) -> Result<String> {
+    Ok(path
+        .strip_prefix(dir)?
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_symlinks_are_not_hashed() {
+        let dir =
+            std::env::temp_dir().join(format!("fetchbrowser-checksums-{}", std::process::id()));
+        let versions = dir.join("Versions");
+        std::fs::create_dir_all(versions.join("114")).unwrap();
+        std::fs::write(versions.join("114").join("lib"), "lib").unwrap();
+        std::os::unix::fs::symlink("114", versions.join("Current")).unwrap();
+
+        let written = write_checksums(&dir);
+        let sums = std::fs::read_to_string(dir.join(CHECKSUMS_FILE));
+        let checked = spot_check(&dir, 10);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        written.unwrap();
+        let sums = sums.unwrap();
+        let files: Vec<_> = sums
+            .lines()
+            .filter_map(|line| line.split_once("  "))
+            .map(|(_, path)| path)
+            .collect();
+        assert_eq!(files, ["Versions/114/lib"]);
+        checked.unwrap();
+    }
+}
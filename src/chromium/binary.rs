@@ -0,0 +1,44 @@
+use std::{path::Path, process::Command};
+
+use regex::Regex;
+
+use crate::platform::{Os, Platform};
+
+/// 从解压出的文件中读取浏览器实际的版本号，无法确定时返回 `None`。
+///
+/// Windows 包中 chrome.exe 旁有一个以版本号命名的目录，macOS 包读取 Info.plist，
+/// Linux 包只能运行 `chrome --version`，因此仅在 Linux 主机上检测。
+pub(crate) fn detect_binary_version(install_dir: &Path, platform: Platform) -> Option<String> {
+    let version = Regex::new(r"\d+\.\d+\.\d+\.\d+").unwrap();
+    match platform.os() {
+        Os::Windows => std::fs::read_dir(install_dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .find(|name| version.find(name).is_some_and(|m| m.as_str() == name)),
+        Os::Mac => {
+            let plist =
+                std::fs::read_to_string(install_dir.join("Chromium.app/Contents/Info.plist"))
+                    .ok()?;
+            let regex =
+                Regex::new(r"<key>CFBundleShortVersionString</key>\s*<string>([^<]+)</string>")
+                    .unwrap();
+            Some(regex.captures(&plist)?[1].to_owned())
+        }
+        Os::Linux if cfg!(target_os = "linux") => {
+            let output = Command::new(install_dir.join("chrome"))
+                .arg("--version")
+                .output()
+                .ok()?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            Some(version.find(&stdout)?.as_str().to_owned())
+        }
+        Os::Linux => None,
+    }
+}
+
+/// 快照由主干构建，版本号形如 `114.0.5735.0`，只比较主版本号。
+pub(crate) fn same_milestone(a: &str, b: &str) -> bool {
+    a.split('.').next() == b.split('.').next()
+}
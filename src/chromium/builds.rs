@@ -1,78 +1,292 @@
-use std::{fs::File, io::BufReader};
-
-use anyhow::{anyhow, Result};
-use reqwest::blocking::Client;
+use anyhow::Result;
+use futures::future::try_join_all;
 use serde::{Deserialize, Serialize};
 
-use crate::{platform::Platform, utils::get_cached_file_path};
+use crate::{
+    cache::CacheStatus,
+    common::BuildVariant,
+    error::FetchError,
+    http::Http,
+    metadata::SnapshotObject,
+    metrics,
+    paths::Paths,
+    platform::Platform,
+    timings::{self, Phase},
+};
+
+/// 发布的分支位置之后多少个 revision 以内的快照可以视为该版本的构建。
+pub(crate) const SNAPSHOT_TOLERANCE: usize = 120;
 
-pub(crate) struct ChromiumBuilds(Vec<String>);
+pub(crate) struct ChromiumBuilds {
+    variant: BuildVariant,
+    prefix: &'static str,
+    list: Vec<String>,
+    /// 列表来自缓存文件，而不是刚刚抓取的。
+    cached: bool,
+}
 
 impl ChromiumBuilds {
-    pub(crate) fn init(platform: Platform, client: Client) -> Result<Self> {
-        let prefix = platform.prefix();
-        let builds_json_path = get_cached_file_path(&format!("builds-{prefix}.json"))?;
-        let build_list = if std::fs::try_exists(&builds_json_path).unwrap_or_default() {
-            println!("==> using cached builds: {}", builds_json_path.display());
-            serde_json::from_reader(BufReader::new(File::open(&builds_json_path)?))?
+    pub(crate) async fn init(
+        platform: Platform,
+        variant: BuildVariant,
+        client: Http,
+        paths: &Paths,
+    ) -> Result<Self> {
+        let prefix = variant.prefix(platform).ok_or_else(|| {
+            FetchError::UnsupportedPlatform(format!(
+                "No {} builds are published for {}.",
+                variant.browser_name(),
+                platform.prefix()
+            ))
+        })?;
+        let builds_json_path = paths.cached_file(&format!("builds-{prefix}.json"))?;
+        let cached_list = paths.cache.read_verified(&builds_json_path)?;
+        let cached = cached_list.is_some();
+        metrics::cache_lookup(cached);
+        let build_list = if let Some(build_list) = cached_list {
+            status!("==> using cached builds: {}", builds_json_path.display());
+            build_list
         } else {
-            println!("==> retrieving builds ...");
-            let pages = ChromiumBuildsPage::new(prefix, client)?;
-            let mut unwrapped_page_list = Vec::new();
-            for page in pages {
-                unwrapped_page_list.push(page?);
-            }
-            let builds: Vec<String> = unwrapped_page_list.into_iter().flatten().collect();
-            std::fs::write(&builds_json_path, serde_json::to_string(&builds)?)?;
+            status!("==> retrieving builds ...");
+            let builds = fetch_all_builds(variant, prefix, &client).await?;
+            paths
+                .cache
+                .write_signed(&builds_json_path, serde_json::to_string(&builds)?)?;
             builds
         };
-        Ok(Self(build_list))
+        let mut builds = Self {
+            variant,
+            prefix,
+            list: build_list,
+            cached,
+        };
+        // 快照列表只会增加，过期时只获取更新的快照。
+        if cached && paths.cache.expired(&builds_json_path) {
+            if let Err(err) = builds.refresh_newer(client, paths).await {
+                status!(
+                    "==> warning[cache-stale]: refreshing {} failed, using the expired cache: {err}",
+                    builds_json_path.display()
+                );
+            }
+        }
+        Ok(builds)
+    }
+
+    /// 缓存中最新的构建早于 `pos` 时，只抓取更新的构建并追加到缓存，不重新抓取整个 bucket。
+    pub(crate) async fn refresh_if_older(
+        &mut self,
+        pos: usize,
+        client: Http,
+        paths: &Paths,
+    ) -> Result<()> {
+        match self.published_range() {
+            Some((_, latest)) if self.cached && latest < pos => {
+                self.refresh_newer(client, paths).await
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn refresh_newer(&mut self, client: Http, paths: &Paths) -> Result<()> {
+        let Some((_, latest)) = self.published_range() else {
+            return Ok(());
+        };
+        status!("==> cached builds end at r{latest}, retrieving newer builds ...");
+        let newer = timings::time_async(Phase::Builds, self.fetch_newer(client)).await?;
+        if newer.is_empty() {
+            return Ok(());
+        }
+        status!("==> found {} newer builds", newer.len());
+        self.list.extend(newer);
+        let builds_json_path = paths.cached_file(&format!("builds-{}.json", self.prefix))?;
+        paths
+            .cache
+            .write_signed(&builds_json_path, serde_json::to_string(&self.list)?)?;
+        Ok(())
+    }
+
+    pub(crate) fn prefix(&self) -> &'static str {
+        self.prefix
+    }
+
+    /// 用 `startOffset` 检查上游是否有比缓存中最新的 revision 更新的构建，只需一两次请求。
+    pub(crate) async fn verify_cache(
+        platform: Platform,
+        variant: BuildVariant,
+        client: Http,
+        paths: &Paths,
+    ) -> Result<CacheStatus> {
+        let Some(prefix) = variant.prefix(platform) else {
+            return Ok(CacheStatus::Missing);
+        };
+        let builds_json_path = paths.cache_dir.join(format!("builds-{prefix}.json"));
+        let Some(list) = paths.cache.read_verified(&builds_json_path)? else {
+            return Ok(CacheStatus::Missing);
+        };
+        let builds = Self {
+            variant,
+            prefix,
+            list,
+            cached: true,
+        };
+        let Some((_, latest)) = builds.revisions().last().copied() else {
+            return Ok(CacheStatus::Diverged("no builds are cached".to_owned()));
+        };
+        let newer = builds.fetch_newer(client).await?;
+        Ok(match newer.len() {
+            0 => CacheStatus::Fresh,
+            count => CacheStatus::Diverged(format!(
+                "{count} builds newer than the cached r{latest} are published"
+            )),
+        })
+    }
+
+    /// 从缓存中最新的构建开始列出 bucket，返回更新的构建。
+    ///
+    /// 名称按字典序排列，位数相同的新 revision 紧跟在起点之后，
+    /// 之后才是位数较少的旧 revision，因此遇到不含新 revision 的一页即可停止。
+    async fn fetch_newer(&self, client: Http) -> Result<Vec<String>> {
+        let Some((latest_name, latest)) = self.revisions().last().copied() else {
+            return Ok(Vec::new());
+        };
+        let mut pages = ChromiumBuildsPage::new(self.variant.bucket(), self.prefix, client)?
+            .with_start_offset(latest_name);
+        let mut newer = Vec::new();
+        while let Some(page) = pages.next_page().await {
+            let page = Self {
+                variant: self.variant,
+                prefix: self.prefix,
+                list: page?,
+                cached: false,
+            };
+            let found: Vec<_> = page
+                .revisions()
+                .into_iter()
+                .filter(|(_, rev)| *rev > latest)
+                .map(|(name, _)| name.clone())
+                .collect();
+            if found.is_empty() {
+                break;
+            }
+            newer.extend(found);
+        }
+        Ok(newer)
+    }
+
+    pub(crate) fn find(&self, find_pos: usize) -> Option<(&String, usize)> {
+        self.revisions()
+            .into_iter()
+            .find(|build| build.1 >= find_pos)
+            .filter(|build| build.1 - find_pos <= SNAPSHOT_TOLERANCE)
+    }
+
+    /// `pos` 之前和之后最近的快照，`find` 找不到时用于说明原因。
+    pub(crate) fn nearest(&self, pos: usize) -> (Option<usize>, Option<usize>) {
+        let revisions = self.revisions();
+        let before = revisions.iter().rev().find(|build| build.1 < pos);
+        let after = revisions.iter().find(|build| build.1 >= pos);
+        (before.map(|build| build.1), after.map(|build| build.1))
+    }
+
+    /// 该平台发布过快照的最早和最晚的 revision。
+    pub(crate) fn published_range(&self) -> Option<(usize, usize)> {
+        let list = self.revisions();
+        Some((list.first()?.1, list.last()?.1))
     }
 
-    pub(crate) fn find<'a>(&'a self, find_pos: usize, os_prefix: &str) -> Option<&'a String> {
+    /// 快照按 `<prefix>/<rev>/` 目录发布，ASAN 构建则是 `<prefix>/asan-<prefix>-<rev>.zip` 文件。
+    pub(crate) fn revisions(&self) -> Vec<(&String, usize)> {
         let mut list: Vec<_> = self
-            .0
+            .list
             .iter()
             .filter_map(|build| {
                 let split: Vec<_> = build.split('/').collect();
-                match split.as_slice() {
-                    &[prefix, rev, empty] if prefix == os_prefix && empty.is_empty() => {
-                        rev.parse::<usize>().ok().map(|rev| (build, rev))
+                let rev = match (self.variant, split.as_slice()) {
+                    (BuildVariant::Snapshot, &[prefix, rev, empty])
+                        if prefix == self.prefix && empty.is_empty() =>
+                    {
+                        rev
                     }
-                    _ => None,
-                }
+                    (BuildVariant::Asan, &[prefix, name]) if prefix == self.prefix => name
+                        .strip_prefix("asan-")?
+                        .strip_prefix(prefix)?
+                        .strip_prefix('-')?
+                        .strip_suffix(".zip")?,
+                    _ => return None,
+                };
+                rev.parse::<usize>().ok().map(|rev| (build, rev))
             })
             .collect();
-        list.sort_by(|a, b| a.1.cmp(&b.1));
-        list.into_iter()
-            .find(|build| build.1 >= find_pos)
-            .filter(|build| (build.1 - find_pos <= 120))
-            .map(|b| b.0)
+        list.sort_by_key(|build| build.1);
+        list
     }
 }
 
+/// 按 revision 的首位数字把 bucket 分成 9 段，各段的分页互不依赖，可以同时请求。
+///
+/// 每一页都依赖上一页的 `nextPageToken`，整个 bucket 只能逐页抓取；分段后各段的请求同时进行，
+/// 总耗时取决于最长的一段。名称不以数字开头的对象（如 `LAST_CHANGE`）不是快照，不需要列出。
+async fn fetch_all_builds(
+    variant: BuildVariant,
+    prefix: &'static str,
+    client: &Http,
+) -> Result<Vec<String>> {
+    let shards = (1..=9).map(|digit| async move {
+        let shard = match variant {
+            BuildVariant::Snapshot => digit.to_string(),
+            BuildVariant::Asan => format!("asan-{prefix}-{digit}"),
+        };
+        let mut pages = ChromiumBuildsPage::new(variant.bucket(), prefix, client.clone())?
+            .with_name_prefix(&shard);
+        let mut list = Vec::new();
+        while let Some(page) = pages.next_page().await {
+            list.extend(page?);
+        }
+        Ok::<_, anyhow::Error>(list)
+    });
+    Ok(try_join_all(shards).await?.into_iter().flatten().collect())
+}
+
 pub(crate) struct ChromiumBuildsPage {
+    bucket: &'static str,
     prefix: &'static str,
+    /// 只列出 `<prefix>/<name_prefix>` 开头的对象。
+    name_prefix: String,
+    start_offset: Option<String>,
     next_page_token: Option<String>,
     done: bool,
-    client: Client,
+    client: Http,
 }
 
 impl ChromiumBuildsPage {
-    pub fn new(prefix: &'static str, client: Client) -> Result<Self> {
+    pub fn new(bucket: &'static str, prefix: &'static str, client: Http) -> Result<Self> {
         Ok(Self {
             next_page_token: None,
+            name_prefix: String::new(),
+            start_offset: None,
             done: false,
+            bucket,
             prefix,
             client,
         })
     }
-}
 
-impl Iterator for ChromiumBuildsPage {
-    type Item = Result<Vec<String>>;
+    /// 只列出 `<prefix>/` 下以 `name_prefix` 开头的对象，见 [`fetch_all_builds`]。
+    pub(crate) fn with_name_prefix(mut self, name_prefix: &str) -> Self {
+        self.name_prefix = name_prefix.to_owned();
+        self
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// 只列出名称不小于 `start_offset` 的对象（按字典序）。
+    pub(crate) fn with_start_offset(mut self, start_offset: &str) -> Self {
+        self.start_offset = Some(start_offset.to_owned());
+        self
+    }
+}
+
+impl ChromiumBuildsPage {
+    /// 下一页的对象名称，列完时返回 `None`。
+    pub(crate) async fn next_page(&mut self) -> Option<Result<Vec<String>>> {
         if self.done {
             None
         } else {
@@ -81,18 +295,24 @@ impl Iterator for ChromiumBuildsPage {
                 .as_ref()
                 .map(|t| format!("&pageToken={t}"))
                 .unwrap_or_default();
-            let url = format!("https://www.googleapis.com/storage/v1/b/chromium-browser-snapshots/o?delimiter=/&prefix={}/&fields=items(kind,mediaLink,metadata,name,size,updated),kind,prefixes,nextPageToken{}", self.prefix, next_page_token);
-
-            let prefixes = self
-                .client
-                .get(&url)
-                .send()
-                .map_err(|err| anyhow!("请求 {} 时出错：{:?}", url, err))
-                .and_then(|response| {
-                    let page: ChromiumBuildPage = serde_json::from_reader(response)?;
+            let start_offset = self
+                .start_offset
+                .as_ref()
+                .map(|offset| format!("&startOffset={offset}"))
+                .unwrap_or_default();
+            let url = format!("https://www.googleapis.com/storage/v1/b/{}/o?delimiter=/&prefix={}/{}{start_offset}&fields=items(kind,mediaLink,metadata,name,size,updated),kind,prefixes,nextPageToken{}", self.bucket, self.prefix, self.name_prefix, next_page_token);
+
+            let prefixes = get_page(&self.client, &url)
+                .await
+                // 保留错误链，命令行据此把网络错误归类。
+                .map_err(|err| err.context(format!("请求 {url} 时出错")))
+                .map(|page| {
                     self.next_page_token = page.next_page_token;
                     self.done = self.next_page_token.is_none();
-                    Ok(page.prefixes)
+                    // 快照以目录（prefixes）发布，ASAN 构建以文件（items）发布。
+                    let mut names = page.prefixes;
+                    names.extend(page.items.into_iter().map(|item| item.name));
+                    names
                 });
 
             prefixes
@@ -102,21 +322,53 @@ impl Iterator for ChromiumBuildsPage {
     }
 }
 
-pub(crate) fn fetch_build_detail(
+pub(crate) async fn fetch_build_detail(
+    bucket: &str,
     prefix: &str,
-    client: &Client,
+    client: &Http,
 ) -> Result<Vec<GoogleApiStorageObject>> {
-    let url = format!("https://www.googleapis.com/storage/v1/b/chromium-browser-snapshots/o?delimiter=/&prefix={prefix}&fields=items(kind,mediaLink,metadata,name,size,updated),kind,prefixes,nextPageToken");
-    println!("==> fetching history {url} ...");
-    let response = client.get(url).send()?;
-    let build_detail: ChromiumBuildPage = serde_json::from_reader(response)?;
-    println!("==> files:");
+    let url = format!("https://www.googleapis.com/storage/v1/b/{bucket}/o?delimiter=/&prefix={prefix}&fields=items(kind,mediaLink,metadata,name,size,updated,crc32c,generation),kind,prefixes,nextPageToken");
+    status!("==> fetching history {url} ...");
+    let build_detail = timings::time_async(Phase::Detail, get_page(client, &url)).await?;
+    status!("==> files:");
     for file in &build_detail.items {
-        println!("    {}", file.name);
+        status!("    {}", file.name);
     }
     Ok(build_detail.items)
 }
 
+/// 不带 `delimiter` 列出 `prefix` 下的所有对象（包括子目录中的），没有对象时返回空列表。
+///
+/// 已知 revision 时用 `<Platform>/<rev>/` 直接探测快照，不必抓取整个 bucket 的快照列表。
+pub(crate) async fn list_objects(
+    bucket: &str,
+    prefix: &str,
+    client: &Http,
+) -> Result<Vec<GoogleApiStorageObject>> {
+    let mut objects = Vec::new();
+    let mut page_token = String::new();
+    loop {
+        let url = format!("https://www.googleapis.com/storage/v1/b/{bucket}/o?prefix={prefix}&fields=items(kind,mediaLink,metadata,name,size,updated,crc32c,generation),kind,nextPageToken{page_token}");
+        let page = timings::time_async(Phase::Detail, get_page(client, &url)).await?;
+        objects.extend(page.items);
+        match page.next_page_token {
+            Some(token) => page_token = format!("&pageToken={token}"),
+            None => return Ok(objects),
+        }
+    }
+}
+
+/// 获取一页对象列表，非 2xx 的响应（例如限流时的错误页）不当作列表解析。
+async fn get_page(client: &Http, url: &str) -> Result<ChromiumBuildPage> {
+    let response = client.get(url).await?;
+    if !response.is_success() {
+        return Err(
+            FetchError::Network(format!("Retrieving {url} failed: {}", response.status())).into(),
+        );
+    }
+    response.json().await
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct ChromiumBuildPage {
@@ -128,7 +380,7 @@ pub(crate) struct ChromiumBuildPage {
     pub(crate) items: Vec<GoogleApiStorageObject>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct GoogleApiStorageObject {
     pub(crate) kind: String,
@@ -136,4 +388,161 @@ pub(crate) struct GoogleApiStorageObject {
     pub(crate) name: String,
     pub(crate) size: String,
     pub(crate) updated: String,
+    /// base64 编码的 CRC32C，只在获取单个快照的文件列表时请求。
+    pub(crate) crc32c: Option<String>,
+    /// 对象的版本号，对象被覆盖后改变，同样只在获取单个快照的文件列表时请求。
+    pub(crate) generation: Option<String>,
+}
+
+impl From<&GoogleApiStorageObject> for SnapshotObject {
+    fn from(object: &GoogleApiStorageObject) -> Self {
+        Self {
+            name: object.name.clone(),
+            size: object.size.parse().ok(),
+            updated: object.updated.clone(),
+            media_link: object.media_link.clone(),
+            generation: object.generation.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{error::ErrorCategory, http::MockTransport};
+
+    use super::*;
+
+    const BUCKET: &str = "chromium-browser-snapshots";
+
+    fn page_url(token: Option<&str>) -> String {
+        let token = token.map(|t| format!("&pageToken={t}")).unwrap_or_default();
+        format!("https://www.googleapis.com/storage/v1/b/{BUCKET}/o?delimiter=/&prefix=Linux_x64/&fields=items(kind,mediaLink,metadata,name,size,updated),kind,prefixes,nextPageToken{token}")
+    }
+
+    fn builds(variant: BuildVariant, prefix: &'static str, list: &[&str]) -> ChromiumBuilds {
+        ChromiumBuilds {
+            variant,
+            prefix,
+            list: list.iter().map(|name| name.to_string()).collect(),
+            cached: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_follow_next_page_token() {
+        let mock = Arc::new(MockTransport::default());
+        mock.respond(
+            &page_url(None),
+            200,
+            r#"{"kind":"storage#objects","prefixes":["Linux_x64/1000/","Linux_x64/1100/"],"nextPageToken":"t1"}"#,
+        );
+        mock.respond(
+            &page_url(Some("t1")),
+            200,
+            r#"{"kind":"storage#objects","prefixes":["Linux_x64/900/"]}"#,
+        );
+        let mut pages = ChromiumBuildsPage::new(BUCKET, "Linux_x64", mock).unwrap();
+        let mut list = Vec::new();
+        while let Some(page) = pages.next_page().await {
+            list.push(page.unwrap());
+        }
+        let pages = list;
+        assert_eq!(pages.len(), 2);
+
+        let list: Vec<_> = pages.iter().flatten().map(String::as_str).collect();
+        let builds = builds(BuildVariant::Snapshot, "Linux_x64", &list);
+        let revisions: Vec<_> = builds.revisions().into_iter().map(|b| b.1).collect();
+        assert_eq!(revisions, [900, 1000, 1100]);
+    }
+
+    #[tokio::test]
+    async fn shards_are_listed_separately() {
+        let shard_url = |digit: usize, token: Option<&str>| {
+            let token = token.map(|t| format!("&pageToken={t}")).unwrap_or_default();
+            format!("https://www.googleapis.com/storage/v1/b/{BUCKET}/o?delimiter=/&prefix=Linux_x64/{digit}&fields=items(kind,mediaLink,metadata,name,size,updated),kind,prefixes,nextPageToken{token}")
+        };
+        let mock = Arc::new(MockTransport::default());
+        for digit in 1..=9 {
+            mock.respond(
+                &shard_url(digit, None),
+                200,
+                r#"{"kind":"storage#objects"}"#,
+            );
+        }
+        mock.respond(
+            &shard_url(1, None),
+            200,
+            r#"{"kind":"storage#objects","prefixes":["Linux_x64/1000/"],"nextPageToken":"t1"}"#,
+        );
+        mock.respond(
+            &shard_url(1, Some("t1")),
+            200,
+            r#"{"kind":"storage#objects","prefixes":["Linux_x64/1100/"]}"#,
+        );
+        mock.respond(
+            &shard_url(9, None),
+            200,
+            r#"{"kind":"storage#objects","prefixes":["Linux_x64/900/"]}"#,
+        );
+        let http: Http = mock;
+        let list = fetch_all_builds(BuildVariant::Snapshot, "Linux_x64", &http)
+            .await
+            .unwrap();
+        assert_eq!(
+            list,
+            ["Linux_x64/1000/", "Linux_x64/1100/", "Linux_x64/900/"]
+        );
+    }
+
+    #[tokio::test]
+    async fn non_success_status_is_a_network_error() {
+        let mock = Arc::new(MockTransport::default());
+        mock.respond(&page_url(None), 503, "Service Unavailable");
+        let err = ChromiumBuildsPage::new(BUCKET, "Linux_x64", mock)
+            .unwrap()
+            .next_page()
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(ErrorCategory::of(&err), ErrorCategory::Network);
+    }
+
+    #[tokio::test]
+    async fn malformed_page_is_an_error() {
+        let mock = Arc::new(MockTransport::default());
+        mock.respond(&page_url(None), 200, "<html>rate limited</html>");
+        let mut pages = ChromiumBuildsPage::new(BUCKET, "Linux_x64", mock).unwrap();
+        assert!(pages.next_page().await.unwrap().is_err());
+    }
+
+    #[test]
+    fn find_stays_within_tolerance() {
+        let builds = builds(
+            BuildVariant::Snapshot,
+            "Linux_x64",
+            &["Linux_x64/1000/", "Linux_x64/1300/", "Mac/1010/"],
+        );
+        assert_eq!(builds.find(950).map(|b| b.1), Some(1000));
+        assert_eq!(builds.find(1000).map(|b| b.1), Some(1000));
+        assert_eq!(builds.find(1100), None);
+        assert_eq!(builds.nearest(1100), (Some(1000), Some(1300)));
+        assert_eq!(builds.published_range(), Some((1000, 1300)));
+    }
+
+    #[test]
+    fn asan_revisions_come_from_file_names() {
+        let builds = builds(
+            BuildVariant::Asan,
+            "linux-release",
+            &[
+                "linux-release/asan-linux-release-1200.zip",
+                "linux-release/asan-linux-release-1100.zip",
+                "linux-release/readme.txt",
+            ],
+        );
+        let revisions: Vec<_> = builds.revisions().into_iter().map(|b| b.1).collect();
+        assert_eq!(revisions, [1100, 1200]);
+    }
 }
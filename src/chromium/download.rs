@@ -1,40 +1,109 @@
-use std::{fs::OpenOptions, io::copy, path::Path};
+use std::{
+    fs::{File, OpenOptions},
+    io::{copy, BufReader, Read},
+    path::Path,
+};
 
 use anyhow::anyhow;
-use reqwest::blocking::Client;
-use zip::read::read_zipfile_from_stream;
+use zip::{read::read_zipfile_from_stream, ZipArchive};
+
+use crate::{
+    deadline,
+    downloads::Downloaded,
+    extract::{extract_archive, set_mode},
+    progress::ExtractProgress,
+    timings::{self, Phase},
+    utils::{copy_dir, retry_locked},
+};
 
 use super::builds::GoogleApiStorageObject;
 
-pub(crate) fn download_chromium_zip_file(
-    zip_file: &GoogleApiStorageObject,
+/// `locales` 为空时保留所有语言包，否则只保留 `locales/` 下指定语言的 `.pak` 文件。
+fn is_wanted_locale(path: &str, locales: &[String]) -> bool {
+    match path.strip_prefix("locales/") {
+        Some(file_name) if !locales.is_empty() => locales
+            .iter()
+            .any(|locale| file_name.starts_with(&format!("{locale}.pak"))),
+        _ => true,
+    }
+}
+
+/// 上游测试程序的压缩包，例如 `chrome-linux-tests.zip`、`interactive_ui_tests.zip`。
+pub(crate) fn is_tests_artifact(file_name: &str) -> bool {
+    file_name.contains("interactive_ui_tests")
+        || (file_name.starts_with("chrome-") && file_name.ends_with("-tests.zip"))
+}
+
+pub(crate) fn extract_chromium_zip_file(
+    zip_file: &Downloaded,
     base_path: &Path,
-    client: &Client,
+    locales: &[String],
+    tests: bool,
 ) -> std::result::Result<(), anyhow::Error> {
-    // 开始下载压缩文件。
-    println!("==> downloading {}", zip_file.media_link);
-    let mut win_zip_response = client.get(&zip_file.media_link).send()?;
+    timings::time(Phase::Extract, || {
+        unzip_file(&zip_file.path, base_path, locales, tests)
+    })
+}
 
+/// 流式解压只能读到本地文件头，其中没有文件权限，解压后再按中央目录补上，否则 `chrome` 没有可执行权限。
+pub(crate) fn unzip_file(
+    zip_path: &Path,
+    base_path: &Path,
+    locales: &[String],
+    tests: bool,
+) -> std::result::Result<(), anyhow::Error> {
+    unzip_stream(
+        &mut BufReader::new(File::open(zip_path)?),
+        base_path,
+        locales,
+        tests,
+    )?;
+    let mut archive = ZipArchive::new(BufReader::new(File::open(zip_path)?))?;
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        let (Some(mode), Some((_, relative))) = (entry.unix_mode(), entry.name().split_once('/'))
+        else {
+            continue;
+        };
+        // 跳过的测试程序和语言包不在安装目录中。
+        let file_path = base_path.join(relative);
+        if !entry.is_dir() && file_path.is_file() {
+            set_mode(&file_path, mode)?;
+        }
+    }
+    Ok(())
+}
+
+/// `tests` 为 false 时跳过压缩包中体积较大的 interactive_ui_tests。
+fn unzip_stream(
+    reader: &mut impl Read,
+    base_path: &Path,
+    locales: &[String],
+    tests: bool,
+) -> std::result::Result<(), anyhow::Error> {
+    let mut progress = ExtractProgress::new(None);
     loop {
-        let mut zip = match read_zipfile_from_stream(&mut win_zip_response) {
+        deadline::check()?;
+        let mut zip = match read_zipfile_from_stream(reader) {
             Ok(Some(zip)) => zip,
             Ok(None) => break,
             Err(err) => return Err(anyhow!("读取压缩文件出错：{:?}", err)),
         };
 
         let zip_name = zip.name();
-        println!("==> unzip: {zip_name}");
-
-        if zip_name.contains("interactive_ui_tests") {
+        if !tests && zip_name.contains("interactive_ui_tests") {
             continue;
         }
 
-        if zip_name.starts_with("chrome-win/")
-            || zip_name.starts_with("chrome-win32/")
-            || zip_name.starts_with("chrome-mac/")
-            || zip_name.starts_with("chrome-linux/")
-        {
-            let prefix_len = zip_name.find('/').unwrap() + 1;
+        // 压缩包中的文件位于与压缩包同名的 `chrome-<platform>/` 目录下，配置文件可以指定其他名称的压缩包。
+        let top_level = zip_name.starts_with("chrome-") || zip_name.starts_with("asan-");
+        if let Some(pos) = zip_name.find('/').filter(|_| top_level) {
+            let prefix_len = pos + 1;
+            if !is_wanted_locale(&zip_name[prefix_len..], locales) {
+                continue;
+            }
+            // 只统计实际解压的文件，跳过的测试程序和语言包不计入进度。
+            progress.file(zip_name, zip.size());
             let file_path = base_path.join(&zip_name[prefix_len..]);
             if zip.is_dir() {
                 std::fs::create_dir_all(&file_path).map_err(|err| {
@@ -50,18 +119,20 @@ pub(crate) fn download_chromium_zip_file(
                 }
                 copy(
                     &mut zip,
-                    &mut OpenOptions::new()
-                        .write(true)
-                        .truncate(true)
-                        .create(true)
-                        .open(&file_path)
-                        .map_err(|err| {
-                            anyhow!(
-                                "解压文件 {} 时出错：{:?}",
-                                file_path.to_str().unwrap_or_default(),
-                                err
-                            )
-                        })?,
+                    &mut retry_locked(|| {
+                        OpenOptions::new()
+                            .write(true)
+                            .truncate(true)
+                            .create(true)
+                            .open(&file_path)
+                    })
+                    .map_err(|err| {
+                        anyhow!(
+                            "解压文件 {} 时出错：{:?}",
+                            file_path.to_str().unwrap_or_default(),
+                            err
+                        )
+                    })?,
                 )
                 .map_err(|err| {
                     anyhow!(
@@ -75,6 +146,41 @@ pub(crate) fn download_chromium_zip_file(
             return Err(anyhow!("压缩包文件结构不正确。"));
         }
     }
+    progress.finish();
 
     Ok(())
 }
+
+/// 快照中的附加文件：zip 解压到安装目录，其余文件原样复制。
+///
+/// 测试程序需要与浏览器的资源文件放在同一目录下才能运行，因此去掉压缩包中的顶层目录后合并到安装目录。
+pub(crate) fn install_asset(
+    file: &GoogleApiStorageObject,
+    downloaded: &Downloaded,
+    base_path: &Path,
+) -> anyhow::Result<()> {
+    let file_name = file.name.rsplit('/').next().unwrap_or(&file.name);
+    if file_name.ends_with(".zip") && is_tests_artifact(file_name) {
+        let temp_path = base_path.join(format!(".tmp-{file_name}"));
+        if temp_path.exists() {
+            std::fs::remove_dir_all(&temp_path)?;
+        }
+        timings::time(Phase::Extract, || {
+            extract_archive(downloaded.bytes()?, &temp_path)
+        })?;
+        let mut entries = std::fs::read_dir(&temp_path)?.collect::<Result<Vec<_>, _>>()?;
+        let content_path = match entries.as_slice() {
+            [entry] if entry.file_type()?.is_dir() => entries.remove(0).path(),
+            _ => temp_path.clone(),
+        };
+        copy_dir(&content_path, base_path)?;
+        std::fs::remove_dir_all(&temp_path)?;
+    } else if file_name.ends_with(".zip") {
+        timings::time(Phase::Extract, || {
+            extract_archive(downloaded.bytes()?, base_path)
+        })?;
+    } else {
+        std::fs::copy(&downloaded.path, base_path.join(file_name))?;
+    }
+    Ok(())
+}
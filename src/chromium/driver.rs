@@ -0,0 +1,212 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    extract::extract_archive,
+    metadata::{mark_complete, mark_incomplete, InstallMetadata},
+    paths::Paths,
+    platform::{Os, Platform},
+    timings::{self, Phase},
+    utils::move_dir,
+};
+
+use super::{binary::same_milestone, builds::GoogleApiStorageObject};
+
+/// 快照目录中 chromedriver 压缩包的文件名，各平台目录下只会有其中一个。
+const DRIVER_ZIPS: [&str; 4] = [
+    "chromedriver_win32.zip",
+    "chromedriver_linux64.zip",
+    "chromedriver_mac64.zip",
+    "chromedriver_mac_arm64.zip",
+];
+
+/// 缓存目录中记录快照与 chromedriver 对应关系的文件，压缩包保存在同名目录下。
+pub(crate) const DRIVER_CACHE: &str = "chromedriver";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DriverCacheIndex(BTreeMap<String, DriverCacheEntry>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DriverCacheEntry {
+    /// 压缩包的文件名，例如 `chromedriver_win32.zip`。
+    file: String,
+    version: Option<String>,
+    /// 写入缓存时的 Unix 时间戳（秒）。
+    fetched_at: u64,
+}
+
+impl DriverCacheIndex {
+    fn load(paths: &Paths) -> Self {
+        let path = paths.cache_dir.join(format!("{DRIVER_CACHE}.json"));
+        if path.exists() && !paths.cache.signature_valid(&path) {
+            return Self::default();
+        }
+        File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(file).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, paths: &Paths) -> Result<()> {
+        let path = paths.cached_file(&format!("{DRIVER_CACHE}.json"))?;
+        paths
+            .cache
+            .write_signed(&path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+fn cached_archive_path(paths: &Paths, rev_prefix: &str, file: &str) -> PathBuf {
+    paths
+        .cache_dir
+        .join(DRIVER_CACHE)
+        .join(rev_prefix.trim_end_matches('/').replace('/', "-"))
+        .join(file)
+}
+
+/// 之前为该快照下载过、且未超过 `ttl_days` 天的 chromedriver 压缩包，有则不再联网查找和下载。
+pub(crate) fn cached_driver(rev_prefix: &str, ttl_days: u64, paths: &Paths) -> Option<PathBuf> {
+    let entry = DriverCacheIndex::load(paths).0.remove(rev_prefix)?;
+    let fetched_at = UNIX_EPOCH + Duration::from_secs(entry.fetched_at);
+    let expired = SystemTime::now()
+        .duration_since(fetched_at)
+        .is_ok_and(|elapsed| elapsed > Duration::from_secs(ttl_days * 24 * 60 * 60));
+    let archive = cached_archive_path(paths, rev_prefix, &entry.file);
+    if expired || !archive.exists() || !paths.cache.signature_valid(&archive) {
+        return None;
+    }
+    status!(
+        "==> using cached chromedriver {}: {}",
+        entry.version.as_deref().unwrap_or("(unknown version)"),
+        archive.display()
+    );
+    Some(archive)
+}
+
+/// 记录快照对应的 chromedriver，并把压缩包复制到缓存目录。
+pub(crate) fn cache_driver(
+    rev_prefix: &str,
+    archive: &Path,
+    file: &GoogleApiStorageObject,
+    version: Option<String>,
+    paths: &Paths,
+) -> Result<()> {
+    let file_name = file.name.rsplit('/').next().unwrap_or(&file.name);
+    let cached = cached_archive_path(paths, rev_prefix, file_name);
+    if let Some(parent) = cached.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(archive, &cached)?;
+    paths.cache.sign(&cached)?;
+    let mut index = DriverCacheIndex::load(paths);
+    index.0.insert(
+        rev_prefix.to_owned(),
+        DriverCacheEntry {
+            file: file_name.to_owned(),
+            version,
+            fetched_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        },
+    );
+    index.save(paths)
+}
+
+pub(crate) fn find_driver_file(
+    build_files: &[GoogleApiStorageObject],
+) -> Option<&GoogleApiStorageObject> {
+    build_files.iter().find(|file| {
+        let file_name = file.name.rsplit('/').next().unwrap_or(&file.name);
+        DRIVER_ZIPS.contains(&file_name)
+    })
+}
+
+fn driver_executable(os: Os) -> &'static str {
+    match os {
+        Os::Windows => "chromedriver.exe",
+        Os::Linux | Os::Mac => "chromedriver",
+    }
+}
+
+/// 解压 chromedriver 并安装到浏览器目录旁的 `chromedriver-<version>` 目录，返回读取到的版本号。
+///
+/// `browser_version` 为浏览器实际的版本号（读取不到时为发布版本号），主版本号不一致时报错。
+pub(crate) fn install_driver(
+    archive: &Path,
+    browser: &InstallMetadata,
+    browser_version: &str,
+    platform: Platform,
+    paths: &Paths,
+) -> Result<Option<String>> {
+    let temp_path = paths.temp_install_dir("chromedriver", &browser.version);
+    if temp_path.exists() {
+        std::fs::remove_dir_all(&temp_path)?;
+    }
+    std::fs::create_dir_all(&temp_path)?;
+    timings::time(Phase::Extract, || {
+        extract_archive(Bytes::from(std::fs::read(archive)?), &temp_path)
+    })?;
+    // 压缩包中的文件位于 chromedriver_<platform> 目录下。
+    let mut entries = std::fs::read_dir(&temp_path)?.collect::<Result<Vec<_>, _>>()?;
+    let content_path = match entries.as_slice() {
+        [entry] if entry.file_type()?.is_dir() => entries.remove(0).path(),
+        _ => temp_path.clone(),
+    };
+
+    let executable = driver_executable(platform.os());
+    let driver_version = detect_driver_version(&content_path.join(executable), platform);
+    match &driver_version {
+        Some(driver_version) if !same_milestone(driver_version, browser_version) => {
+            return Err(anyhow!(
+                "chromedriver {driver_version} does not match {} {browser_version}.",
+                browser.browser
+            ));
+        }
+        Some(driver_version) => status!("==> chromedriver version: {driver_version}"),
+        None => status!("==> could not determine the chromedriver version"),
+    }
+
+    let metadata = InstallMetadata {
+        executable: Some(executable.to_owned()),
+        binary_version: driver_version.clone(),
+        platform: browser.platform.clone(),
+        arch: browser.arch.clone(),
+        channel: browser.channel.clone(),
+        snapshot_prefix: browser.snapshot_prefix.clone(),
+        revision: browser.revision,
+        ..InstallMetadata::new("chromedriver", &browser.version)
+    };
+    mark_incomplete(&content_path)?;
+    metadata.write(&content_path)?;
+    let driver_path = paths.install_dir("chromedriver", &browser.version);
+    if driver_path.exists() {
+        std::fs::remove_dir_all(&driver_path)?;
+    }
+    std::fs::create_dir_all(&paths.output_dir)?;
+    move_dir(&content_path, &driver_path)?;
+    mark_complete(&driver_path)?;
+    if temp_path.exists() {
+        std::fs::remove_dir_all(&temp_path)?;
+    }
+    status!("==> installed chromedriver into {}", driver_path.display());
+    Ok(driver_version)
+}
+
+/// 只有目标平台与当前主机相同时才能运行 `chromedriver --version`。
+fn detect_driver_version(executable: &Path, platform: Platform) -> Option<String> {
+    if Os::from_str(std::env::consts::OS).ok() != Some(platform.os()) {
+        return None;
+    }
+    let output = Command::new(executable).arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = Regex::new(r"\d+\.\d+\.\d+\.\d+").unwrap();
+    Some(version.find(&stdout)?.as_str().to_owned())
+}
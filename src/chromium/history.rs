@@ -1,49 +1,335 @@
-use std::{fs::File, io::BufReader};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use anyhow::Result;
-use reqwest::blocking::Client;
-use serde::{Deserialize, Serialize};
+use crate::{
+    cache::CacheStatus,
+    common::ReleaseChannel,
+    error::{ErrorCategory, FetchError},
+    http::Http,
+    metrics,
+    paths::Paths,
+    platform::{Arch, Os, Platform},
+    version::VersionSpec,
+};
 
-use crate::{common::ReleaseChannel, platform::Platform, utils::get_cached_file_path};
+const CFT_KNOWN_GOOD_URL: &str =
+    "https://googlechromelabs.github.io/chrome-for-testing/known-good-versions.json";
+
+/// 获取 Chromium 发布列表的数据源。
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum HistorySource {
+    /// 依次尝试 chromiumdash、versionhistory；`--channel any` 时最后使用 cft。
+    #[default]
+    Auto,
+    /// chromiumdash.appspot.com，带有分支位置。
+    Chromiumdash,
+    /// versionhistory.googleapis.com，分支位置取自 Chrome for Testing 的版本列表。
+    Versionhistory,
+    /// Chrome for Testing 的 `known-good-versions.json`，不区分渠道，只有 113 之后的版本。
+    Cft,
+}
+
+impl HistorySource {
+    fn name(&self) -> &'static str {
+        match self {
+            HistorySource::Auto => "auto",
+            HistorySource::Chromiumdash => "chromiumdash",
+            HistorySource::Versionhistory => "versionhistory",
+            HistorySource::Cft => "cft",
+        }
+    }
+
+    async fn fetch(
+        &self,
+        platform: Platform,
+        channel: ReleaseChannel,
+        client: &Http,
+    ) -> Result<Vec<ChromiumHistoryInfo>> {
+        match self {
+            // `fetch_releases` 依次调用其他数据源的 `fetch`。
+            HistorySource::Auto => Box::pin(fetch_releases(platform, channel, client)).await,
+            HistorySource::Chromiumdash => fetch_chromiumdash(platform, channel, client).await,
+            HistorySource::Versionhistory => fetch_versionhistory(platform, channel, client).await,
+            HistorySource::Cft => fetch_cft(platform, channel, client).await,
+        }
+    }
+}
 
 pub(crate) struct ChromiumHistory(Vec<ChromiumHistoryInfo>);
 
 impl ChromiumHistory {
-    pub(crate) fn init(
+    pub(crate) async fn init(
         platform: Platform,
         channel: ReleaseChannel,
-        client: Client,
+        client: Http,
+        paths: &Paths,
     ) -> Result<Self> {
+        let mut history_list = Vec::new();
+        for concrete in channel.concrete() {
+            match Self::load_channel(platform, concrete, &client, paths).await {
+                Ok(list) => history_list.extend(list),
+                // cft 的列表不区分渠道，只有不要求渠道时才能代替，版本标记为 Any 而不是某个渠道。
+                Err(err)
+                    if channel == ReleaseChannel::Any
+                        && paths.history_source == HistorySource::Auto =>
+                {
+                    status!("==> warning[history-source]: {err}, trying cft for all channels");
+                    return fetch_cft(platform, channel, &client)
+                        .await
+                        .map(Self)
+                        .map_err(|cft_err| err.context(format!("cft failed: {cft_err}")));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Self(history_list))
+    }
+
+    async fn load_channel(
+        platform: Platform,
+        channel: ReleaseChannel,
+        client: &Http,
+        paths: &Paths,
+    ) -> Result<Vec<ChromiumHistoryInfo>> {
         let os_arg = platform.arg_name();
-        let channel = channel.as_constant();
-        let history_json_path = get_cached_file_path(&format!("releases-{os_arg}-{channel}.json"))?;
-        let history_list = if std::fs::try_exists(&history_json_path).unwrap_or_default() {
-            println!("==> using cached history: {}", history_json_path.display());
-            serde_json::from_reader(BufReader::new(File::open(&history_json_path)?))?
+        let history_json_path =
+            paths.cached_file(&format!("releases-{os_arg}-{}.json", channel.as_constant()))?;
+        let cached = paths.cache.read_usable(&history_json_path)?;
+        metrics::cache_lookup(cached.is_some());
+        let history_list = if let Some(history_list) = cached {
+            status!("==> using cached history: {}", history_json_path.display());
+            history_list
         } else {
-            println!("==> retrieving releases.json ...");
-            let url = format!(
-                "https://chromiumdash.appspot.com/fetch_releases?platform={os_arg}&channel={channel}&num=600&offset=0"
-            );
-            let response = client.get(url).send()?;
-            let history_list: Vec<ChromiumHistoryInfo> = serde_json::from_reader(response)?;
-            std::fs::write(&history_json_path, serde_json::to_string(&history_list)?)?;
+            status!("==> retrieving releases.json ...");
+            let source = paths.history_source;
+            let history_list = match source.fetch(platform, channel, client).await {
+                Ok(history_list) => history_list,
+                Err(err) => return paths.cache.read_stale(&history_json_path, err),
+            };
+            metrics::channel_checked(os_arg, channel.as_constant());
+            paths
+                .cache
+                .write_signed(&history_json_path, serde_json::to_string(&history_list)?)?;
             history_list
         };
-        Ok(Self(history_list))
+        Ok(history_list)
+    }
+
+    /// 与上游比较缓存的发布列表，上游有而缓存中没有的版本说明缓存已过期或被截断。
+    pub(crate) async fn verify_cache(
+        platform: Platform,
+        channel: ReleaseChannel,
+        client: &Http,
+        paths: &Paths,
+    ) -> Result<CacheStatus> {
+        let history_json_path = paths.cache_dir.join(format!(
+            "releases-{}-{}.json",
+            platform.arg_name(),
+            channel.as_constant()
+        ));
+        let Some(cached) = paths
+            .cache
+            .read_verified::<Vec<ChromiumHistoryInfo>>(&history_json_path)?
+        else {
+            return Ok(CacheStatus::Missing);
+        };
+        let source = paths.history_source;
+        let upstream = source.fetch(platform, channel, client).await?;
+        let missing: Vec<_> = upstream
+            .iter()
+            .filter(|info| !cached.iter().any(|c| c.version == info.version))
+            .map(|info| info.version.as_str())
+            .collect();
+        Ok(match missing.as_slice() {
+            [] => CacheStatus::Fresh,
+            [first, ..] => CacheStatus::Diverged(format!(
+                "{} of {} upstream releases are missing, e.g. {first}",
+                missing.len(),
+                upstream.len()
+            )),
+        })
     }
 
     pub(crate) fn find<'a>(&'a self, version: &str) -> Vec<&'a ChromiumHistoryInfo> {
-        let ver_len = version.len();
+        let spec = VersionSpec::parse(version);
         self.0
             .iter()
-            .filter(|info| {
-                info.version == version
-                    || (info.version.chars().nth(ver_len) == Some('.')
-                        && info.version.starts_with(version))
-            })
+            .filter(|info| spec.matches(&info.version))
             .collect()
     }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &ChromiumHistoryInfo> {
+        self.0.iter()
+    }
+
+    pub(crate) fn find_exact<'a>(&'a self, version: &str) -> Vec<&'a ChromiumHistoryInfo> {
+        self.0
+            .iter()
+            .filter(|info| info.version == version)
+            .collect()
+    }
+}
+
+/// 按 `HistorySource::Auto` 的顺序尝试按渠道发布的数据源，前一个失败时使用下一个。
+///
+/// cft 不区分渠道，不在这里回退，见 [`ChromiumHistory::init`]。
+async fn fetch_releases(
+    platform: Platform,
+    channel: ReleaseChannel,
+    client: &Http,
+) -> Result<Vec<ChromiumHistoryInfo>> {
+    let sources = [HistorySource::Chromiumdash, HistorySource::Versionhistory];
+    let mut last_err = None;
+    for source in sources {
+        if let Some(err) = &last_err {
+            status!(
+                "==> warning[history-source]: {err}, trying {}",
+                source.name()
+            );
+        }
+        match source.fetch(platform, channel, client).await {
+            Ok(releases) => return Ok(releases),
+            Err(err) => {
+                let message = format!("{} failed: {err}", source.name());
+                // 保留网络错误的类别，命令行据此决定退出码。
+                last_err = Some(match ErrorCategory::of(&err) {
+                    ErrorCategory::Network => FetchError::Network(message).into(),
+                    _ => anyhow!(message),
+                });
+            }
+        }
+    }
+    Err(last_err.unwrap())
+}
+
+async fn get_json<T: DeserializeOwned>(client: &Http, url: &str) -> Result<T> {
+    let response = client.get(url).await?;
+    if !response.is_success() {
+        return Err(
+            FetchError::Network(format!("Retrieving {url} failed: {}", response.status())).into(),
+        );
+    }
+    response.json().await
+}
+
+async fn fetch_chromiumdash(
+    platform: Platform,
+    channel: ReleaseChannel,
+    client: &Http,
+) -> Result<Vec<ChromiumHistoryInfo>> {
+    let url = format!(
+        "https://chromiumdash.appspot.com/fetch_releases?platform={}&channel={}&num=600&offset=0",
+        platform.arg_name(),
+        channel.as_constant()
+    );
+    get_json(client, &url).await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VersionHistoryPage {
+    #[serde(default)]
+    versions: Vec<VersionHistoryVersion>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionHistoryVersion {
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CftKnownGoodVersions {
+    versions: Vec<CftKnownGoodVersion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CftKnownGoodVersion {
+    version: String,
+    revision: String,
+}
+
+/// versionhistory.googleapis.com 中的平台名。
+fn versionhistory_platform(platform: Platform) -> &'static str {
+    match (platform.os(), platform.arch()) {
+        (Os::Windows, Arch::X86) => "win",
+        (Os::Windows, Arch::X86_64) => "win64",
+        (Os::Windows, Arch::Arm64) => "win_arm64",
+        (Os::Mac, Arch::Arm64) => "mac_arm64",
+        (Os::Mac, _) => "mac",
+        (Os::Linux, _) => "linux",
+    }
+}
+
+/// versionhistory 只有版本号，分支位置从 Chrome for Testing 的版本列表中查找，查不到的版本没有分支位置。
+async fn fetch_versionhistory(
+    platform: Platform,
+    channel: ReleaseChannel,
+    client: &Http,
+) -> Result<Vec<ChromiumHistoryInfo>> {
+    let base = format!(
+        "https://versionhistory.googleapis.com/v1/chrome/platforms/{}/channels/{}/versions",
+        versionhistory_platform(platform),
+        channel.as_constant().to_lowercase()
+    );
+    let mut versions = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let url = match &page_token {
+            Some(token) => format!("{base}?pageSize=1000&pageToken={token}"),
+            None => format!("{base}?pageSize=1000"),
+        };
+        let page: VersionHistoryPage = get_json(client, &url).await?;
+        versions.extend(page.versions.into_iter().map(|v| v.version));
+        match page.next_page_token.filter(|token| !token.is_empty()) {
+            Some(token) => page_token = Some(token),
+            None => break,
+        }
+    }
+    let positions = match get_json::<CftKnownGoodVersions>(client, CFT_KNOWN_GOOD_URL).await {
+        Ok(known_good) => known_good.versions,
+        Err(err) => {
+            status!(
+                "==> warning[history-source]: no branch positions from chrome for testing: {err}"
+            );
+            Vec::new()
+        }
+    };
+    Ok(versions
+        .into_iter()
+        .map(|version| ChromiumHistoryInfo {
+            channel: channel.as_constant().to_owned(),
+            platform: platform.arg_name().to_owned(),
+            chromium_main_branch_position: positions
+                .iter()
+                .find(|known| known.version == version)
+                .and_then(|known| known.revision.parse().ok()),
+            version,
+            time: None,
+        })
+        .collect())
+}
+
+/// Chrome for Testing 的版本列表不区分渠道，每个渠道都使用完整的列表。
+async fn fetch_cft(
+    platform: Platform,
+    channel: ReleaseChannel,
+    client: &Http,
+) -> Result<Vec<ChromiumHistoryInfo>> {
+    let known_good: CftKnownGoodVersions = get_json(client, CFT_KNOWN_GOOD_URL).await?;
+    Ok(known_good
+        .versions
+        .into_iter()
+        .map(|known| ChromiumHistoryInfo {
+            channel: channel.as_constant().to_owned(),
+            platform: platform.arg_name().to_owned(),
+            chromium_main_branch_position: known.revision.parse().ok(),
+            version: known.version,
+            time: None,
+        })
+        .collect())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -52,29 +338,302 @@ pub(crate) struct ChromiumHistoryInfo {
     pub(crate) platform: String,
     pub(crate) version: String,
     pub(crate) chromium_main_branch_position: Option<usize>,
+    #[serde(default)]
+    pub(crate) time: Option<u64>,
 }
 
 impl ChromiumHistoryInfo {
-    pub(crate) fn deps(&self, client: &Client) -> Result<ChromiumDepsInfo> {
+    /// 从 chromium/src 中该版本 tag 下的 DEPS 读取依赖的 V8 和 Skia 提交，V8 的版本号再从 V8 仓库读取。
+    pub(crate) async fn deps(&self, client: &Http) -> Result<ChromiumDepsInfo> {
         let url = format!(
-            "https://omahaproxy.appspot.com/deps.json?version={}",
+            "https://chromium.googlesource.com/chromium/src/+/refs/tags/{}/DEPS?format=TEXT",
             self.version
         );
-        println!("==> fetching deps {url} ...");
-        let response = client.get(url).send()?;
-        Ok(serde_json::from_reader(response)?)
+        status!("==> fetching deps {url} ...");
+        let deps =
+            get_gitiles_file(client, &url)
+                .await
+                .map_err(|err| match ErrorCategory::of(&err) {
+                    ErrorCategory::VersionNotFound => {
+                        err.context(format!("No DEPS found for Chromium {}.", self.version))
+                    }
+                    _ => err,
+                })?;
+        let revision = |name: &str| {
+            Regex::new(&format!(r"'{name}':\s*'([0-9a-f]{{40}})'"))
+                .unwrap()
+                .captures(&deps)
+                .map(|captures| captures[1].to_owned())
+                .ok_or_else(|| anyhow!("No {name} found in {url}"))
+        };
+        let v8_commit = revision("v8_revision")?;
+        let v8_version = match v8_version(client, &v8_commit).await {
+            Ok(version) => Some(version),
+            Err(err) => {
+                status!("==> warning: reading the V8 version failed: {err}");
+                None
+            }
+        };
+        Ok(ChromiumDepsInfo {
+            chromium_version: self.version.clone(),
+            // 版本号的第三段是分支号，例如 114.0.5735.199 来自 5735 分支。
+            chromium_branch: self.version.split('.').nth(2).map(str::to_owned),
+            skia_commit: revision("skia_revision")?,
+            v8_commit,
+            v8_version,
+        })
+    }
+}
+
+/// gitiles 的 `?format=TEXT` 返回 base64 编码的文件内容；404 说明 tag 或文件不存在。
+async fn get_gitiles_file(client: &Http, url: &str) -> Result<String> {
+    let response = client.get(url).await?;
+    match response.status() {
+        404 => Err(FetchError::VersionNotFound(format!("{url} does not exist.")).into()),
+        _ if !response.is_success() => Err(FetchError::Network(format!(
+            "Retrieving {url} failed: {}",
+            response.status()
+        ))
+        .into()),
+        _ => Ok(String::from_utf8(base64::decode(
+            response.text().await?.trim(),
+        )?)?),
     }
 }
 
+/// 从 V8 仓库的 `include/v8-version.h` 中拼出 `major.minor.build.patch`。
+async fn v8_version(client: &Http, commit: &str) -> Result<String> {
+    let url = format!(
+        "https://chromium.googlesource.com/v8/v8/+/{commit}/include/v8-version.h?format=TEXT"
+    );
+    let header = get_gitiles_file(client, &url).await?;
+    let parts = [
+        "MAJOR_VERSION",
+        "MINOR_VERSION",
+        "BUILD_NUMBER",
+        "PATCH_LEVEL",
+    ]
+    .map(|name| {
+        Regex::new(&format!(r"#define V8_{name}\s+(\d+)"))
+            .unwrap()
+            .captures(&header)
+            .map(|captures| captures[1].to_owned())
+            .ok_or_else(|| anyhow!("No V8_{name} found in {url}"))
+    });
+    Ok(parts.into_iter().collect::<Result<Vec<_>>>()?.join("."))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub(crate) struct ChromiumDepsInfo {
-    pub(crate) chromium_base_commit: Option<String>,
-    pub(crate) chromium_base_position: Option<String>,
-    pub(crate) chromium_branch: Option<String>,
-    pub(crate) chromium_commit: String,
     pub(crate) chromium_version: String,
+    pub(crate) chromium_branch: Option<String>,
     pub(crate) skia_commit: String,
     pub(crate) v8_commit: String,
-    pub(crate) v8_position: String,
-    pub(crate) v8_version: String,
+    /// 读取失败时为空，只比较提交。
+    pub(crate) v8_version: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::http::MockTransport;
+
+    use super::*;
+
+    const CHROMIUMDASH_URL: &str = "https://chromiumdash.appspot.com/fetch_releases?platform=linux&channel=Stable&num=600&offset=0";
+
+    const VERSIONHISTORY_URL: &str =
+        "https://versionhistory.googleapis.com/v1/chrome/platforms/linux/channels/stable/versions";
+
+    fn linux() -> Platform {
+        Platform::new(Os::Linux, Arch::X86_64)
+    }
+
+    fn info(version: &str, position: Option<usize>) -> ChromiumHistoryInfo {
+        ChromiumHistoryInfo {
+            channel: "Stable".to_owned(),
+            platform: "linux".to_owned(),
+            version: version.to_owned(),
+            chromium_main_branch_position: position,
+            time: None,
+        }
+    }
+
+    fn versions(releases: &[ChromiumHistoryInfo]) -> Vec<&str> {
+        releases.iter().map(|info| info.version.as_str()).collect()
+    }
+
+    #[test]
+    fn find_matches_prefix_exact_and_range() {
+        let history = ChromiumHistory(vec![
+            info("114.0.5735.199", Some(1135570)),
+            info("114.0.5735.133", Some(1135570)),
+            info("115.0.5790.102", Some(1148114)),
+        ]);
+        assert_eq!(history.find("114").len(), 2);
+        assert_eq!(history.find("=114.0.5735.133").len(), 1);
+        assert_eq!(history.find("114.0.5735.150..115").len(), 2);
+        assert_eq!(history.find_exact("114").len(), 0);
+    }
+
+    #[tokio::test]
+    async fn chromiumdash_is_preferred() {
+        let mock = Arc::new(MockTransport::default());
+        mock.respond(
+            CHROMIUMDASH_URL,
+            200,
+            r#"[{"channel":"Stable","platform":"linux","version":"114.0.5735.199","chromium_main_branch_position":1135570,"time":1687910400000}]"#,
+        );
+        let client: Http = mock;
+        let releases = fetch_releases(linux(), ReleaseChannel::Stable, &client)
+            .await
+            .unwrap();
+        assert_eq!(versions(&releases), ["114.0.5735.199"]);
+        assert_eq!(releases[0].chromium_main_branch_position, Some(1135570));
+    }
+
+    #[tokio::test]
+    async fn versionhistory_follows_page_tokens() {
+        let mock = Arc::new(MockTransport::default());
+        mock.respond(CHROMIUMDASH_URL, 503, "Service Unavailable");
+        mock.respond(
+            &format!("{VERSIONHISTORY_URL}?pageSize=1000"),
+            200,
+            r#"{"versions":[{"version":"115.0.5790.102"}],"nextPageToken":"p2"}"#,
+        );
+        mock.respond(
+            &format!("{VERSIONHISTORY_URL}?pageSize=1000&pageToken=p2"),
+            200,
+            r#"{"versions":[{"version":"114.0.5735.199"}],"nextPageToken":""}"#,
+        );
+        mock.respond(
+            CFT_KNOWN_GOOD_URL,
+            200,
+            r#"{"versions":[{"version":"115.0.5790.102","revision":"1148114"}]}"#,
+        );
+        let client: Http = mock;
+        let releases = fetch_releases(linux(), ReleaseChannel::Stable, &client)
+            .await
+            .unwrap();
+        assert_eq!(versions(&releases), ["115.0.5790.102", "114.0.5735.199"]);
+        assert_eq!(releases[0].chromium_main_branch_position, Some(1148114));
+        assert_eq!(releases[1].chromium_main_branch_position, None);
+    }
+
+    #[tokio::test]
+    async fn malformed_json_falls_back_to_the_next_source() {
+        let mock = Arc::new(MockTransport::default());
+        mock.respond(CHROMIUMDASH_URL, 200, "<html>maintenance</html>");
+        mock.respond(
+            &format!("{VERSIONHISTORY_URL}?pageSize=1000"),
+            200,
+            r#"{"versions":[{"version":"114.0.5735.199"}]}"#,
+        );
+        mock.respond(CFT_KNOWN_GOOD_URL, 200, "{\"versions\":");
+        let client: Http = mock;
+        let releases = fetch_releases(linux(), ReleaseChannel::Stable, &client)
+            .await
+            .unwrap();
+        assert_eq!(versions(&releases), ["114.0.5735.199"]);
+        assert_eq!(releases[0].chromium_main_branch_position, None);
+    }
+
+    #[tokio::test]
+    async fn channel_specific_requests_do_not_fall_back_to_cft() {
+        let mock = Arc::new(MockTransport::default());
+        mock.respond(CHROMIUMDASH_URL, 500, "");
+        mock.respond(&format!("{VERSIONHISTORY_URL}?pageSize=1000"), 500, "");
+        mock.respond(
+            CFT_KNOWN_GOOD_URL,
+            200,
+            r#"{"versions":[{"version":"113.0.5672.0","revision":"1121455"}]}"#,
+        );
+        let client: Http = mock;
+        let err = fetch_releases(linux(), ReleaseChannel::Stable, &client)
+            .await
+            .unwrap_err();
+        assert_eq!(ErrorCategory::of(&err), ErrorCategory::Network);
+        assert!(err.to_string().starts_with("versionhistory failed"));
+    }
+
+    #[tokio::test]
+    async fn any_channel_falls_back_to_channel_less_cft() {
+        let mock = Arc::new(MockTransport::default());
+        for channel in ReleaseChannel::Any.concrete() {
+            let constant = channel.as_constant();
+            mock.respond(
+                &format!("https://chromiumdash.appspot.com/fetch_releases?platform=linux&channel={constant}&num=600&offset=0"),
+                500,
+                "",
+            );
+            mock.respond(
+                &format!(
+                    "https://versionhistory.googleapis.com/v1/chrome/platforms/linux/channels/{}/versions?pageSize=1000",
+                    constant.to_lowercase()
+                ),
+                500,
+                "",
+            );
+        }
+        mock.respond(
+            CFT_KNOWN_GOOD_URL,
+            200,
+            r#"{"versions":[{"version":"113.0.5672.0","revision":"1121455"}]}"#,
+        );
+        let dir = std::env::temp_dir().join(format!("fetchbrowser-history-{}", std::process::id()));
+        let paths = Paths {
+            output_dir: dir.clone(),
+            temp_dir: dir.clone(),
+            cache_dir: dir.clone(),
+            name_by: Default::default(),
+            dir_name: None,
+            platform: None,
+            cache: Default::default(),
+            history_source: Default::default(),
+        };
+        let history = ChromiumHistory::init(linux(), ReleaseChannel::Any, mock, &paths).await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let history = history.unwrap();
+        let releases: Vec<_> = history.iter().collect();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].channel, "Any");
+    }
+
+    #[tokio::test]
+    async fn deps_are_read_from_gitiles() {
+        let v8 = "a".repeat(40);
+        let skia = "b".repeat(40);
+        let mock = Arc::new(MockTransport::default());
+        mock.respond(
+            "https://chromium.googlesource.com/chromium/src/+/refs/tags/114.0.5735.199/DEPS?format=TEXT",
+            200,
+            base64::encode(format!(
+                "vars = {{\n  'skia_revision': '{skia}',\n  'v8_revision': '{v8}',\n}}\n"
+            )),
+        );
+        mock.respond(
+            &format!("https://chromium.googlesource.com/v8/v8/+/{v8}/include/v8-version.h?format=TEXT"),
+            200,
+            base64::encode(
+                "#define V8_MAJOR_VERSION 11\n#define V8_MINOR_VERSION 4\n#define V8_BUILD_NUMBER 183\n#define V8_PATCH_LEVEL 25\n",
+            ),
+        );
+        mock.respond(
+            "https://chromium.googlesource.com/chromium/src/+/refs/tags/1.2.3.4/DEPS?format=TEXT",
+            404,
+            "",
+        );
+        let client: Http = mock;
+
+        let deps = info("114.0.5735.199", None).deps(&client).await.unwrap();
+        assert_eq!(deps.chromium_branch.as_deref(), Some("5735"));
+        assert_eq!(deps.v8_commit, v8);
+        assert_eq!(deps.v8_version.as_deref(), Some("11.4.183.25"));
+        assert_eq!(deps.skia_commit, skia);
+
+        let err = info("1.2.3.4", None).deps(&client).await.unwrap_err();
+        assert_eq!(ErrorCategory::of(&err), ErrorCategory::VersionNotFound);
+    }
 }
@@ -0,0 +1,307 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{
+    common::{BuildVariant, ReleaseChannel},
+    http::Http,
+    paths::Paths,
+    platform::{Arch, Os, Platform},
+    utils::format_date,
+    version::BrowserVersion,
+};
+
+use super::{builds::ChromiumBuilds, history::ChromiumHistory};
+
+fn all_platforms() -> Vec<Platform> {
+    let mut platforms: Vec<Platform> = Vec::new();
+    for os in Os::ALL {
+        for arch in Arch::ALL {
+            let platform = Platform::new(os, arch);
+            if !platforms
+                .iter()
+                .any(|p| p.arg_name() == platform.arg_name())
+            {
+                platforms.push(platform);
+            }
+        }
+    }
+    platforms
+}
+
+pub(crate) async fn print_channels(version: &str, client: &Http, paths: &Paths) -> Result<()> {
+    let mut rows = Vec::new();
+    for platform in all_platforms() {
+        let history =
+            ChromiumHistory::init(platform, ReleaseChannel::Any, client.clone(), paths).await?;
+        for info in history.find_exact(version) {
+            let date = info
+                .time
+                .map(format_date)
+                .unwrap_or_else(|| "unknown".to_owned());
+            rows.push(format!(
+                "    {:<8} {:<8} {}",
+                info.channel,
+                platform.arg_name(),
+                date
+            ));
+        }
+    }
+    if rows.is_empty() {
+        status!("==> chromium {version} was not found in any channel.");
+    } else {
+        status!("==> chromium {version} appeared in:");
+        for row in rows {
+            println!("{row}");
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_revision(revision: &str) -> Result<usize> {
+    revision
+        .trim_start_matches('r')
+        .parse()
+        .map_err(|_| anyhow!("Invalid revision: {revision}"))
+}
+
+pub(crate) async fn print_snapshots(
+    from: Option<usize>,
+    to: Option<usize>,
+    platform: Platform,
+    variant: BuildVariant,
+    client: &Http,
+    paths: &Paths,
+) -> Result<()> {
+    let builds = ChromiumBuilds::init(platform, variant, client.clone(), paths).await?;
+    let prefix = builds.prefix();
+    let revisions: Vec<_> = builds
+        .revisions()
+        .into_iter()
+        .map(|(_, rev)| rev)
+        .filter(|rev| from.is_none_or(|from| *rev >= from) && to.is_none_or(|to| *rev <= to))
+        .collect();
+    match (revisions.first(), revisions.last()) {
+        (Some(first), Some(last)) => status!(
+            "==> {} snapshots of {prefix} between r{first} and r{last}:",
+            revisions.len()
+        ),
+        _ => status!("==> no snapshot of {prefix} in the given range."),
+    }
+    for rev in revisions {
+        println!("    r{rev}");
+    }
+    Ok(())
+}
+
+pub(crate) async fn print_whatis(
+    revision: usize,
+    platform: Platform,
+    channel: ReleaseChannel,
+    client: &Http,
+    paths: &Paths,
+) -> Result<()> {
+    let history = ChromiumHistory::init(platform, channel, client.clone(), paths).await?;
+    let mut branch_points: Vec<(usize, Vec<&str>)> = Vec::new();
+    for info in history.iter() {
+        if let Some(pos) = info.chromium_main_branch_position {
+            match branch_points.iter_mut().find(|(p, _)| *p == pos) {
+                Some((_, versions)) => versions.push(&info.version),
+                None => branch_points.push((pos, vec![&info.version])),
+            }
+        }
+    }
+    branch_points.sort_by_key(|(pos, _)| *pos);
+
+    let describe = |(pos, versions): &(usize, Vec<&str>)| {
+        let mut versions = versions.clone();
+        versions.sort_by_key(|v| BrowserVersion::from_str(v).ok());
+        versions.dedup();
+        match versions.as_slice() {
+            [only] => format!("{only} (r{pos})"),
+            [first, .., last] => format!("{first} ~ {last} (r{pos})"),
+            [] => format!("r{pos}"),
+        }
+    };
+    let before = branch_points.iter().rev().find(|(pos, _)| *pos <= revision);
+    let after = branch_points.iter().find(|(pos, _)| *pos > revision);
+    match (before, after) {
+        (Some(point), _) if point.0 == revision => {
+            status!("==> r{revision} is the branch point of {}", describe(point));
+        }
+        (Some(before), Some(after)) => status!(
+            "==> r{revision} is after {} and before {}",
+            describe(before),
+            describe(after)
+        ),
+        (Some(before), None) => status!(
+            "==> r{revision} is after {}, no later release is known",
+            describe(before)
+        ),
+        (None, Some(after)) => status!(
+            "==> r{revision} is before {}, no earlier release is known",
+            describe(after)
+        ),
+        (None, None) => status!("==> no branch point found in history data"),
+    }
+    Ok(())
+}
+
+fn resolve_position(history: &ChromiumHistory, version: &str) -> Result<(String, usize)> {
+    history
+        .find(version)
+        .into_iter()
+        .find_map(|info| {
+            info.chromium_main_branch_position
+                .map(|pos| (info.version.clone(), pos))
+        })
+        .ok_or_else(|| anyhow!("No base position found for chromium {version}"))
+}
+
+#[derive(Debug, Deserialize)]
+struct CrrevRedirect {
+    git_sha: String,
+}
+
+async fn position_to_commit(position: usize, client: &Http) -> Result<String> {
+    let url = format!("https://cr-rev.appspot.com/_ah/api/crrev/v1/redirect/{position}");
+    let redirect: CrrevRedirect = client.get(&url).await?.json().await?;
+    Ok(redirect.git_sha)
+}
+
+#[derive(Debug, Deserialize)]
+struct GitilesLog {
+    log: Vec<GitilesCommit>,
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitilesCommit {
+    commit: String,
+    message: String,
+}
+
+impl GitilesCommit {
+    fn position(&self) -> Option<&str> {
+        self.message
+            .lines()
+            .find_map(|line| line.strip_prefix("Cr-Commit-Position: "))
+            .and_then(|line| line.rsplit("@{#").next())
+            .map(|pos| pos.trim_end_matches('}'))
+    }
+}
+
+pub(crate) async fn print_changelog(
+    from: &str,
+    to: &str,
+    limit: usize,
+    platform: Platform,
+    channel: ReleaseChannel,
+    client: &Http,
+    paths: &Paths,
+) -> Result<()> {
+    let history = ChromiumHistory::init(platform, channel, client.clone(), paths).await?;
+    let (from_version, from_pos) = resolve_position(&history, from)?;
+    let (to_version, to_pos) = resolve_position(&history, to)?;
+    status!("==> {from_version}: r{from_pos}, {to_version}: r{to_pos}");
+
+    let from_commit = position_to_commit(from_pos, client).await?;
+    let to_commit = position_to_commit(to_pos, client).await?;
+    let log_url =
+        format!("https://chromium.googlesource.com/chromium/src/+log/{from_commit}..{to_commit}");
+    status!("==> {log_url}");
+
+    let mut next: Option<String> = None;
+    let mut count = 0;
+    while count < limit {
+        let page_size = (limit - count).min(100);
+        let page_url = match &next {
+            Some(start) => format!("{log_url}?format=JSON&n={page_size}&s={start}"),
+            None => format!("{log_url}?format=JSON&n={page_size}"),
+        };
+        let body = client.get(&page_url).await?.text().await?;
+        // gitiles 的 JSON 响应带有 `)]}'` 前缀以防止 XSSI。
+        let page: GitilesLog = serde_json::from_str(body.trim_start_matches(")]}'"))?;
+        for commit in &page.log {
+            let title = commit.message.lines().next().unwrap_or_default();
+            let position = commit
+                .position()
+                .map(|pos| format!("r{pos}"))
+                .unwrap_or_default();
+            println!("    {} {:<9} {}", &commit.commit[..12], position, title);
+        }
+        count += page.log.len();
+        next = page.next;
+        if next.is_none() || page.log.is_empty() {
+            break;
+        }
+    }
+    if next.is_some() {
+        status!("==> showing the first {count} commits, see the url above for the rest");
+    }
+    Ok(())
+}
+
+pub(crate) async fn print_deps_diff(
+    from: &str,
+    to: &str,
+    platform: Platform,
+    channel: ReleaseChannel,
+    client: &Http,
+    paths: &Paths,
+) -> Result<()> {
+    let history = ChromiumHistory::init(platform, channel, client.clone(), paths).await?;
+    let find = |version: &str| {
+        history
+            .find(version)
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Chromium {version} not found in history"))
+    };
+    let from_deps = find(from)?.deps(client).await?;
+    let to_deps = find(to)?.deps(client).await?;
+    let empty = String::new();
+
+    let rows = [
+        (
+            "chromium",
+            &from_deps.chromium_version,
+            &to_deps.chromium_version,
+        ),
+        (
+            "branch",
+            from_deps.chromium_branch.as_ref().unwrap_or(&empty),
+            to_deps.chromium_branch.as_ref().unwrap_or(&empty),
+        ),
+        (
+            "v8",
+            from_deps
+                .v8_version
+                .as_ref()
+                .unwrap_or(&from_deps.v8_commit),
+            to_deps.v8_version.as_ref().unwrap_or(&to_deps.v8_commit),
+        ),
+        ("skia", &from_deps.skia_commit, &to_deps.skia_commit),
+    ];
+    status!("==> {:<10} {:<42} {}", "component", from, to);
+    for (name, a, b) in rows {
+        let mark = if a == b { "" } else { " *" };
+        println!("    {name:<10} {a:<42} {b}{mark}");
+    }
+    if from_deps.v8_commit != to_deps.v8_commit {
+        status!(
+            "==> v8 rolls: https://chromium.googlesource.com/v8/v8/+log/{}..{}",
+            from_deps.v8_commit,
+            to_deps.v8_commit
+        );
+    }
+    if from_deps.skia_commit != to_deps.skia_commit {
+        status!(
+            "==> skia rolls: https://skia.googlesource.com/skia/+log/{}..{}",
+            from_deps.skia_commit,
+            to_deps.skia_commit
+        );
+    }
+    Ok(())
+}
@@ -1,71 +1,253 @@
-use std::vec::IntoIter;
+use std::{
+    path::{Path, PathBuf},
+    vec::IntoIter,
+};
 
 use anyhow::{anyhow, Result};
-use reqwest::blocking::Client;
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use tokio::sync::OnceCell;
 
 use crate::{
-    common::{BrowserReleaseItem, BrowserReleases, ReleaseChannel},
-    platform::Platform,
+    archive::{keep_archive, kept_archive_name},
+    cache::CacheStatus,
+    checksums::Checksum,
+    common::{
+        BrowserReleaseItem, BrowserReleases, BuildVariant, DownloadOptions, DownloadPlan,
+        ReleaseChannel, ReleaseMatches,
+    },
+    diagnostic::{ResolutionDiagnostic, ResolutionStage},
+    downloads::{DownloadManager, DownloadRequest, Downloads},
+    error::FetchError,
+    http::Http,
+    lock::with_install_lock,
+    metadata::{mark_complete, mark_incomplete, InstallMetadata, SnapshotObject},
+    paths::Paths,
+    platform::{Arch, Os, Platform},
+    session::Session,
+    timings::{self, Phase},
+    utils::{blocking, move_dir, wildcard_match},
+    version::nearest_versions,
 };
 
 use self::{
-    builds::{fetch_build_detail, ChromiumBuilds},
-    download::download_chromium_zip_file,
+    binary::{detect_binary_version, same_milestone},
+    builds::{fetch_build_detail, ChromiumBuilds, GoogleApiStorageObject, SNAPSHOT_TOLERANCE},
+    download::{extract_chromium_zip_file, install_asset},
+    driver::{cache_driver, cached_driver, find_driver_file, install_driver},
     history::{ChromiumHistory, ChromiumHistoryInfo},
+    protocol::download_devtools_protocol,
 };
 
+mod binary;
 mod builds;
 mod download;
+mod driver;
 mod history;
-mod version;
+mod lookup;
+mod protocol;
+mod resolve;
+
+pub(crate) use self::download::unzip_file;
+pub(crate) use self::driver::DRIVER_CACHE;
+pub use self::history::HistorySource;
+pub(crate) use self::lookup::{
+    parse_revision, print_changelog, print_channels, print_deps_diff, print_snapshots, print_whatis,
+};
+pub(crate) use self::resolve::revision_release;
+pub use self::resolve::{list_chromium_versions, resolve_chromium, ResolvedBuild};
 
-pub(crate) struct ChromiumReleases {
+/// Chromium 某个平台、渠道的发布，以及对应的快照构建。
+pub struct ChromiumReleases {
     platform: Platform,
+    channel: ReleaseChannel,
     history: ChromiumHistory,
     builds: ChromiumBuilds,
-    client: Client,
+    client: Http,
+    downloads: Downloads,
+    options: DownloadOptions,
+    paths: Paths,
 }
 
-impl BrowserReleases for ChromiumReleases {
-    type ReleaseItem = ChromiumReleaseItem;
-    type Matches<'r> = ChromiumReleaseMatches<'r>;
-
-    fn init(platform: Platform, channel: ReleaseChannel, client: Client) -> anyhow::Result<Self>
-    where
-        Self: Sized,
-    {
-        // history.json 包含了 base_position 和版本号。
-        let history = ChromiumHistory::init(platform, channel, client.clone())?;
-        // builds 包含了所有可下载的 position 信息。
-        let builds = ChromiumBuilds::init(platform, client.clone())?;
+impl ChromiumReleases {
+    /// 获取发布历史和快照列表，已缓存时使用缓存。
+    pub async fn new(
+        session: &Session,
+        platform: Platform,
+        channel: ReleaseChannel,
+        options: DownloadOptions,
+    ) -> anyhow::Result<Self> {
+        Self::init(
+            platform,
+            channel,
+            session.client.clone(),
+            session.downloads.clone(),
+            options,
+            session.paths.clone(),
+        )
+        .await
+    }
+
+    pub(crate) async fn init(
+        platform: Platform,
+        channel: ReleaseChannel,
+        client: Http,
+        downloads: Downloads,
+        options: DownloadOptions,
+        paths: Paths,
+    ) -> anyhow::Result<Self> {
+        if channel == ReleaseChannel::Esr {
+            return Err(anyhow!("The esr channel is only published for Firefox."));
+        }
+        // 两者互不依赖，并行获取以缩短冷启动时间。
+        let (history, builds) = tokio::join!(
+            // history.json 包含了 base_position 和版本号。
+            timings::time_async(
+                Phase::History,
+                ChromiumHistory::init(platform, channel, client.clone(), &paths),
+            ),
+            // builds 包含了所有可下载的 position 信息。
+            timings::time_async(
+                Phase::Builds,
+                ChromiumBuilds::init(platform, options.variant, client.clone(), &paths),
+            ),
+        );
+        let (history, mut builds) = (history?, builds?);
+        // 缓存的快照列表早于最新的发布时，增量抓取更新的快照。
+        if let Some(pos) = history
+            .iter()
+            .filter_map(|info| info.chromium_main_branch_position)
+            .max()
+        {
+            if let Err(err) = builds.refresh_if_older(pos, client.clone(), &paths).await {
+                status!(
+                    "==> warning: retrieving newer builds failed, using the cached list: {err}"
+                );
+            }
+        }
         Ok(Self {
             platform,
+            channel,
             history,
             builds,
             client,
+            downloads,
+            options,
+            paths,
         })
     }
+}
 
-    fn match_version<'r>(&'r self, version: &str) -> Self::Matches<'r> {
-        ChromiumReleaseMatches::new(self, self.history.find(version))
+impl BrowserReleases for ChromiumReleases {
+    fn match_version<'r>(&'r self, version: &str) -> ReleaseMatches<'r> {
+        let matches = ChromiumReleaseMatches::new(self, version, self.history.find(version));
+        // 匹配只查询已获取的列表，不需要联网。
+        stream::iter(
+            matches.map(|item| item.map(|item| Box::new(item) as Box<dyn BrowserReleaseItem>)),
+        )
+        .boxed()
     }
 }
 
 pub(crate) struct ChromiumReleaseMatches<'r> {
     iter: IntoIter<&'r ChromiumHistoryInfo>,
     releases: &'r ChromiumReleases,
-    prefix: &'static str,
+    version: String,
+    /// 匹配到的分支位置，都找不到快照时用于诊断。
+    positions: Vec<(&'r str, usize)>,
+    matched: usize,
+    yielded: bool,
+    diagnosed: bool,
+    unpublished: Option<String>,
 }
 
 impl<'r> ChromiumReleaseMatches<'r> {
-    fn new(releases: &'r ChromiumReleases, items: Vec<&'r ChromiumHistoryInfo>) -> Self {
-        let prefix = releases.platform.prefix();
+    fn new(
+        releases: &'r ChromiumReleases,
+        version: &str,
+        items: Vec<&'r ChromiumHistoryInfo>,
+    ) -> Self {
         Self {
             releases,
+            version: version.to_owned(),
+            positions: Vec::new(),
+            matched: items.len(),
             iter: items.into_iter(),
-            prefix,
+            yielded: false,
+            diagnosed: false,
+            unpublished: None,
         }
     }
+
+    /// 没有任何匹配的版本能够下载时，说明在哪个阶段失败以及最接近的候选项。
+    fn diagnose(&mut self) -> anyhow::Error {
+        let releases = self.releases;
+        let history: Vec<&str> = releases
+            .history
+            .iter()
+            .map(|info| info.version.as_str())
+            .collect();
+        let (stage, reason, candidates) = if self.matched == 0 {
+            (
+                ResolutionStage::History,
+                format!("no release in the history matches {}", self.version),
+                nearest_versions(&self.version, history, 5),
+            )
+        } else if self.positions.is_empty() {
+            let with_position = releases
+                .history
+                .iter()
+                .filter(|info| info.chromium_main_branch_position.is_some())
+                .map(|info| info.version.as_str());
+            (
+                ResolutionStage::BasePosition,
+                format!(
+                    "none of the {} matching releases has a base position",
+                    self.matched
+                ),
+                nearest_versions(&self.version, with_position, 5),
+            )
+        } else {
+            let reason = self.unpublished.take().unwrap_or_else(|| {
+                format!(
+                    "no {} snapshot within {SNAPSHOT_TOLERANCE} positions after the base position",
+                    releases.builds.prefix()
+                )
+            });
+            let candidates = self
+                .positions
+                .iter()
+                .map(|(version, pos)| {
+                    let (before, after) = releases.builds.nearest(*pos);
+                    let describe = |rev: Option<usize>| match rev {
+                        Some(rev) => format!("r{rev} ({} away)", rev.abs_diff(*pos)),
+                        None => "none".to_owned(),
+                    };
+                    format!(
+                        "{version} (r{pos}): before {}, after {}",
+                        describe(before),
+                        describe(after)
+                    )
+                })
+                .take(5)
+                .collect();
+            (ResolutionStage::Snapshot, reason, candidates)
+        };
+        let diagnostic = ResolutionDiagnostic {
+            browser: releases.options.variant.browser_name().to_owned(),
+            version: self.version.clone(),
+            platform: releases.builds.prefix().to_owned(),
+            channel: releases.channel.as_constant().to_owned(),
+            stage,
+            reason: reason.clone(),
+            candidates,
+        };
+        let message = format!(
+            "No downloadable {} build found for {}.",
+            diagnostic.browser, diagnostic.version
+        );
+        anyhow::Error::new(FetchError::VersionNotFound(message)).context(diagnostic)
+    }
 }
 
 impl<'r> Iterator for ChromiumReleaseMatches<'r> {
@@ -73,55 +255,530 @@ impl<'r> Iterator for ChromiumReleaseMatches<'r> {
 
     fn next(&mut self) -> Option<Self::Item> {
         for history in self.iter.by_ref() {
-            match history.chromium_main_branch_position {
-                Some(pos) => match self.releases.builds.find(pos, self.prefix) {
-                    Some(rev_prefix) => {
-                        return Some(Ok(ChromiumReleaseItem {
-                            rev_prefix: rev_prefix.clone(),
-                            version: history.version.clone(),
-                            client: self.releases.client.clone(),
-                        }))
+            let Some(pos) = history.chromium_main_branch_position else {
+                log::debug!("==> chromium {} has no base position", history.version);
+                continue;
+            };
+            self.positions.push((&history.version, pos));
+            match self.releases.builds.find(pos) {
+                Some((rev_prefix, revision)) => {
+                    self.yielded = true;
+                    return Some(Ok(ChromiumReleaseItem {
+                        rev_prefix: rev_prefix.clone(),
+                        revision,
+                        base_position: pos,
+                        platform: self.releases.platform,
+                        version: history.version.clone(),
+                        channel: history.channel.clone(),
+                        client: self.releases.client.clone(),
+                        downloads: self.releases.downloads.clone(),
+                        options: self.releases.options.clone(),
+                        paths: self.releases.paths.clone(),
+                        build_files: OnceCell::new(),
+                    }));
+                }
+                None => {
+                    log::debug!(
+                        "==> chromium {}: no snapshot found for r{pos}",
+                        history.version
+                    );
+                    if self.unpublished.is_none() {
+                        self.unpublished =
+                            unpublished_reason(&self.releases.builds, pos, &history.version);
                     }
-                    None => println!("==> no build found for rev: {pos}"),
-                },
-                None => println!(
-                    "==> chromium {}: no chromium_base_position.",
-                    history.version
-                ),
+                }
             }
         }
-        None
+        if self.yielded || self.diagnosed {
+            return None;
+        }
+        self.diagnosed = true;
+        Some(Err(self.diagnose()))
+    }
+}
+
+fn unpublished_reason(builds: &ChromiumBuilds, pos: usize, version: &str) -> Option<String> {
+    let prefix = builds.prefix();
+    match builds.published_range() {
+        None => Some(format!(
+            "Chromium snapshots are not published for {prefix}, {version} (r{pos}) is unavailable on this platform."
+        )),
+        Some((_, last)) if pos > last => Some(format!(
+            "Chromium snapshots are not published for {prefix} since r{last}, {version} (r{pos}) is unavailable on this platform."
+        )),
+        Some((first, _)) if pos < first => Some(format!(
+            "Chromium snapshots are not published for {prefix} before r{first}, {version} (r{pos}) is unavailable on this platform."
+        )),
+        Some(_) => None,
     }
 }
 
 pub(crate) struct ChromiumReleaseItem {
     rev_prefix: String,
+    revision: usize,
+    base_position: usize,
+    platform: Platform,
     version: String,
-    client: Client,
+    channel: String,
+    client: Http,
+    downloads: Downloads,
+    options: DownloadOptions,
+    paths: Paths,
+    /// 快照目录的文件列表，预先下载和安装时共用。
+    build_files: OnceCell<Vec<GoogleApiStorageObject>>,
 }
 
+#[async_trait]
 impl BrowserReleaseItem for ChromiumReleaseItem {
-    fn download(&self) -> Result<()> {
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    async fn prefetch_requests(&self) -> Result<Vec<DownloadRequest>> {
+        let zip_file = self.zip_file(self.build_files().await?)?;
+        Ok(vec![self.zip_request(zip_file)])
+    }
+
+    async fn download(&self) -> Result<PathBuf> {
+        let browser = self.options.variant.browser_name();
+        let path = with_install_lock(&self.paths, browser, &self.version, self.install()).await?;
+        // 复用已有的安装时没有经过 install()，单独补上 chromedriver。
+        if self.options.with_driver
+            && !self
+                .paths
+                .install_dir("chromedriver", &self.version)
+                .exists()
+        {
+            self.pair_driver(&InstallMetadata::read(&path)?).await?;
+        }
+        Ok(path)
+    }
+
+    async fn plan(&self) -> Result<DownloadPlan> {
+        self.check_drift()?;
+        let build_files = self.build_files().await?;
+        let mut requests = vec![self.zip_request(self.zip_file(build_files)?)];
+        let driver_cached = cached_driver(
+            &self.rev_prefix,
+            self.options.driver_cache_days,
+            &self.paths,
+        )
+        .is_some();
+        if self.options.with_driver && !driver_cached {
+            let file = find_driver_file(build_files)
+                .ok_or_else(|| anyhow!("No chromedriver found in {}", self.rev_prefix))?;
+            requests.extend(download_requests(&[file]));
+        }
+        Ok(DownloadPlan {
+            requests,
+            revision: Some(self.revision),
+            install_dir: self
+                .paths
+                .install_dir(self.options.variant.browser_name(), &self.version),
+        })
+    }
+}
+
+impl ChromiumReleaseItem {
+    async fn build_files(&self) -> Result<&[GoogleApiStorageObject]> {
+        let build_files = self
+            .build_files
+            .get_or_try_init(|| {
+                fetch_build_detail(
+                    self.options.variant.bucket(),
+                    &self.rev_prefix,
+                    &self.client,
+                )
+            })
+            .await?;
+        Ok(build_files)
+    }
+
+    fn zip_file<'f>(
+        &self,
+        build_files: &'f [GoogleApiStorageObject],
+    ) -> Result<&'f GoogleApiStorageObject> {
+        let names = match self.options.variant {
+            BuildVariant::Snapshot => self.snapshot_archives(),
+            // ASAN 构建的 prefix 就是压缩包本身。
+            BuildVariant::Asan => {
+                return build_files
+                    .iter()
+                    .find(|file| file.name == self.rev_prefix)
+                    .ok_or_else(|| anyhow!("No ASAN archive found at {}.", self.rev_prefix));
+            }
+        };
+        names
+            .iter()
+            .find_map(|name| {
+                let path = format!("{}{name}", self.rev_prefix);
+                build_files.iter().find(|file| file.name == path)
+            })
+            .ok_or_else(|| anyhow!("None of {} found in {}.", names.join(", "), self.rev_prefix))
+    }
+
+    /// 依次尝试的压缩包文件名：配置文件中为该快照目录指定的，否则按平台内置的顺序，
+    /// 避免上游改名后匹配到其他平台的压缩包。
+    fn snapshot_archives(&self) -> Vec<String> {
+        if let Some(names) = self.options.snapshot_archives.get(self.platform.prefix()) {
+            return names.clone();
+        }
+        let names: &[&str] = match (self.platform.os(), self.platform.arch()) {
+            (Os::Windows, Arch::Arm64) => &["chrome-win-arm64.zip", "chrome-win.zip"],
+            (Os::Windows, _) => &["chrome-win.zip", "chrome-win32.zip"],
+            (Os::Mac, _) => &["chrome-mac.zip"],
+            (Os::Linux, _) => &["chrome-linux.zip"],
+        };
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    /// 只缓存浏览器本身的压缩包，附加文件和 chromedriver 按需下载。
+    fn zip_request(&self, zip_file: &GoogleApiStorageObject) -> DownloadRequest {
+        let prefix = self.rev_prefix.split('/').next().unwrap_or_default();
+        let zip_name = zip_file.name.rsplit('/').next().unwrap_or(&zip_file.name);
+        download_requests(&[zip_file]).remove(0).with_cache_key(
+            self.options.variant.browser_name(),
+            &self.version,
+            prefix,
+            zip_name,
+        )
+    }
+
+    /// `--revision` 探测到的快照可能早于指定的 revision，`--strict` 时拒绝，超过阈值时给出警告并返回 `true`。
+    fn check_drift(&self) -> Result<bool> {
+        let drift = self.revision.abs_diff(self.base_position);
+        if self.options.strict && drift > 0 {
+            return Err(anyhow!(
+                "No {} snapshot exactly at r{} for {} (the nearest is r{}, drift {drift}), refusing it because of --strict.",
+                self.options.variant.browser_name(),
+                self.base_position,
+                self.version,
+                self.revision
+            ));
+        }
+        let approximate = drift > self.options.drift_warn;
+        if approximate {
+            status!(
+                "==> warning[snapshot-drift]: version={} base_position={} revision={} drift={} threshold={}",
+                self.version, self.base_position, self.revision, drift, self.options.drift_warn
+            );
+        }
+        Ok(approximate)
+    }
+
+    async fn install(&self) -> Result<PathBuf> {
+        let browser = self.options.variant.browser_name();
+        status!(
+            "==> matched {browser} {} from channel {}",
+            self.version,
+            self.channel
+        );
+        let approximate = self.check_drift()?;
+
         // 根据 prefix 找到该版本文件列表，以及 chrome-win.zip 文件信息。
-        let build_files = fetch_build_detail(&self.rev_prefix, &self.client)?;
-        let zip_file = [
-            "chrome-win.zip",
-            "chrome-win32.zip",
-            "chrome-mac.zip",
-            "chrome-linux.zip",
-        ]
-        .into_iter()
-        .find_map(|f| build_files.iter().find(|file| file.name.ends_with(f)))
+        let build_files = self.build_files().await?;
+        let zip_file = self.zip_file(build_files)?;
+
+        // 先保存到临时目录里面，全部下载完成后再移动到安装目录。
+        let temp_path = self.paths.temp_install_dir(browser, &self.version);
+        if temp_path.exists() {
+            std::fs::remove_dir_all(&temp_path)?;
+        }
+        mark_incomplete(&temp_path)?;
+        let archive_path = self.options.keep_archive.then(|| {
+            self.paths.output_dir.join(kept_archive_name(
+                browser,
+                &self.version,
+                self.platform,
+                "zip",
+            ))
+        });
+        if archive_path.is_some() {
+            std::fs::create_dir_all(&self.paths.output_dir)?;
+        }
+
+        let cached_driver = self
+            .options
+            .with_driver
+            .then(|| {
+                cached_driver(
+                    &self.rev_prefix,
+                    self.options.driver_cache_days,
+                    &self.paths,
+                )
+            })
+            .flatten();
+        let driver_file = match self.options.with_driver && cached_driver.is_none() {
+            true => Some(
+                find_driver_file(build_files)
+                    .ok_or_else(|| anyhow!("No chromedriver found in {}", self.rev_prefix))?,
+            ),
+            false => None,
+        };
+
+        let mut files = vec![zip_file];
+        files.extend(build_files.iter().filter(|file| {
+            file.name != zip_file.name && matches_asset(file, &self.options.assets)
+        }));
+        files.extend(driver_file);
+        let mut requests = download_requests(&files);
+        requests[0] = self.zip_request(zip_file);
+        let downloaded = self.downloads.fetch_all(&requests).await?;
+        let mut downloaded_driver = None;
+        for (file, downloaded) in files.iter().zip(&downloaded) {
+            if driver_file.is_some_and(|driver| driver.name == file.name) {
+                downloaded_driver = Some(downloaded);
+                continue;
+            }
+            let (file, downloaded, temp_path) =
+                ((*file).clone(), downloaded.clone(), temp_path.clone());
+            if file.name == zip_file.name {
+                let locales = self.options.locales.clone();
+                let tests = wants_tests(&self.options.assets);
+                let archive_path = archive_path.clone();
+                blocking(move || {
+                    extract_chromium_zip_file(&downloaded, &temp_path, &locales, tests)?;
+                    if let Some(archive_path) = &archive_path {
+                        std::fs::copy(&downloaded.path, archive_path)?;
+                    }
+                    Ok(())
+                })
+                .await?;
+            } else {
+                blocking(move || install_asset(&file, &downloaded, &temp_path)).await?;
+            }
+        }
+        if self.options.strict {
+            let missing: Vec<_> = self
+                .options
+                .locales
+                .iter()
+                .filter(|locale| {
+                    !temp_path
+                        .join("locales")
+                        .join(format!("{locale}.pak"))
+                        .exists()
+                })
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                return Err(anyhow!(
+                    "{browser} {} has no locale pack for {}, refusing it because of --strict.",
+                    self.version,
+                    missing.join(", ")
+                ));
+            }
+        }
+        let binary_version = {
+            let (temp_path, platform) = (temp_path.clone(), self.platform);
+            blocking(move || Ok(detect_binary_version(&temp_path, platform))).await?
+        };
+        match &binary_version {
+            Some(binary_version) if !same_milestone(binary_version, &self.version) => status!(
+                "==> warning[version-mismatch]: {browser} {} resolved to r{}, but the extracted binary reports {binary_version}",
+                self.version, self.revision
+            ),
+            Some(binary_version) => status!("==> binary version: {binary_version}"),
+            None => status!("==> could not determine the binary version of {browser} {}", self.version),
+        }
+        let devtools_protocol = match self.options.devtools_protocol {
+            true => {
+                Some(download_devtools_protocol(self.revision, &temp_path, &self.client).await?)
+            }
+            false => None,
+        };
+
+        let metadata = InstallMetadata {
+            platform: Some(self.platform.prefix().to_owned()),
+            arch: Some(self.platform.arch().as_str().to_owned()),
+            channel: Some(self.channel.clone()),
+            snapshot_prefix: Some(self.rev_prefix.clone()),
+            revision: Some(self.revision),
+            base_position: Some(self.base_position),
+            drift: Some(self.revision.abs_diff(self.base_position)),
+            approximate,
+            locales: (!self.options.locales.is_empty()).then(|| self.options.locales.clone()),
+            executable: Some(chromium_executable(self.platform.os()).to_owned()),
+            devtools_protocol,
+            binary_version,
+            snapshot_object: Some(SnapshotObject::from(zip_file)),
+            ..InstallMetadata::new(browser, &self.version)
+        };
+        metadata.write(&temp_path)?;
+        if let Some(archive_path) = &archive_path {
+            keep_archive(archive_path, &metadata)?;
+        }
+        if let Some(archive) = &cached_driver {
+            self.install_driver(archive, &metadata).await?;
+        }
+        if let (Some(file), Some(downloaded)) = (driver_file, downloaded_driver) {
+            let driver_version = self.install_driver(&downloaded.path, &metadata).await?;
+            cache_driver(
+                &self.rev_prefix,
+                &downloaded.path,
+                file,
+                driver_version,
+                &self.paths,
+            )?;
+        }
+
+        let base_path = self.paths.install_dir(browser, &self.version);
+        if base_path.exists() {
+            std::fs::remove_dir_all(&base_path)?;
+        }
+        std::fs::create_dir_all(&self.paths.output_dir)?;
+        move_dir(&temp_path, &base_path)?;
+        mark_complete(&base_path)?;
+        Ok(base_path)
+    }
+}
+
+impl ChromiumReleaseItem {
+    async fn install_driver(
+        &self,
+        archive: &Path,
+        metadata: &InstallMetadata,
+    ) -> Result<Option<String>> {
+        let browser_version = metadata
+            .binary_version
+            .clone()
+            .unwrap_or_else(|| self.version.clone());
+        let (archive, metadata) = (archive.to_owned(), metadata.clone());
+        let (platform, paths) = (self.platform, self.paths.clone());
+        blocking(move || install_driver(&archive, &metadata, &browser_version, platform, &paths))
+            .await
+    }
+
+    /// 为已安装的浏览器配对 chromedriver，缓存未过期时不需要联网。
+    async fn pair_driver(&self, metadata: &InstallMetadata) -> Result<()> {
+        if let Some(archive) = cached_driver(
+            &self.rev_prefix,
+            self.options.driver_cache_days,
+            &self.paths,
+        ) {
+            self.install_driver(&archive, metadata).await?;
+            return Ok(());
+        }
+        let build_files = fetch_build_detail(
+            self.options.variant.bucket(),
+            &self.rev_prefix,
+            &self.client,
+        )
+        .await?;
+        let file = find_driver_file(&build_files)
+            .ok_or_else(|| anyhow!("No chromedriver found in {}", self.rev_prefix))?;
+        let downloaded = self
+            .downloads
+            .fetch_all(&download_requests(&[file]))
+            .await?;
+        let driver_version = self.install_driver(&downloaded[0].path, metadata).await?;
+        cache_driver(
+            &self.rev_prefix,
+            &downloaded[0].path,
+            file,
+            driver_version,
+            &self.paths,
+        )
+    }
+}
+
+pub(crate) async fn verify_cached_releases(
+    platform: Platform,
+    channel: ReleaseChannel,
+    client: &Http,
+    paths: &Paths,
+) -> Result<CacheStatus> {
+    ChromiumHistory::verify_cache(platform, channel, client, paths).await
+}
+
+pub(crate) async fn verify_cached_builds(
+    platform: Platform,
+    variant: BuildVariant,
+    client: &Http,
+    paths: &Paths,
+) -> Result<CacheStatus> {
+    ChromiumBuilds::verify_cache(platform, variant, client.clone(), paths).await
+}
+
+/// 根据 metadata.json 中记录的快照目录，向已有的安装目录追加下载文件，不重新解析版本。
+pub(crate) async fn add_assets(
+    install_dir: &Path,
+    patterns: &[String],
+    client: &Http,
+    downloads: &DownloadManager,
+) -> Result<()> {
+    let metadata = InstallMetadata::read(install_dir).map_err(|err| {
+        anyhow!(
+            "Reading the metadata of {} failed: {err}",
+            install_dir.display()
+        )
+    })?;
+    let rev_prefix = metadata
+        .snapshot_prefix
+        .as_deref()
+        .filter(|_| metadata.browser == "chromium")
         .ok_or_else(|| {
             anyhow!(
-                "在版本 {} 中，未找到 chrome-win.zip/chrome-win32-zip/chrome-mac.zip。",
-                self.rev_prefix
+                "{} is not a Chromium snapshot install.",
+                install_dir.display()
             )
         })?;
+    let build_files =
+        fetch_build_detail(BuildVariant::Snapshot.bucket(), rev_prefix, client).await?;
+    let assets: Vec<_> = build_files
+        .iter()
+        .filter(|file| matches_asset(file, patterns))
+        .collect();
+    if assets.is_empty() {
+        return Err(anyhow!(
+            "No file in {rev_prefix} matches {}",
+            patterns.join(", ")
+        ));
+    }
+    let downloaded = downloads.fetch_all(&download_requests(&assets)).await?;
+    mark_incomplete(install_dir)?;
+    let (assets, install_dir) = (
+        assets.into_iter().cloned().collect::<Vec<_>>(),
+        install_dir.to_owned(),
+    );
+    blocking(move || {
+        for (file, downloaded) in assets.iter().zip(&downloaded) {
+            install_asset(file, downloaded, &install_dir)?;
+        }
+        mark_complete(&install_dir)
+    })
+    .await
+}
+
+fn download_requests(files: &[&GoogleApiStorageObject]) -> Vec<DownloadRequest> {
+    files
+        .iter()
+        .map(|file| {
+            DownloadRequest::new(&file.media_link)
+                .with_size(file.size.parse().ok())
+                .with_checksum(file.crc32c.clone().map(Checksum::Crc32c))
+        })
+        .collect()
+}
+
+fn matches_asset(file: &GoogleApiStorageObject, patterns: &[String]) -> bool {
+    let file_name = file.name.rsplit('/').next().unwrap_or(&file.name);
+    patterns
+        .iter()
+        .any(|pattern| wildcard_match(pattern, file_name))
+}
+
+/// 请求了测试程序时，保留浏览器压缩包中的 interactive_ui_tests。
+fn wants_tests(patterns: &[String]) -> bool {
+    ["interactive_ui_tests", "interactive_ui_tests.exe"]
+        .iter()
+        .any(|name| patterns.iter().any(|pattern| wildcard_match(pattern, name)))
+}
 
-        // 先保存到临时目录里面，待解压的时候，找到里面的版本信息，再重命名一下文件夹。
-        let base_path = std::env::current_dir()?.join(format!("chromium-{}", self.version));
-        std::fs::create_dir_all(&base_path)?;
-        download_chromium_zip_file(zip_file, &base_path, &self.client)
+fn chromium_executable(os: Os) -> &'static str {
+    match os {
+        Os::Windows => "chrome.exe",
+        Os::Linux => "chrome",
+        Os::Mac => "Chromium.app/Contents/MacOS/Chromium",
     }
 }
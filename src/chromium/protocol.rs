@@ -0,0 +1,73 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{error::FetchError, http::Http};
+
+/// 安装目录中保存协议定义的子目录。
+const PROTOCOL_DIR: &str = "devtools-protocol";
+
+const PROTOCOL_FILES: [&str; 2] = ["browser_protocol.json", "js_protocol.json"];
+
+#[derive(Deserialize)]
+struct NpmPackage {
+    versions: BTreeMap<String, serde::de::IgnoredAny>,
+}
+
+/// 下载与 revision 对应的 CDP 协议定义（browser_protocol.json、js_protocol.json）。
+///
+/// npm 上的 `devtools-protocol` 包以 `0.0.<revision>` 发布每次协议变更，
+/// 取不晚于该 revision 的最新一版，即为该构建使用的协议。返回所用的包版本。
+pub(crate) async fn download_devtools_protocol(
+    revision: usize,
+    base_path: &Path,
+    client: &Http,
+) -> Result<String> {
+    let url = "https://registry.npmjs.org/devtools-protocol";
+    status!("==> retrieving devtools-protocol versions ...");
+    let package: NpmPackage = client.get(url).await?.json().await?;
+    let protocol_revision = package
+        .versions
+        .keys()
+        .filter_map(|version| version.strip_prefix("0.0.")?.parse::<usize>().ok())
+        .filter(|rev| *rev <= revision)
+        .max()
+        .ok_or_else(|| anyhow!("No devtools protocol published before r{revision}."))?;
+    let version = format!("0.0.{protocol_revision}");
+
+    let protocol_path = base_path.join(PROTOCOL_DIR);
+    std::fs::create_dir_all(&protocol_path)?;
+    for file in PROTOCOL_FILES {
+        let url = format!("https://unpkg.com/devtools-protocol@{version}/json/{file}");
+        let response = client.get(&url).await?;
+        if !response.is_success() {
+            return Err(FetchError::Network(format!(
+                "Download {url} failed: {}",
+                response.status()
+            ))
+            .into());
+        }
+        let content = response.bytes().await?;
+        let protocol: Value = serde_json::from_slice(&content)?;
+        let domains = protocol
+            .get("domains")
+            .and_then(Value::as_array)
+            .map(Vec::len)
+            .unwrap_or_default();
+        let protocol_version = protocol
+            .get("version")
+            .map(|v| {
+                format!(
+                    "{}.{}",
+                    v["major"].as_str().unwrap_or("?"),
+                    v["minor"].as_str().unwrap_or("?")
+                )
+            })
+            .unwrap_or_default();
+        std::fs::write(protocol_path.join(file), &content)?;
+        status!("==> devtools protocol {version} (r{protocol_revision}): {file} v{protocol_version}, {domains} domains");
+    }
+    Ok(version)
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use crate::{
+    common::{
+        sort_newest_first, BrowserReleaseItem, BuildVariant, DownloadOptions, ReleaseChannel,
+    },
+    downloads::Downloads,
+    error::FetchError,
+    http::Http,
+    paths::Paths,
+    platform::Platform,
+    session::Session,
+};
+
+use super::{
+    builds::{list_objects, GoogleApiStorageObject},
+    ChromiumReleaseItem, ChromiumReleaseMatches, ChromiumReleases,
+};
+
+/// `--revision` 指定的 revision 没有快照时，向前后各探测多少个 revision。
+const REVISION_PROBE_RANGE: usize = 10;
+
+/// 解析出的 Chromium 快照，可以交给其他机器用 `fb --revision` 直接下载，不必再查找版本。
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedBuild {
+    pub version: String,
+    pub channel: String,
+    /// 快照所在的目录，例如 `Win_x64`。
+    pub platform: String,
+    /// 发布对应的主干位置。
+    pub base_position: usize,
+    /// 实际使用的快照 revision，不小于 `base_position`。
+    pub revision: usize,
+    pub snapshot_prefix: String,
+}
+
+impl From<&ChromiumReleaseItem> for ResolvedBuild {
+    fn from(item: &ChromiumReleaseItem) -> Self {
+        Self {
+            version: item.version.clone(),
+            channel: item.channel.clone(),
+            platform: item.platform.prefix().to_owned(),
+            base_position: item.base_position,
+            revision: item.revision,
+            snapshot_prefix: item.rev_prefix.clone(),
+        }
+    }
+}
+
+/// 列出与 `version` 匹配、且有快照可下载的所有版本，从新到旧排列。
+///
+/// 一个都没有时返回的错误带有说明失败阶段和最接近的候选项的诊断信息。
+pub async fn list_chromium_versions(
+    session: &Session,
+    version: &str,
+    platform: Platform,
+    channel: ReleaseChannel,
+) -> Result<Vec<ResolvedBuild>> {
+    let releases =
+        ChromiumReleases::new(session, platform, channel, DownloadOptions::default()).await?;
+    let mut builds =
+        ChromiumReleaseMatches::new(&releases, version, releases.history.find(version))
+            .map(|item| Ok(ResolvedBuild::from(&item?)))
+            .collect::<Result<Vec<_>>>()?;
+    sort_newest_first(&mut builds, |build| &build.version);
+    Ok(builds)
+}
+
+/// 解析 `version` 对应的最新的快照，与不带 `--pick` 时下载的版本相同。
+pub async fn resolve_chromium(
+    session: &Session,
+    version: &str,
+    platform: Platform,
+    channel: ReleaseChannel,
+) -> Result<ResolvedBuild> {
+    list_chromium_versions(session, version, platform, channel)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            FetchError::VersionNotFound(format!("No Chromium snapshot found for {version}.")).into()
+        })
+}
+
+/// `fb --revision`：跳过发布历史和快照列表，直接使用指定 revision 的快照。
+///
+/// 该 revision 没有快照时由近及远探测前后的 revision（`--strict` 时不探测）。
+/// 没有版本号可用，安装目录和 metadata 使用 `version`（通常是解析时得到的版本），未指定时使用 `r<revision>`。
+pub(crate) async fn revision_release(
+    revision: usize,
+    version: Option<&str>,
+    platform: Platform,
+    client: &Http,
+    downloads: &Downloads,
+    options: DownloadOptions,
+    paths: &Paths,
+) -> Result<Box<dyn BrowserReleaseItem>> {
+    if options.variant != BuildVariant::Snapshot {
+        return Err(anyhow!("--revision only supports snapshot builds."));
+    }
+    let range = match options.strict {
+        true => 0,
+        false => REVISION_PROBE_RANGE,
+    };
+    let (found, build_files) = probe_revision(revision, range, platform, client).await?;
+    if found != revision {
+        status!("==> no snapshot at r{revision}, using the nearest r{found}");
+    }
+    Ok(Box::new(ChromiumReleaseItem {
+        rev_prefix: format!("{}/{found}/", platform.prefix()),
+        revision: found,
+        base_position: revision,
+        platform,
+        version: version
+            .map(str::to_owned)
+            .unwrap_or_else(|| format!("r{found}")),
+        channel: "snapshot".to_owned(),
+        client: client.clone(),
+        downloads: downloads.clone(),
+        options,
+        paths: paths.clone(),
+        build_files: OnceCell::from(build_files),
+    }))
+}
+
+/// 依次列出 `revision`、`revision + 1`、`revision - 1`……的快照目录，返回第一个有文件的 revision 及其文件列表。
+async fn probe_revision(
+    revision: usize,
+    range: usize,
+    platform: Platform,
+    client: &Http,
+) -> Result<(usize, Vec<GoogleApiStorageObject>)> {
+    let bucket = BuildVariant::Snapshot.bucket();
+    if BuildVariant::Snapshot.prefix(platform).is_none() {
+        return Err(FetchError::UnsupportedPlatform(format!(
+            "No chromium snapshots are published for {}.",
+            platform.prefix()
+        ))
+        .into());
+    }
+    let candidates = (0..=range).flat_map(|distance| {
+        let after = revision + distance;
+        let before = revision.checked_sub(distance).filter(|_| distance > 0);
+        std::iter::once(after).chain(before)
+    });
+    for candidate in candidates {
+        let prefix = format!("{}/{candidate}/", platform.prefix());
+        status!("==> probing snapshot {prefix} ...");
+        let files = list_objects(bucket, &prefix, client).await?;
+        if !files.is_empty() {
+            status!("==> files:");
+            for file in &files {
+                status!("    {}", file.name);
+            }
+            return Ok((candidate, files));
+        }
+    }
+    let message = match range {
+        0 => format!("No {} snapshot at r{revision}.", platform.prefix()),
+        _ => format!(
+            "No {} snapshot within {range} revisions of r{revision}.",
+            platform.prefix()
+        ),
+    };
+    Err(FetchError::VersionNotFound(message).into())
+}
@@ -0,0 +1,1814 @@
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use futures::{future::BoxFuture, TryStreamExt};
+use reqwest::{ClientBuilder, Url};
+use serde_json::json;
+use tokio::{runtime::Handle, sync::OnceCell};
+
+use crate::{
+    archive, bundle,
+    cache::{self, CacheFilter},
+    cft::{channel_alias, CftBinary, CftReleases, ChromiumSource},
+    check, checksums,
+    chromium::{
+        self, parse_revision, print_changelog, print_channels, print_deps_diff, print_snapshots,
+        print_whatis, ChromiumReleases, HistorySource,
+    },
+    common::{
+        download_browser, select_release, sort_newest_first, BrowserReleaseItem, BrowserReleases,
+        BuildVariant, DownloadOptions, ReleaseChannel, VersionPick,
+    },
+    config::{update_alias, Config, MirrorConfig},
+    connections, crx,
+    custom::CustomReleases,
+    daemon, deadline,
+    downloads::{ArchiveCache, DownloadManager, Downloads},
+    edge::EdgeReleases,
+    error::ErrorCategory,
+    events,
+    firefox::FirefoxReleases,
+    fork::ChromiumFork,
+    http::{
+        Http, MirrorTransport, MockTransport, RateLimitedTransport, RecordingTransport,
+        ReqwestTransport,
+    },
+    lock,
+    metadata::InstallMetadata,
+    metrics,
+    pac::PacResolver,
+    paths::{validate_dir_name, NameBy, Paths},
+    platform::{Arch, Os, Platform},
+    progress,
+    queue::DownloadQueue,
+    schema::SchemaName,
+    shortcut, summary,
+    summary::{OutputFormat, PlanReport, PlannedFile},
+    sync::{LockedEntry, LockedFile, SyncLock, SyncManifest, SyncPlan},
+    system_proxy::{self, env_proxy_set, ProxyBypass, SystemProxy},
+    timings,
+    utils::{parse_duration, run_bounded},
+    version::extract_version,
+    which,
+};
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Target OS (`windows`, `linux`, `mac`), defaults to the host's. Several comma-separated
+    /// values fetch for each of them, downloading the next while extracting the previous.
+    #[arg(short, long, global = true, value_delimiter = ',')]
+    os: Vec<String>,
+
+    /// Target architecture (`x86`, `x64`, `arm64`), defaults to the host's.
+    #[arg(long, global = true)]
+    arch: Option<String>,
+
+    /// Version prefix (`102.1`), exact version (`=102.1.0`), inclusive range (`100..102`),
+    /// or a channel (`stable`, `beta`, `dev`, `canary`) resolved through Chrome for Testing.
+    #[arg(required_unless_present_any = ["revision", "from_edge", "from_brave", "from_electron"])]
+    browser_version: Option<String>,
+
+    /// Download this exact Chromium snapshot revision (e.g. `r1135580`) without resolving a version.
+    #[arg(long, value_name = "REV", conflicts_with_all = ["firefox", "edge", "provider", "pick", "oldest"])]
+    revision: Option<String>,
+
+    /// Fetch the Chromium build closest to this Microsoft Edge version (e.g. `120.0.2210.91`).
+    #[arg(long, value_name = "VERSION", conflicts_with_all = ["browser_version", "revision", "firefox", "edge", "provider", "from_brave"])]
+    from_edge: Option<String>,
+
+    /// Fetch the Chromium build that this Brave version (e.g. `1.61.109`) is based on.
+    #[arg(long, value_name = "VERSION", conflicts_with_all = ["browser_version", "revision", "firefox", "edge", "provider", "from_electron"])]
+    from_brave: Option<String>,
+
+    /// Fetch the Chromium build embedded in this Electron release (e.g. `28.1.0`).
+    #[arg(long, value_name = "VERSION", conflicts_with_all = ["browser_version", "revision", "firefox", "edge", "provider", "from_edge"])]
+    from_electron: Option<String>,
+
+    #[arg(long, global = true)]
+    chrome: bool,
+
+    #[arg(long, global = true)]
+    firefox: bool,
+
+    /// Download Microsoft Edge (the recent releases of `--channel` listed by Edge's update service).
+    #[arg(long, global = true)]
+    edge: bool,
+
+    #[arg(short, long, global = true)]
+    proxy: Option<String>,
+
+    /// Choose the proxy per host with a proxy auto-config (PAC) file, by URL or local path.
+    #[arg(long, value_name = "URL", global = true, conflicts_with = "proxy")]
+    pac_url: Option<String>,
+
+    /// Use the proxy configured in the OS (Windows Internet/WinHTTP settings, macOS network settings)
+    /// when neither `--proxy`, `--pac-url` nor a proxy environment variable is set.
+    #[arg(long, global = true)]
+    system_proxy: bool,
+
+    /// Release channel, defaults to stable. `any` searches all channels but extended stable. With
+    /// --firefox, `dev` and `canary` (aliases `devedition`, `nightly`) select Developer Edition and
+    /// Nightly; `esr` is Firefox only and `extended` (alias `extended-stable`) Chromium only.
+    #[arg(long, value_enum, global = true)]
+    channel: Option<ReleaseChannel>,
+
+    /// Where to fetch the Chromium release history; `auto` falls back through the sources in order.
+    #[arg(long, value_enum, global = true, default_value = "auto")]
+    history_source: HistorySource,
+
+    /// Where Chromium builds come from: the nearest `snapshots` build, or the exact
+    /// `chrome-for-testing` release.
+    #[arg(long, value_enum, global = true, default_value = "snapshots")]
+    source: ChromiumSource,
+
+    /// Chromium build variant, `asan` downloads AddressSanitizer builds.
+    #[arg(long, value_enum, global = true, default_value = "snapshot")]
+    variant: BuildVariant,
+
+    /// Name of a custom provider defined in the config file.
+    #[arg(long)]
+    provider: Option<String>,
+
+    /// Additional files of the Chromium snapshot to download, e.g. `*syms*` or `*-tests.zip`.
+    #[arg(long = "asset", value_name = "PATTERN")]
+    assets: Vec<String>,
+
+    /// Keep the downloaded archive next to the install for `fetchbrowser extract`.
+    #[arg(long)]
+    keep_archive: bool,
+
+    /// Firefox installer locale, e.g. `de`; defaults to the system locale (LC_ALL, LC_MESSAGES,
+    /// LANG), else en-US.
+    #[arg(long, value_name = "LOCALE")]
+    lang: Option<String>,
+
+    /// Only keep these Chromium locale packs, e.g. `en-US,de`.
+    #[arg(long, value_name = "LOCALES", value_delimiter = ',')]
+    chromium_locales: Vec<String>,
+
+    /// Also download the DevTools protocol definitions matching the Chromium revision.
+    #[arg(long)]
+    devtools_protocol: bool,
+
+    /// Fail instead of retrying with the 32-bit build when the 64-bit one is unavailable.
+    #[arg(long)]
+    no_arch_fallback: bool,
+
+    /// Reuse an existing install of the matched version instead of downloading it again.
+    #[arg(long)]
+    skip_existing: bool,
+
+    /// With `--skip-existing`, re-hash N random files recorded in SHA256SUMS before reusing an install.
+    #[arg(long, value_name = "N", requires = "skip_existing")]
+    spot_check: Option<usize>,
+
+    /// Also install the chromedriver of the same Chromium snapshot (or Chrome for Testing version)
+    /// next to the browser; with --firefox, install a compatible geckodriver into the Firefox directory.
+    #[arg(long)]
+    with_driver: bool,
+
+    /// Also install chrome-headless-shell of the same Chrome for Testing version.
+    #[arg(long)]
+    with_headless_shell: bool,
+
+    /// Also download the complete MAR update package of the Firefox version.
+    #[arg(long)]
+    with_mar: bool,
+
+    /// Neither use nor fill the archive cache, download everything again.
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Download archives again even when cached, and update the cache.
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    refresh: bool,
+
+    /// Skip verifying downloads against SHA512SUMS (Firefox) or the storage object's CRC32C (Chromium).
+    #[arg(long, global = true)]
+    no_verify: bool,
+
+    /// Fail instead of installing approximate snapshots, other architectures or other locales.
+    #[arg(long)]
+    strict: bool,
+
+    /// Warn when the chosen snapshot is more than N positions away from the release.
+    #[arg(long, value_name = "N")]
+    drift_warn: Option<usize>,
+
+    /// Pick the oldest matched version instead of the newest.
+    #[arg(long, conflicts_with = "pick")]
+    oldest: bool,
+
+    /// Pick an exact version, or the N-th entry of `--list-matches`, among the matched versions.
+    #[arg(long, value_name = "N|VERSION")]
+    pick: Option<String>,
+
+    /// Print all matched versions instead of downloading.
+    #[arg(long, conflicts_with_all = ["oldest", "pick"])]
+    list_matches: bool,
+
+    /// Resolve the version and print which archives would be downloaded and where they would be
+    /// installed, without downloading.
+    #[arg(long, conflicts_with_all = ["list_matches", "create_shortcut"])]
+    dry_run: bool,
+
+    /// Maximum number of files downloaded concurrently.
+    #[arg(long, default_value_t = 4)]
+    jobs: usize,
+
+    /// Split each large archive into N ranges downloaded concurrently.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    connections: usize,
+
+    /// Compare the cached release data against upstream before downloading.
+    #[arg(long)]
+    verify_cache: bool,
+
+    /// Refetch cached release and build lists older than this (e.g. `12h`), `0` never expires them.
+    /// Defaults to `cache-ttl` in the config file, or 24h.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    cache_ttl: Option<Duration>,
+
+    /// Pause N milliseconds after each extracted file, for antivirus scanners on locked-down machines.
+    #[arg(long, value_name = "MS", global = true)]
+    extract_throttle: Option<u64>,
+
+    /// Create desktop and start menu shortcuts for the installed browser (Windows only).
+    #[arg(long)]
+    create_shortcut: bool,
+
+    /// Print debug log lines, such as every extracted file instead of a progress counter.
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Hide download and extraction progress.
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Format of the summary printed at the end; `json-lines` also prints JSON events while running.
+    /// With `json` and `json-lines`, log lines go to stderr.
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    format: OutputFormat,
+
+    /// Abort the run after this long (e.g. `90s`, `10m`, `1h`), exiting with code 124.
+    /// Ignored by `fb daemon`.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    deadline: Option<Duration>,
+
+    /// Report time spent per phase at the end of the run.
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// Report requests and opened connections per host at the end of the run.
+    #[arg(long, global = true)]
+    audit_connections: bool,
+
+    /// Write download and cache metrics in the Prometheus text format to FILE at the end of the run.
+    #[arg(long, global = true, value_name = "FILE")]
+    metrics_file: Option<PathBuf>,
+
+    /// Only use HTTP/2, failing on servers that do not support it.
+    #[arg(long, global = true)]
+    http2: bool,
+
+    /// Directory browsers are installed into, defaults to the current directory.
+    #[arg(long, global = true, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Directory used for temporary extraction, defaults to the system temp directory.
+    #[arg(long, global = true, value_name = "DIR")]
+    temp_dir: Option<PathBuf>,
+
+    /// Directory of the cached release data.
+    #[arg(long, global = true, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Name install directories by full version (`chromium-114.0.5735.90`) or milestone (`chromium-M114`).
+    #[arg(long, value_enum, global = true, default_value = "version")]
+    name_by: NameBy,
+
+    /// Template of install directory names, e.g. `{browser}-{version}-{arch}`.
+    /// Placeholders: {browser}, {version}, {major}, {os}, {arch}.
+    #[arg(
+        long,
+        global = true,
+        value_name = "TEMPLATE",
+        conflicts_with = "name_by"
+    )]
+    dir_name: Option<String>,
+
+    #[arg(long, hide = true, global = true, conflicts_with = "replay_fixtures")]
+    record_fixtures: Option<PathBuf>,
+
+    #[arg(long, hide = true, global = true)]
+    replay_fixtures: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the cached metadata.
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// List every channel/OS/date where an exact Chromium version appeared.
+    Channels { version: String },
+    /// Map a revision such as `r972766` back to the nearest release branch points.
+    Whatis { revision: String },
+    /// List the available Chromium snapshot revisions of a platform.
+    Snapshots {
+        /// Lowest revision, e.g. `r1100000`.
+        #[arg(long)]
+        from: Option<String>,
+        /// Highest revision, e.g. `r1101000`.
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Print the commits between two Chromium versions.
+    Changelog {
+        from: String,
+        to: String,
+        /// Maximum number of commits to fetch.
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// Compare the V8, Skia and Chromium branch of two Chromium versions.
+    Deps { from: String, to: String },
+    /// Print the JSON Schema of a machine-readable output.
+    Schema {
+        #[arg(value_enum, default_value_t = SchemaName::Metadata)]
+        name: SchemaName,
+    },
+    /// Download the CRX of an extension as served to the given Chrome version.
+    Crx {
+        /// Extension id, e.g. `cjpalhdlnbpafiamejdnhcphjbkeiagm`.
+        id: String,
+        /// Chrome version the extension must be compatible with.
+        #[arg(long)]
+        chrome_version: String,
+    },
+    /// Download additional snapshot files into an existing Chromium install.
+    AddAsset {
+        /// Install directory, relative to the output directory.
+        install: PathBuf,
+        /// Files of the snapshot to download, e.g. `*syms*` or `*-tests.zip`.
+        #[arg(long = "asset", value_name = "PATTERN", required = true)]
+        assets: Vec<String>,
+    },
+    /// Verify and re-extract an archive kept with `--keep-archive` into the output directory.
+    Extract { archive: PathBuf },
+    /// Launch an installed browser headless and diagnose common startup failures.
+    Check {
+        /// Install directory, relative to the output directory.
+        install: PathBuf,
+    },
+    /// Print the executable path of the newest installed browser matching a version.
+    Which {
+        /// Browser name, e.g. `chromium`, `firefox` or a custom provider.
+        browser: String,
+        version: String,
+    },
+    /// Show the install events recorded in the cache directory.
+    History {
+        /// Only show the last N events.
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+    },
+    /// Pack installed browsers into an offline bundle, or install one.
+    #[command(subcommand)]
+    Bundle(BundleCommand),
+    /// Manage version aliases, usable as `@name` wherever a version is accepted.
+    #[command(subcommand)]
+    Alias(AliasCommand),
+    /// Install the browsers listed in a manifest into the output directory.
+    Sync {
+        /// TOML manifest with `[[browsers]]` entries of `browser`, `version` and optionally `os` and
+        /// `arch`. The resolved versions are locked in a `.lock` file next to it.
+        manifest: PathBuf,
+        /// Only print the plan.
+        #[arg(long)]
+        dry_run: bool,
+        /// Remove installs that are not listed in the manifest.
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Serve `POST /fetch` and `GET /status/<id>` so orchestrators can request browsers over HTTP.
+    Daemon {
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
+        /// Maximum number of fetches run at the same time; further jobs wait in a queue.
+        #[arg(long, default_value_t = 2)]
+        workers: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BundleCommand {
+    /// Pack the installed browsers in the output directory matching the given versions.
+    Create {
+        bundle: PathBuf,
+        #[arg(required = true)]
+        versions: Vec<String>,
+    },
+    /// Unpack a bundle into the output directory.
+    Install { bundle: PathBuf },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Delete the cached entries of the selected browser, OS and channel.
+    Invalidate,
+    /// Compare the cached entries of the selected browser, OS and channel against upstream.
+    Verify,
+    /// Delete all cached release lists, chromedrivers and archives.
+    Clear,
+    /// List the cached files with their size and age.
+    List,
+    /// Print the cache directory.
+    Path,
+}
+
+#[derive(Subcommand, Debug)]
+enum AliasCommand {
+    /// Point an alias at a version, e.g. `alias set default-chrome 114.0.5735.90`.
+    Set { name: String, version: String },
+    /// Delete an alias.
+    Remove { name: String },
+    /// Print all aliases.
+    List,
+}
+
+/// 命令行入口，`fb` 可执行文件只调用这个函数。
+pub fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {err:?}");
+        let category = ErrorCategory::of(&err);
+        if let Some(hint) = category.hint() {
+            eprintln!("Hint: {hint}");
+        }
+        std::process::exit(category.exit_code());
+    }
+}
+
+fn run() -> Result<()> {
+    let mut args = Args::parse();
+    summary::init_logger(args.verbose);
+    resolve_aliases(&mut args)?;
+    // JSON 输出时不显示以 `\r` 刷新的进度，便于按行解析。
+    progress::set_quiet(args.quiet || args.format != OutputFormat::Text);
+    summary::set_format(args.format);
+    checksums::set_verify(!args.no_verify);
+    let config = Config::load()?;
+    // 守护进程长期运行，`--deadline` 只限制单次获取。
+    let daemon = matches!(args.command, Some(Command::Daemon { .. }));
+    if let Some(limit) = args.deadline.filter(|_| !daemon) {
+        deadline::start(limit);
+    }
+    progress::set_extract_throttle(args.extract_throttle.unwrap_or_default());
+    lock::set_skip_existing(args.skip_existing, args.spot_check.unwrap_or_default());
+    let mut paths = Paths::resolve(
+        args.output_dir.as_deref(),
+        args.temp_dir.as_deref(),
+        args.cache_dir.as_deref(),
+    )?;
+    paths.name_by = args.name_by;
+    paths.history_source = args.history_source;
+    paths.cache.set_signing_key(
+        std::env::var("FETCHBROWSER_CACHE_KEY")
+            .ok()
+            .or(config.cache_signing_key),
+    );
+    if let Some(ttl) = args.cache_ttl.or(config
+        .cache_ttl
+        .as_deref()
+        .map(parse_duration)
+        .transpose()?)
+    {
+        paths.cache.set_ttl(ttl);
+    }
+    if let Err(err) = cache::migrate_legacy(&paths) {
+        status!("==> warning[cache-migration]: migrating the legacy cache failed: {err}");
+    }
+    if let Some(template) = &args.dir_name {
+        validate_dir_name(template)?;
+        if args.os.len() > 1 && !template.contains("{os}") {
+            return Err(anyhow!(
+                "--dir-name must contain {{os}} when fetching for several OSes."
+            ));
+        }
+        paths.dir_name = Some(template.clone());
+        paths.platform = Some(host_platform(&args)?);
+    } else if args.os.len() > 1 {
+        // 各平台的安装目录不能相同。
+        paths.dir_name = Some("{browser}-{version}-{os}".to_owned());
+    }
+    // 网络请求在 tokio 运行时中执行，命令行本身仍然是同步的。
+    let runtime = tokio::runtime::Runtime::new()?;
+    let transport = SharedTransport::default();
+    runtime.block_on(translate_fork(&mut args, &transport))?;
+    let result = runtime.block_on(async {
+        match &args.command {
+            Some(command) => run_command(&args, command, &transport, &paths).await,
+            None if args.list_matches => list_matches(&args, &transport, &paths).await,
+            None if args.dry_run => dry_run(&args, &transport, &paths).await,
+            None => {
+                let start = Instant::now();
+                let result = fetch_browsers(&args, &transport, &paths).await;
+                deadline::wait_if_cancelled();
+                summary::print_summary(&result, start.elapsed());
+                result.map(|_| ())
+            }
+        }
+    });
+    deadline::wait_if_cancelled();
+    if args.timings {
+        timings::report();
+    }
+    if args.audit_connections {
+        connections::report();
+    }
+    if let Some(path) = &args.metrics_file {
+        if let Err(err) = metrics::write(path) {
+            status!(
+                "==> warning[metrics]: writing {} failed: {err}",
+                path.display()
+            );
+        }
+    }
+    result
+}
+
+async fn run_command(
+    args: &Args,
+    command: &Command,
+    transport: &SharedTransport,
+    paths: &Paths,
+) -> Result<()> {
+    match command {
+        Command::Cache(CacheCommand::Invalidate) => {
+            let removed = cache::invalidate(&cache_filter(args)?, paths)?;
+            if removed.is_empty() {
+                status!("==> nothing to invalidate");
+            }
+            for path in removed {
+                status!("==> removed {}", path.display());
+            }
+            Ok(())
+        }
+        Command::Cache(CacheCommand::Clear) => {
+            let removed = cache::clear(paths)?;
+            status!(
+                "==> removed {removed} cached files from {}",
+                paths.cache_dir.display()
+            );
+            Ok(())
+        }
+        Command::Cache(CacheCommand::List) => cache::list(paths),
+        Command::Cache(CacheCommand::Path) => {
+            println!("{}", paths.cache_dir.display());
+            Ok(())
+        }
+        Command::Cache(CacheCommand::Verify) => {
+            match cache::verify(&cache_filter(args)?, &transport.get(args).await?, paths).await? {
+                0 => Ok(()),
+                diverged => Err(anyhow!("{diverged} cached files diverged from upstream.")),
+            }
+        }
+        Command::Channels { version } => {
+            print_channels(version, &transport.get(args).await?, paths).await
+        }
+        Command::Whatis { revision } => {
+            print_whatis(
+                parse_revision(revision)?,
+                host_platform(args)?,
+                args.channel.unwrap_or(ReleaseChannel::Any),
+                &transport.get(args).await?,
+                paths,
+            )
+            .await
+        }
+        Command::Snapshots { from, to } => {
+            print_snapshots(
+                from.as_deref().map(parse_revision).transpose()?,
+                to.as_deref().map(parse_revision).transpose()?,
+                host_platform(args)?,
+                args.variant,
+                &transport.get(args).await?,
+                paths,
+            )
+            .await
+        }
+        Command::Changelog { from, to, limit } => {
+            print_changelog(
+                from,
+                to,
+                *limit,
+                host_platform(args)?,
+                args.channel.unwrap_or(ReleaseChannel::Any),
+                &transport.get(args).await?,
+                paths,
+            )
+            .await
+        }
+        Command::Deps { from, to } => {
+            print_deps_diff(
+                from,
+                to,
+                host_platform(args)?,
+                args.channel.unwrap_or(ReleaseChannel::Any),
+                &transport.get(args).await?,
+                paths,
+            )
+            .await
+        }
+        Command::Schema { name } => {
+            print!("{}", name.content());
+            Ok(())
+        }
+        Command::Crx { id, chrome_version } => {
+            let downloads = build_downloads(&transport.get(args).await?, args, paths);
+            crx::download_crx(id, chrome_version, &downloads, paths)
+                .await
+                .map(|_| ())
+        }
+        Command::AddAsset { install, assets } => {
+            let client = transport.get(args).await?;
+            let install_dir = paths.output_dir.join(install);
+            chromium::add_assets(
+                &install_dir,
+                assets,
+                &client,
+                &build_downloads(&client, args, paths),
+            )
+            .await?;
+            events::record(paths, "add-asset", &install_dir);
+            Ok(())
+        }
+        Command::Extract { archive } => {
+            let path = archive::extract_kept_archive(archive, paths)?;
+            status!("==> extracted to {}", path.display());
+            events::record(paths, "extract", &path);
+            Ok(())
+        }
+        Command::Check { install } => check::check_install(&paths.output_dir.join(install)),
+        Command::Bundle(BundleCommand::Create { bundle, versions }) => {
+            bundle::create_bundle(bundle, versions, &paths.output_dir)
+        }
+        Command::Bundle(BundleCommand::Install { bundle }) => {
+            for path in bundle::install_bundle(bundle, &paths.output_dir)? {
+                events::record(paths, "bundle-install", &path);
+            }
+            Ok(())
+        }
+        Command::History { limit } => events::print_history(paths, *limit),
+        Command::Sync {
+            manifest,
+            dry_run,
+            prune,
+        } => run_sync(args, manifest, *dry_run, *prune, transport, paths).await,
+        Command::Daemon { listen, workers } => {
+            run_daemon(args, listen, *workers, transport, paths).await
+        }
+        Command::Which { browser, version } => which::print_which(browser, version, paths),
+        Command::Alias(AliasCommand::Set { name, version }) => update_alias(name, Some(version)),
+        Command::Alias(AliasCommand::Remove { name }) => update_alias(name, None),
+        Command::Alias(AliasCommand::List) => {
+            for (name, version) in &Config::load()?.aliases {
+                println!("@{name} = {version}");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 整理命令行中所有接受版本号的参数：从复制来的版本字符串中取出版本号，
+/// 并将 `@name` 别名替换为配置中的版本。
+fn resolve_aliases(args: &mut Args) -> Result<()> {
+    let mut versions: Vec<&mut String> = Vec::new();
+    versions.extend(args.browser_version.as_mut());
+    versions.extend(args.pick.as_mut());
+    match &mut args.command {
+        Some(Command::Channels { version } | Command::Which { version, .. }) => {
+            versions.push(version)
+        }
+        Some(Command::Changelog { from, to, .. } | Command::Deps { from, to }) => {
+            versions.extend([from, to])
+        }
+        Some(Command::Crx { chrome_version, .. }) => versions.push(chrome_version),
+        Some(Command::Bundle(BundleCommand::Create {
+            versions: selectors,
+            ..
+        })) => versions.extend(selectors.iter_mut()),
+        _ => {}
+    }
+    for version in versions.iter_mut() {
+        if let Some(extracted) = extract_version(version) {
+            status!("==> using version {extracted} from \"{}\"", version.trim());
+            **version = extracted;
+        }
+    }
+    if !versions.iter().any(|version| version.starts_with('@')) {
+        return Ok(());
+    }
+    let config = Config::load()?;
+    for version in versions {
+        *version = config.resolve_alias(version)?;
+    }
+    Ok(())
+}
+
+/// `--from-edge`、`--from-brave`、`--from-electron`：将下游浏览器的版本换算为 Chromium 版本，之后按通常的版本下载。
+async fn translate_fork(args: &mut Args, transport: &SharedTransport) -> Result<()> {
+    let (fork, version) = match (&args.from_edge, &args.from_brave, &args.from_electron) {
+        (Some(version), _, _) => (ChromiumFork::Edge, version),
+        (None, Some(version), _) => (ChromiumFork::Brave, version),
+        (None, None, Some(version)) => (ChromiumFork::Electron, version),
+        (None, None, None) => return Ok(()),
+    };
+    let spec = fork.translate(version, &transport.get(args).await?).await?;
+    status!("==> fetching Chromium {spec} for {} {version}", fork.name());
+    args.browser_version = Some(spec);
+    Ok(())
+}
+
+/// daemon 请求中的平台：`linux` 或 `linux-x64`，省略架构时使用本机的架构。
+fn parse_platform(platform: &str) -> Result<Platform> {
+    let (os, arch) = match platform.split_once('-') {
+        Some((os, arch)) => (os, Arch::from_str(arch)?),
+        None => (platform, Arch::host()),
+    };
+    Ok(Platform::new(Os::from_str(os)?, arch))
+}
+
+/// `fb daemon`：通过 HTTP 接收下载请求，共用一个下载管理器和缓存。
+///
+/// 任务在 daemon 的工作线程中执行，每个任务在该线程上阻塞等待运行时完成下载。
+async fn run_daemon(
+    args: &Args,
+    listen: &str,
+    workers: usize,
+    transport: &SharedTransport,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load()?;
+    let proxy = transport.get(args).await?;
+    let runtime = Handle::current();
+    daemon::serve(listen, workers, |id, request| {
+        // 每个任务使用单独的下载目录，任务结束时删除其中下载的压缩包，压缩包缓存中的保留。
+        let downloads: Downloads =
+            Arc::new(download_manager(&proxy, args, paths).with_subdir(&format!("job-{id}")));
+        let platform = match &request.platform {
+            Some(platform) => parse_platform(platform)?,
+            None => host_platform(args)?,
+        };
+        let version = config.resolve_alias(&request.version)?;
+        let requested =
+            provider_by_name(&request.browser, args, &config, &proxy, &downloads, paths)?;
+        let path = runtime.block_on(download_with_fallback(
+            args,
+            &requested,
+            platform,
+            &version,
+            &VersionPick::Newest,
+        ))?;
+        events::record(paths, "install", &path);
+        Ok(path)
+    })
+}
+
+fn host_platform(args: &Args) -> Result<Platform> {
+    let os = Os::from_str(args.os.first().map_or(std::env::consts::OS, String::as_str))?;
+    let arch = match &args.arch {
+        Some(arch) => Arch::from_str(arch)?,
+        None => Arch::host(),
+    };
+    Ok(Platform::new(os, arch))
+}
+
+fn cache_filter(args: &Args) -> Result<CacheFilter> {
+    let no_browser_specified = !args.chrome && !args.firefox && !args.edge;
+    Ok(CacheFilter {
+        chrome: args.chrome || no_browser_specified,
+        firefox: args.firefox || no_browser_specified,
+        os: args.os.first().map(|os| Os::from_str(os)).transpose()?,
+        channel: args.channel,
+    })
+}
+
+async fn fetch_browsers(
+    args: &Args,
+    transport: &SharedTransport,
+    paths: &Paths,
+) -> Result<Vec<PathBuf>> {
+    if let Some(revision) = &args.revision {
+        return fetch_revision(args, parse_revision(revision)?, transport, paths).await;
+    }
+    let version = args
+        .browser_version
+        .as_deref()
+        .ok_or_else(|| anyhow!("No browser version specified."))?;
+    if args.create_shortcut {
+        shortcut::ensure_supported()?;
+    }
+    let proxy = transport.get(args).await?;
+    let config = Config::load()?;
+    let verify_due = config
+        .verify_cache_days
+        .is_some_and(|days| cache::verification_due(days, paths));
+    if args.verify_cache || verify_due {
+        let filter = CacheFilter {
+            chrome: args.chrome || !args.firefox && !args.edge && args.provider.is_none(),
+            firefox: args.firefox,
+            os: Some(host_platform(args)?.os()),
+            channel: args.channel,
+        };
+        cache::verify(&filter, &proxy, paths).await?;
+    }
+    let pick = version_pick(args);
+    let downloads = build_downloads(&proxy, args, paths);
+    if args.os.len() > 1 {
+        return fetch_platforms(args, version, &pick, &config, &proxy, &downloads, paths).await;
+    }
+    // 同时请求多个浏览器（如 `--chrome --firefox`）时各浏览器的下载并发进行，
+    // 各自的下载仍受 `--jobs` 限制，安装结果的顺序与命令行一致。
+    let requested = requested_providers(args, &config, &proxy, &downloads, paths)?;
+    let platform = host_platform(args)?;
+    let queue = (requested.len() > 1).then(|| DownloadQueue::open(paths));
+    let installed = run_bounded(requested.iter().collect(), requested.len(), |requested| {
+        let (queue, pick) = (&queue, &pick);
+        async move {
+            if let Some(path) = queue
+                .as_ref()
+                .and_then(|queue| queue.finished(&requested.name))
+            {
+                return Ok(path);
+            }
+            let path = download_with_fallback(args, requested, platform, version, pick).await?;
+            if let Some(queue) = queue {
+                queue.mark_finished(&requested.name, &path)?;
+            }
+            summary::emit_event(
+                "installed",
+                json!({ "browser": requested.name, "requested_version": version, "path": path }),
+            );
+            Ok(path)
+        }
+    })
+    .await?;
+    if let Some(queue) = queue {
+        queue.complete()?;
+    }
+    for path in &installed {
+        events::record(paths, "install", path);
+    }
+    if args.create_shortcut {
+        for path in &installed {
+            shortcut::create_shortcut(path)?;
+        }
+    }
+    Ok(installed)
+}
+
+fn version_pick(args: &Args) -> VersionPick {
+    match (&args.pick, args.oldest) {
+        (Some(pick), _) => VersionPick::parse(pick),
+        (None, true) => VersionPick::Oldest,
+        (None, false) => VersionPick::Newest,
+    }
+}
+
+/// 多个 `--os`：先为每个平台解析版本，再通过下载管理器的流水线在解压一个平台时下载下一个平台。
+/// 各平台使用同一个 `--arch`，不回退其他架构。
+async fn fetch_platforms(
+    args: &Args,
+    version: &str,
+    pick: &VersionPick,
+    config: &Config,
+    proxy: &Http,
+    downloads: &Downloads,
+    paths: &Paths,
+) -> Result<Vec<PathBuf>> {
+    let arch = host_platform(args)?.arch();
+    let platforms = args
+        .os
+        .iter()
+        .map(|os| {
+            let platform = Platform::new(Os::from_str(os)?, arch);
+            let mut paths = paths.clone();
+            paths.platform = Some(platform);
+            Ok((platform, paths))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let queue = DownloadQueue::open(paths);
+    let mut installed = Vec::new();
+    let mut stages = Vec::new();
+    for (platform, paths) in &platforms {
+        for requested in requested_providers(args, config, proxy, downloads, paths)? {
+            let key = format!("{}/{}", requested.name, platform.os().as_str());
+            if let Some(path) = queue.finished(&key) {
+                installed.push(path);
+                continue;
+            }
+            let item = select_release(&*(requested.init)(*platform).await?, version, pick).await?;
+            stages.push((
+                item.prefetch_requests().await?,
+                (key, requested.name, item, paths),
+            ));
+        }
+    }
+    let installed_stages = downloads
+        .pipeline(stages, |(key, name, item, paths)| {
+            let queue = &queue;
+            async move {
+                let path = item.download().await?;
+                queue.mark_finished(&key, &path)?;
+                summary::emit_event(
+                    "installed",
+                    json!({ "browser": name, "requested_version": version, "path": path }),
+                );
+                events::record(paths, "install", &path);
+                Ok(path)
+            }
+        })
+        .await?;
+    installed.extend(installed_stages);
+    queue.complete()?;
+    if args.create_shortcut {
+        for path in &installed {
+            shortcut::create_shortcut(path)?;
+        }
+    }
+    Ok(installed)
+}
+
+/// 下载请求的平台，出错时按 `Arch::fallbacks` 依次尝试其他架构。
+async fn download_with_fallback(
+    args: &Args,
+    requested: &RequestedProvider<'_>,
+    requested_platform: Platform,
+    version: &str,
+    pick: &VersionPick,
+) -> Result<PathBuf> {
+    with_arch_fallback(args, requested, requested_platform, |platform| {
+        requested.download(platform, version, pick)
+    })
+    .await
+}
+
+async fn with_arch_fallback<F>(
+    args: &Args,
+    requested: &RequestedProvider<'_>,
+    requested_platform: Platform,
+    mut download: impl FnMut(Platform) -> F,
+) -> Result<PathBuf>
+where
+    F: Future<Output = Result<PathBuf>>,
+{
+    let mut platform = requested_platform;
+    let mut result = download(platform).await;
+    // todo 这里不要无脑回退下载 x86，应该在版本找不到的时候才下载 x86 版本的。
+    let fallbacks = match requested.arch_fallback && !args.no_arch_fallback && !args.strict {
+        true => platform.arch().fallbacks(),
+        false => &[],
+    };
+    for &arch in fallbacks {
+        let Err(err) = &result else { break };
+        let fallback = Platform::new(platform.os(), arch);
+        if fallback.eq_impl(&platform) {
+            continue;
+        }
+        status!(
+            "==> downloading the {} build failed, trying {}:\n{err}",
+            platform.arch().as_str(),
+            arch.as_str()
+        );
+        platform = fallback;
+        result = download(platform).await;
+    }
+    let path = result?;
+    if platform.arch() != requested_platform.arch() {
+        record_arch_fallback(&path, requested_platform.arch())?;
+    }
+    Ok(path)
+}
+
+/// `fb sync`：比较清单与输出目录中的安装，安装缺少的浏览器，`--prune` 时删除清单之外的安装。
+///
+/// 安装后把解析出的版本和下载的文件写入清单旁边的锁定文件，之后（包括其他机器上）按锁定的版本安装并校验文件。
+async fn run_sync(
+    args: &Args,
+    manifest_path: &Path,
+    dry_run: bool,
+    prune: bool,
+    transport: &SharedTransport,
+    paths: &Paths,
+) -> Result<()> {
+    let config = Config::load()?;
+    let mut manifest = SyncManifest::load(manifest_path)?;
+    for entry in &mut manifest.browsers {
+        entry.version = config.resolve_alias(&entry.version)?;
+    }
+    let lock_path = SyncLock::path(manifest_path);
+    let mut lock = SyncLock::load(&lock_path)?;
+    let host = host_platform(args)?;
+    let mut paths = paths.clone();
+    // 与多个 `--os` 相同，各平台的安装目录不能相同。
+    if paths.dir_name.is_none()
+        && manifest.browsers.iter().any(|entry| {
+            entry
+                .os
+                .as_deref()
+                .is_some_and(|os| os != host.os().as_str())
+        })
+    {
+        paths.dir_name = Some("{browser}-{version}-{os}".to_owned());
+    }
+    let paths = &paths;
+    let plan = SyncPlan::new(&manifest, &lock, host, &paths.output_dir, prune)?;
+    status!("==> sync plan for {}:", paths.output_dir.display());
+    plan.print();
+    if dry_run {
+        return Ok(());
+    }
+
+    // 锁定文件出现之前安装的版本只能锁定版本号，没有可以校验的文件。
+    for (entry, dir) in &plan.up_to_date {
+        let platform = entry.platform(host)?;
+        if lock.find(entry, platform).is_none() {
+            let metadata = InstallMetadata::read(dir)?;
+            lock.insert(LockedEntry::new(entry, platform, &metadata, Vec::new()));
+        }
+    }
+    let proxy = transport.get(args).await?;
+    let downloads = build_downloads(&proxy, args, paths);
+    for entry in &plan.install {
+        let platform = entry.platform(host)?;
+        let mut paths = paths.clone();
+        paths.platform = Some(platform);
+        let requested =
+            provider_by_name(&entry.browser, args, &config, &proxy, &downloads, &paths)?;
+        let (path, files) = match lock.find(entry, platform) {
+            Some(locked) => {
+                status!(
+                    "==> installing the locked {} {} for {} {}",
+                    locked.browser,
+                    locked.resolved_version,
+                    entry.browser,
+                    entry.version
+                );
+                let item = locked_release(
+                    args, &config, &requested, locked, &proxy, &downloads, &paths,
+                )
+                .await?;
+                install_planned(&*item, Some(locked), &downloads).await?
+            }
+            None => {
+                let files = Mutex::new(Vec::new());
+                let path = with_arch_fallback(args, &requested, platform, |platform| {
+                    let (requested, downloads, files) = (&requested, &downloads, &files);
+                    async move {
+                        let item = select_release(
+                            &*(requested.init)(platform).await?,
+                            &entry.version,
+                            &VersionPick::Newest,
+                        )
+                        .await?;
+                        let (path, downloaded) = install_planned(&*item, None, downloads).await?;
+                        *files.lock().unwrap() = downloaded;
+                        Ok(path)
+                    }
+                })
+                .await?;
+                (path, files.into_inner().unwrap())
+            }
+        };
+        events::record(&paths, "install", &path);
+        let metadata = InstallMetadata::read(&path)?;
+        lock.insert(LockedEntry::new(entry, platform, &metadata, files));
+    }
+    for (dir, metadata) in &plan.remove {
+        status!(
+            "==> removing {} {}: {}",
+            metadata.browser,
+            metadata.version,
+            dir.display()
+        );
+        // 删除前记录，日志中才有浏览器和版本。
+        events::record(paths, "remove", dir);
+        std::fs::remove_dir_all(dir)?;
+    }
+    lock.retain_manifest(&manifest);
+    lock.write(&lock_path)?;
+    status!("==> wrote lockfile {}", lock_path.display());
+    Ok(())
+}
+
+/// 锁定的版本：Chromium 快照直接使用锁定的 revision，其他浏览器按精确的版本号匹配。
+async fn locked_release(
+    args: &Args,
+    config: &Config,
+    requested: &RequestedProvider<'_>,
+    locked: &LockedEntry,
+    proxy: &Http,
+    downloads: &Downloads,
+    paths: &Paths,
+) -> Result<Box<dyn BrowserReleaseItem>> {
+    let platform = locked.platform()?;
+    match (locked.browser.as_str(), locked.revision) {
+        ("chromium", Some(revision)) => {
+            chromium::revision_release(
+                revision,
+                Some(&locked.resolved_version),
+                platform,
+                proxy,
+                downloads,
+                download_options(args, config),
+                paths,
+            )
+            .await
+        }
+        _ => {
+            select_release(
+                &*(requested.init)(platform).await?,
+                &format!("={}", locked.resolved_version),
+                &VersionPick::Newest,
+            )
+            .await
+        }
+    }
+}
+
+/// 先下载计划中的文件，有锁定的文件时必须与之完全相同；安装时直接使用已下载的文件。
+/// 返回安装目录和下载的文件。
+async fn install_planned(
+    item: &dyn BrowserReleaseItem,
+    locked: Option<&LockedEntry>,
+    downloads: &Downloads,
+) -> Result<(PathBuf, Vec<LockedFile>)> {
+    let mut requests = item.plan().await?.requests;
+    if let Some(locked) = locked.filter(|locked| !locked.files.is_empty()) {
+        let urls: Vec<_> = requests.iter().map(|request| &request.url).collect();
+        let locked_urls: Vec<_> = locked.files.iter().map(|file| &file.url).collect();
+        if urls != locked_urls {
+            return Err(anyhow!(
+                "{} {} now resolves to other files than the locked ones ({}), delete its entry from the lockfile to resolve it again.",
+                locked.browser,
+                locked.resolved_version,
+                locked_urls.iter().map(|url| url.as_str()).collect::<Vec<_>>().join(", ")
+            ));
+        }
+        for (request, file) in requests.iter_mut().zip(&locked.files) {
+            request.size = Some(file.size);
+            request.sha256 = Some(file.sha256.clone());
+        }
+    }
+    let downloaded = downloads.fetch_all(&requests).await?;
+    let path = item.download().await?;
+    let files = requests
+        .into_iter()
+        .zip(downloaded)
+        .map(|(request, downloaded)| LockedFile {
+            url: request.url,
+            size: downloaded.size,
+            sha256: downloaded.sha256,
+        })
+        .collect();
+    Ok((path, files))
+}
+
+/// `--revision`：直接下载其他机器（如 `resolve_chromium`）解析好的快照，位置参数的版本号只用于命名。
+async fn fetch_revision(
+    args: &Args,
+    revision: usize,
+    transport: &SharedTransport,
+    paths: &Paths,
+) -> Result<Vec<PathBuf>> {
+    if args.create_shortcut {
+        shortcut::ensure_supported()?;
+    }
+    let proxy = transport.get(args).await?;
+    let config = Config::load()?;
+    let path = chromium::revision_release(
+        revision,
+        args.browser_version.as_deref(),
+        host_platform(args)?,
+        &proxy,
+        &build_downloads(&proxy, args, paths),
+        download_options(args, &config),
+        paths,
+    )
+    .await?
+    .download()
+    .await?;
+    events::record(paths, "install", &path);
+    if args.create_shortcut {
+        shortcut::create_shortcut(&path)?;
+    }
+    Ok(vec![path])
+}
+
+/// 回退安装了其他架构时，在输出和 metadata.json 中明确标出，避免用户误以为装的是请求的架构。
+fn record_arch_fallback(path: &Path, requested: Arch) -> Result<()> {
+    let mut metadata = InstallMetadata::read(path)?;
+    status!(
+        "==> warning[arch-fallback]: installed {} {} is the {} build, {} was requested. Pass --no-arch-fallback to fail instead.",
+        metadata.browser,
+        metadata.version,
+        metadata.arch.as_deref().unwrap_or_default(),
+        requested.as_str()
+    );
+    metadata.requested_arch = Some(requested.as_str().to_owned());
+    metadata.write(path)
+}
+
+async fn list_matches(args: &Args, transport: &SharedTransport, paths: &Paths) -> Result<()> {
+    let version = args
+        .browser_version
+        .as_deref()
+        .ok_or_else(|| anyhow!("No browser version specified."))?;
+    let proxy = transport.get(args).await?;
+    let config = Config::load()?;
+    let downloads = build_downloads(&proxy, args, paths);
+    for requested in requested_providers(args, &config, &proxy, &downloads, paths)? {
+        let fetcher = (requested.init)(host_platform(args)?).await?;
+        let versions = fetcher
+            .match_version(version)
+            .map_ok(|release| release.version().to_owned())
+            .try_collect()
+            .await?;
+        print_matches(&requested.name, version, versions);
+    }
+    Ok(())
+}
+
+/// `--dry-run`：解析版本并查找构建，输出将要下载的文件及其大小、安装目录，不下载也不安装。
+/// 只查找请求的架构，不回退其他架构。
+async fn dry_run(args: &Args, transport: &SharedTransport, paths: &Paths) -> Result<()> {
+    let proxy = transport.get(args).await?;
+    let config = Config::load()?;
+    let downloads = build_downloads(&proxy, args, paths);
+    if let Some(revision) = &args.revision {
+        let item = chromium::revision_release(
+            parse_revision(revision)?,
+            args.browser_version.as_deref(),
+            host_platform(args)?,
+            &proxy,
+            &downloads,
+            download_options(args, &config),
+            paths,
+        )
+        .await?;
+        let plan = print_plan(BuildVariant::Snapshot.browser_name(), &*item, &downloads).await?;
+        summary::print_plans(&[plan]);
+        return Ok(());
+    }
+    let version = args
+        .browser_version
+        .as_deref()
+        .ok_or_else(|| anyhow!("No browser version specified."))?;
+    let pick = version_pick(args);
+    let platform = host_platform(args)?;
+    let platforms = match args.os.len() > 1 {
+        true => args
+            .os
+            .iter()
+            .map(|os| {
+                let platform = Platform::new(Os::from_str(os)?, platform.arch());
+                let mut paths = paths.clone();
+                paths.platform = Some(platform);
+                Ok((platform, paths))
+            })
+            .collect::<Result<Vec<_>>>()?,
+        false => vec![(platform, paths.clone())],
+    };
+    let mut plans = Vec::new();
+    for (platform, paths) in &platforms {
+        for requested in requested_providers(args, &config, &proxy, &downloads, paths)? {
+            let item = select_release(&*(requested.init)(*platform).await?, version, &pick).await?;
+            plans.push(print_plan(&requested.name, &*item, &downloads).await?);
+        }
+    }
+    summary::print_plans(&plans);
+    Ok(())
+}
+
+/// 大小未知的文件只请求第一个字节，同时确认文件存在；压缩包缓存中已有的文件不访问网络。
+async fn print_plan(
+    browser: &str,
+    item: &dyn BrowserReleaseItem,
+    downloads: &Downloads,
+) -> Result<PlanReport> {
+    let plan = item.plan().await?;
+    let version = item.version();
+    match plan.revision {
+        Some(revision) => status!(
+            "==> would install {browser} {version} (r{revision}) into {}",
+            plan.install_dir.display()
+        ),
+        None => status!(
+            "==> would install {browser} {version} into {}",
+            plan.install_dir.display()
+        ),
+    }
+    let mut files = Vec::new();
+    for request in &plan.requests {
+        let cached = downloads.is_cached(request);
+        let size = match cached {
+            true => request.size,
+            false => downloads.probe(request).await?,
+        };
+        let size_text = size.map_or_else(
+            || "unknown size".to_owned(),
+            |size| format!("{:.1} MB", size as f64 / 1024.0 / 1024.0),
+        );
+        match cached {
+            true => status!("==>   {} ({size_text}, cached)", request.url),
+            false => status!("==>   {} ({size_text})", request.url),
+        }
+        summary::emit_event(
+            "planned",
+            json!({
+                "browser": browser,
+                "version": version,
+                "revision": plan.revision,
+                "url": request.url,
+                "size": size,
+                "cached": cached,
+                "path": plan.install_dir,
+            }),
+        );
+        files.push(PlannedFile {
+            url: request.url.clone(),
+            size,
+            cached,
+        });
+    }
+    Ok(PlanReport {
+        browser: browser.to_owned(),
+        version: version.to_owned(),
+        revision: plan.revision,
+        path: plan.install_dir,
+        files,
+    })
+}
+
+/// 根据平台初始化版本数据。
+type ProviderInit<'a> =
+    Box<dyn Fn(Platform) -> BoxFuture<'a, Result<Box<dyn BrowserReleases>>> + Send + Sync + 'a>;
+
+/// 命令行中请求下载的浏览器，`init` 根据平台初始化对应的版本数据。
+struct RequestedProvider<'a> {
+    name: String,
+    init: ProviderInit<'a>,
+    arch_fallback: bool,
+}
+
+impl RequestedProvider<'_> {
+    async fn download(
+        &self,
+        platform: Platform,
+        version: &str,
+        pick: &VersionPick,
+    ) -> Result<PathBuf> {
+        download_browser(&*(self.init)(platform).await?, version, pick).await
+    }
+}
+
+/// 按名称（清单、daemon 请求中的 `browser`）选择下载源，其余名称按配置文件中的自定义 provider 处理。
+fn provider_by_name<'a>(
+    name: &str,
+    args: &'a Args,
+    config: &'a Config,
+    proxy: &'a Http,
+    downloads: &'a Downloads,
+    paths: &'a Paths,
+) -> Result<RequestedProvider<'a>> {
+    let channel = args.channel.unwrap_or(ReleaseChannel::Stable);
+    Ok(match name {
+        "chromium" | "chromium-asan" => {
+            let mut options = download_options(args, config);
+            options.variant = match name {
+                "chromium" => BuildVariant::Snapshot,
+                _ => BuildVariant::Asan,
+            };
+            chromium_provider(channel, options, proxy, downloads, paths)
+        }
+        "chrome" => cft_provider(args, proxy, downloads, paths),
+        "firefox" => firefox_provider(
+            args.channel.unwrap_or(ReleaseChannel::Any),
+            download_options(args, config),
+            proxy,
+            downloads,
+            paths,
+        ),
+        "edge" => edge_provider(channel, proxy, downloads, paths),
+        name => custom_provider(name, args, config, proxy, downloads, paths)?,
+    })
+}
+
+fn requested_providers<'a>(
+    args: &'a Args,
+    config: &'a Config,
+    proxy: &'a Http,
+    downloads: &'a Downloads,
+    paths: &'a Paths,
+) -> Result<Vec<RequestedProvider<'a>>> {
+    let channel = args.channel.unwrap_or(ReleaseChannel::Stable);
+    let no_browser_specified =
+        !args.chrome && !args.firefox && !args.edge && args.provider.is_none();
+    let mut requested = Vec::new();
+    if let Some(name) = &args.provider {
+        requested.push(custom_provider(
+            name, args, config, proxy, downloads, paths,
+        )?);
+    }
+    let alias = args.browser_version.as_deref().and_then(channel_alias);
+    let cft = alias.is_some() || args.source == ChromiumSource::ChromeForTesting;
+    if cft && (args.chrome || no_browser_specified) {
+        // 渠道别名通过 Chrome for Testing 解析，保证该版本有可下载的压缩包。
+        if args.variant != BuildVariant::Snapshot {
+            return Err(anyhow!(
+                "Chrome for Testing has no {} builds.",
+                args.variant.browser_name()
+            ));
+        }
+        requested.push(cft_provider(args, proxy, downloads, paths));
+    } else if args.chrome || no_browser_specified {
+        if args.with_headless_shell {
+            return Err(anyhow!(
+                "--with-headless-shell needs --source chrome-for-testing or a channel."
+            ));
+        }
+        requested.push(chromium_provider(
+            channel,
+            download_options(args, config),
+            proxy,
+            downloads,
+            paths,
+        ));
+    }
+    if args.firefox {
+        requested.push(firefox_provider(
+            args.channel.unwrap_or(ReleaseChannel::Any),
+            download_options(args, config),
+            proxy,
+            downloads,
+            paths,
+        ));
+    }
+    if args.edge {
+        requested.push(edge_provider(channel, proxy, downloads, paths));
+    }
+    Ok(requested)
+}
+
+fn custom_provider<'a>(
+    name: &str,
+    args: &'a Args,
+    config: &'a Config,
+    proxy: &'a Http,
+    downloads: &'a Downloads,
+    paths: &'a Paths,
+) -> Result<RequestedProvider<'a>> {
+    let provider = config
+        .provider(name)
+        .ok_or_else(|| anyhow!("Provider {name} is not defined in the config file."))?;
+    Ok(RequestedProvider {
+        name: name.to_owned(),
+        init: Box::new(move |platform| {
+            Box::pin(async move {
+                let fetcher = CustomReleases::init(
+                    provider.clone(),
+                    platform,
+                    proxy.clone(),
+                    downloads.clone(),
+                    paths,
+                    args.keep_archive,
+                )
+                .await?;
+                Ok(Box::new(fetcher) as Box<dyn BrowserReleases>)
+            })
+        }),
+        arch_fallback: false,
+    })
+}
+
+/// `--with-driver`、`--with-headless-shell` 从同一个 Chrome for Testing 版本中安装。
+fn cft_provider<'a>(
+    args: &Args,
+    proxy: &'a Http,
+    downloads: &'a Downloads,
+    paths: &'a Paths,
+) -> RequestedProvider<'a> {
+    let extras = [
+        (args.with_driver, CftBinary::Chromedriver),
+        (args.with_headless_shell, CftBinary::ChromeHeadlessShell),
+    ]
+    .into_iter()
+    .filter_map(|(requested, binary)| requested.then_some(binary))
+    .collect::<Vec<_>>();
+    RequestedProvider {
+        name: "chrome".to_owned(),
+        init: Box::new(move |platform| {
+            let extras = extras.clone();
+            Box::pin(async move {
+                let fetcher = CftReleases::init(
+                    platform,
+                    proxy.clone(),
+                    extras,
+                    downloads.clone(),
+                    paths.clone(),
+                )?;
+                Ok(Box::new(fetcher) as Box<dyn BrowserReleases>)
+            })
+        }),
+        arch_fallback: true,
+    }
+}
+
+fn chromium_provider<'a>(
+    channel: ReleaseChannel,
+    options: DownloadOptions,
+    proxy: &'a Http,
+    downloads: &'a Downloads,
+    paths: &'a Paths,
+) -> RequestedProvider<'a> {
+    RequestedProvider {
+        name: options.variant.browser_name().to_owned(),
+        init: Box::new(move |platform| {
+            let options = options.clone();
+            Box::pin(async move {
+                let fetcher = ChromiumReleases::init(
+                    platform,
+                    channel,
+                    proxy.clone(),
+                    downloads.clone(),
+                    options,
+                    paths.clone(),
+                )
+                .await?;
+                Ok(Box::new(fetcher) as Box<dyn BrowserReleases>)
+            })
+        }),
+        arch_fallback: true,
+    }
+}
+
+/// Firefox 在 FirefoxReleases 中按平台依次尝试各架构的安装包。
+///
+/// 未指定 `--channel` 时与以前一样在 releases 目录下的所有版本中查找。
+fn firefox_provider<'a>(
+    channel: ReleaseChannel,
+    options: DownloadOptions,
+    proxy: &'a Http,
+    downloads: &'a Downloads,
+    paths: &'a Paths,
+) -> RequestedProvider<'a> {
+    RequestedProvider {
+        name: "firefox".to_owned(),
+        init: Box::new(move |platform| {
+            let options = options.clone();
+            Box::pin(async move {
+                let fetcher = FirefoxReleases::init(
+                    platform,
+                    channel,
+                    proxy,
+                    downloads.clone(),
+                    options,
+                    paths.clone(),
+                )
+                .await?;
+                Ok(Box::new(fetcher) as Box<dyn BrowserReleases>)
+            })
+        }),
+        arch_fallback: false,
+    }
+}
+
+/// Edge 的 Windows 版本在 x64 上可以回退到 x86。
+fn edge_provider<'a>(
+    channel: ReleaseChannel,
+    proxy: &'a Http,
+    downloads: &'a Downloads,
+    paths: &'a Paths,
+) -> RequestedProvider<'a> {
+    RequestedProvider {
+        name: "edge".to_owned(),
+        init: Box::new(move |platform| {
+            Box::pin(async move {
+                let fetcher = EdgeReleases::init(
+                    platform,
+                    channel,
+                    proxy.clone(),
+                    downloads.clone(),
+                    paths.clone(),
+                )
+                .await?;
+                Ok(Box::new(fetcher) as Box<dyn BrowserReleases>)
+            })
+        }),
+        arch_fallback: true,
+    }
+}
+
+fn print_matches(browser: &str, version: &str, mut versions: Vec<String>) {
+    sort_newest_first(&mut versions, String::as_str);
+    status!(
+        "==> {browser} versions matching {version}: {}",
+        versions.len()
+    );
+    for (index, matched) in versions.iter().enumerate() {
+        println!("{:>4}. {matched}", index + 1);
+    }
+}
+
+fn download_options(args: &Args, config: &Config) -> DownloadOptions {
+    DownloadOptions {
+        assets: args.assets.clone(),
+        drift_warn: args
+            .drift_warn
+            .or(config.drift_warn)
+            .unwrap_or(DownloadOptions::default().drift_warn),
+        locales: args.chromium_locales.clone(),
+        keep_archive: args.keep_archive,
+        variant: args.variant,
+        devtools_protocol: args.devtools_protocol,
+        strict: args.strict,
+        with_driver: args.with_driver,
+        with_mar: args.with_mar,
+        firefox_lang: args.lang.clone(),
+        driver_cache_days: config
+            .driver_cache_days
+            .unwrap_or(DownloadOptions::default().driver_cache_days),
+        snapshot_archives: config.snapshot_archives.clone(),
+    }
+}
+
+/// 整个运行共用的 HTTP 客户端：第一次联网时按命令行参数和配置创建，之后各处共用同一个连接池和限速。
+/// 不联网的子命令不会创建，代理等配置有误时也不受影响。
+#[derive(Default)]
+struct SharedTransport(OnceCell<Http>);
+
+impl SharedTransport {
+    async fn get(&self, args: &Args) -> Result<Http> {
+        self.0
+            .get_or_try_init(|| build_transport(args))
+            .await
+            .cloned()
+    }
+}
+
+async fn build_transport(args: &Args) -> Result<Http> {
+    if let Some(dir) = &args.replay_fixtures {
+        return Ok(Arc::new(MockTransport::from_fixtures(dir)?));
+    }
+    let config = Config::load()?;
+    let rate_limits = config.rate_limits();
+    let (proxy, pac_url, bypass) = resolve_proxy(
+        args.proxy.as_deref(),
+        args.pac_url.as_deref(),
+        args.system_proxy,
+    );
+    let build = |builder: ClientBuilder| -> Result<Http> {
+        Ok(Arc::new(RateLimitedTransport::new(
+            Arc::new(ReqwestTransport::new(builder.build()?)),
+            &rate_limits,
+        )))
+    };
+    let inner = build(
+        proxy_client_builder(proxy.as_deref(), pac_url.as_deref(), &bypass, args.http2).await?,
+    )?;
+    let client: Http = match config.mirrors.is_empty() {
+        true => inner,
+        false => {
+            let mut mirrors = Vec::new();
+            for mirror in &config.mirrors {
+                let builder =
+                    proxy_client_builder(proxy.as_deref(), pac_url.as_deref(), &bypass, args.http2)
+                        .await?;
+                let builder = trust_mirror_certs(builder, mirror)?;
+                mirrors.push((mirror.upstream.clone(), mirror.url.clone(), build(builder)?));
+            }
+            Arc::new(MirrorTransport::new(inner, mirrors))
+        }
+    };
+    match &args.record_fixtures {
+        Some(dir) => Ok(Arc::new(RecordingTransport::new(client, dir)?)),
+        None => Ok(client),
+    }
+}
+
+fn build_downloads(client: &Http, args: &Args, paths: &Paths) -> Downloads {
+    Arc::new(download_manager(client, args, paths))
+}
+
+fn download_manager(client: &Http, args: &Args, paths: &Paths) -> DownloadManager {
+    let archive_cache = match (args.no_cache, args.refresh) {
+        (true, _) => ArchiveCache::Disabled,
+        (false, true) => ArchiveCache::Refresh,
+        (false, false) => ArchiveCache::Use,
+    };
+    DownloadManager::new(client.clone(), &paths.temp_dir, args.jobs)
+        .with_archive_cache(paths, archive_cache)
+        .with_connections(args.connections)
+}
+
+/// 为镜像的客户端加入配置的 CA 证书。
+fn trust_mirror_certs(builder: ClientBuilder, mirror: &MirrorConfig) -> Result<ClientBuilder> {
+    let load = |path: &Path| -> Result<reqwest::Certificate> {
+        std::fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|pem| Ok(reqwest::Certificate::from_pem(&pem)?))
+            .map_err(|err| {
+                anyhow!(
+                    "Failed to load the certificate {} for mirror {}: {err}",
+                    path.display(),
+                    mirror.url
+                )
+            })
+    };
+    let mut builder = builder;
+    if let Some(path) = &mirror.ca_cert {
+        builder = builder.add_root_certificate(load(path)?);
+    }
+    if let Some(path) = &mirror.exclusive_ca_cert {
+        builder = builder
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(load(path)?);
+    }
+    Ok(builder)
+}
+
+/// 确定实际使用的代理：未指定代理时按需检测系统代理，只检测一次。
+/// 系统代理的例外列表随代理一起返回。
+fn resolve_proxy(
+    proxy: Option<&str>,
+    pac_url: Option<&str>,
+    system_proxy: bool,
+) -> (Option<String>, Option<String>, ProxyBypass) {
+    let detected = match (proxy, pac_url) {
+        (None, None) if system_proxy && !env_proxy_set() => {
+            let detected = system_proxy::detect();
+            match &detected {
+                Some(SystemProxy::Fixed(proxy, _)) => {
+                    status!("==> using the system proxy {proxy}")
+                }
+                Some(SystemProxy::Pac(pac_url)) => {
+                    status!("==> using the system proxy auto-config {pac_url}")
+                }
+                None => status!("==> no system proxy configured, connecting directly"),
+            }
+            detected
+        }
+        _ => None,
+    };
+    match detected {
+        Some(SystemProxy::Fixed(proxy, bypass)) => (Some(proxy), None, bypass),
+        Some(SystemProxy::Pac(pac_url)) => (None, Some(pac_url), ProxyBypass::default()),
+        None => (
+            proxy.map(str::to_owned),
+            pac_url.map(str::to_owned),
+            ProxyBypass::default(),
+        ),
+    }
+}
+
+/// 每个镜像使用一个客户端，其余请求共用一个客户端，请求之间复用连接。
+async fn proxy_client_builder(
+    proxy: Option<&str>,
+    pac_url: Option<&str>,
+    bypass: &ProxyBypass,
+    http2: bool,
+) -> Result<ClientBuilder> {
+    let builder = match http2 {
+        true => ClientBuilder::new().http2_prior_knowledge(),
+        false => ClientBuilder::new(),
+    };
+    let builder = match (proxy, pac_url) {
+        (Some(proxy), _) if bypass.is_empty() => builder.proxy(reqwest::Proxy::all(proxy)?),
+        (Some(proxy), _) => {
+            let proxy = Url::parse(proxy)?;
+            let bypass = bypass.clone();
+            builder.proxy(reqwest::Proxy::custom(move |url| {
+                let direct = url.host_str().is_some_and(|host| bypass.matches(host));
+                (!direct).then(|| proxy.clone())
+            }))
+        }
+        (None, Some(pac_url)) => {
+            let resolver = PacResolver::load(pac_url).await?;
+            builder.proxy(reqwest::Proxy::custom(move |url| resolver.find_proxy(url)))
+        }
+        (None, None) => builder,
+    };
+    Ok(builder)
+}
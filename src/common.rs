@@ -1,32 +1,322 @@
-use anyhow::Result;
+use std::{cmp::Reverse, collections::BTreeMap, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use clap::ValueEnum;
-use reqwest::blocking::Client;
+use futures::{stream::BoxStream, TryStreamExt};
+
+use crate::{
+    downloads::DownloadRequest,
+    error::FetchError,
+    platform::{Arch, Os, Platform},
+    version::BrowserVersion,
+};
+
+/// 按版本匹配到的发布，逐个返回以便在出错前就能使用已匹配到的结果。
+pub type ReleaseMatches<'r> = BoxStream<'r, Result<Box<dyn BrowserReleaseItem>>>;
 
-use crate::platform::Platform;
+/// 某个浏览器在某个平台上可以下载的版本。
+pub trait BrowserReleases: Send + Sync {
+    /// `version` 可以是版本前缀（`102.1`）、精确版本（`=102.1.0`）或区间（`100..102`）。
+    fn match_version<'r>(&'r self, version: &str) -> ReleaseMatches<'r>;
+}
+
+/// 匹配到的一个版本。下载在 tokio 运行时中进行，解压等耗时的操作在阻塞线程中执行。
+#[async_trait]
+pub trait BrowserReleaseItem: Send + Sync {
+    fn version(&self) -> &str;
 
-pub(crate) trait BrowserReleases {
-    type ReleaseItem: BrowserReleaseItem;
-    type Matches<'r>: Iterator<Item = Result<Self::ReleaseItem>>
-    where
-        Self: 'r;
+    /// 下载并安装到输出目录，返回安装目录。
+    async fn download(&self) -> Result<PathBuf>;
 
-    fn init(platform: Platform, channel: ReleaseChannel, client: Client) -> Result<Self>
-    where
-        Self: Sized;
+    /// 安装前可以提前下载的压缩包，多平台流水线在解压上一个平台时下载。
+    /// 返回空列表时全部在 `download` 中下载。
+    async fn prefetch_requests(&self) -> Result<Vec<DownloadRequest>> {
+        Ok(Vec::new())
+    }
+
+    /// 解析出将要下载的文件和安装目录，但不下载（`--dry-run`）。
+    ///
+    /// 库外实现的发布项目没有办法构造下载请求，默认不支持 `--dry-run`。
+    async fn plan(&self) -> Result<DownloadPlan> {
+        Err(anyhow!(
+            "Planning the download of {} is not supported.",
+            self.version()
+        ))
+    }
+}
+
+/// `--dry-run` 输出的下载计划。
+pub struct DownloadPlan {
+    /// 将要下载的文件，大小未知时由调用方探测。
+    pub(crate) requests: Vec<DownloadRequest>,
+    /// Chromium 快照的 revision。
+    pub(crate) revision: Option<usize>,
+    pub(crate) install_dir: PathBuf,
+}
+
+/// 版本前缀匹配到多个版本时的选择方式，默认选择最新的正式版本。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionPick {
+    Newest,
+    Oldest,
+    Exact(String),
+    /// `--list-matches` 列表中的序号，从 1 开始。
+    Index(usize),
+}
+
+impl VersionPick {
+    /// 纯数字表示列表中的序号，否则为精确的版本号。
+    pub fn parse(pick: &str) -> Self {
+        match pick.parse() {
+            Ok(index) => VersionPick::Index(index),
+            Err(_) => VersionPick::Exact(pick.to_owned()),
+        }
+    }
+
+    pub fn select<T>(&self, mut items: Vec<T>, version: impl Fn(&T) -> &str) -> Option<T> {
+        sort_newest_first(&mut items, &version);
+        match self {
+            VersionPick::Newest => items.into_iter().next(),
+            VersionPick::Oldest => items.into_iter().min_by_key(|item| {
+                let (is_release, parsed) = release_key(version(item));
+                (!is_release, parsed)
+            }),
+            VersionPick::Exact(exact) => items.into_iter().find(|item| version(item) == exact),
+            VersionPick::Index(index) => items.into_iter().nth(index.checked_sub(1)?),
+        }
+    }
+}
 
-    fn match_version<'r>(&'r self, version: &str) -> Self::Matches<'r>;
+/// 按从新到旧排序并去重，正式版本排在带标签的版本之前。
+pub(crate) fn sort_newest_first<T>(items: &mut Vec<T>, version: impl Fn(&T) -> &str) {
+    items.sort_by_cached_key(|item| Reverse(release_key(version(item))));
+    items.dedup_by(|a, b| version(a) == version(b));
 }
 
-pub(crate) trait BrowserReleaseItem {
-    fn download(&self) -> Result<()>;
+fn release_key(version: &str) -> (bool, Option<BrowserVersion>) {
+    let parsed = BrowserVersion::from_str(version).ok();
+    (
+        parsed.as_ref().is_some_and(BrowserVersion::is_release),
+        parsed,
+    )
 }
 
+/// Chromium 的下载选项，使用 [`DownloadOptions::builder`] 创建。
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub(crate) assets: Vec<String>,
+    pub(crate) drift_warn: usize,
+    pub(crate) locales: Vec<String>,
+    pub(crate) keep_archive: bool,
+    pub(crate) variant: BuildVariant,
+    pub(crate) devtools_protocol: bool,
+    /// 找不到与发布完全对应的快照或请求的语言包时失败，而不是使用相近的构建。
+    pub(crate) strict: bool,
+    /// 同时下载同一快照中的 chromedriver，Firefox 为兼容的 geckodriver。
+    pub(crate) with_driver: bool,
+    /// 缓存的 chromedriver 超过该天数后重新从快照下载。
+    pub(crate) driver_cache_days: u64,
+    /// 同时下载 Firefox 的完整 MAR 更新包。
+    pub(crate) with_mar: bool,
+    /// Firefox 安装包的语言，未指定时使用系统语言。
+    pub(crate) firefox_lang: Option<String>,
+    /// 快照目录（如 `Linux_x64`）到依次尝试的压缩包文件名，覆盖按平台内置的顺序。
+    pub(crate) snapshot_archives: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            assets: Vec::new(),
+            drift_warn: 20,
+            locales: Vec::new(),
+            keep_archive: false,
+            variant: BuildVariant::Snapshot,
+            devtools_protocol: false,
+            strict: false,
+            with_driver: false,
+            driver_cache_days: 30,
+            with_mar: false,
+            firefox_lang: None,
+            snapshot_archives: BTreeMap::new(),
+        }
+    }
+}
+
+impl DownloadOptions {
+    pub fn builder() -> DownloadOptionsBuilder {
+        DownloadOptionsBuilder::default()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DownloadOptionsBuilder(DownloadOptions);
+
+impl DownloadOptionsBuilder {
+    /// 同时下载快照中匹配这些通配符的文件，例如 `*syms*`。
+    pub fn assets(mut self, patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.0.assets = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 快照与发布的 revision 相差超过该值时给出警告，默认为 20。
+    pub fn drift_warn(mut self, drift_warn: usize) -> Self {
+        self.0.drift_warn = drift_warn;
+        self
+    }
+
+    /// 只保留这些语言包，例如 `en-US`，为空时保留全部。
+    pub fn locales(mut self, locales: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.0.locales = locales.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 在安装目录旁保留下载的压缩包。
+    pub fn keep_archive(mut self, keep_archive: bool) -> Self {
+        self.0.keep_archive = keep_archive;
+        self
+    }
+
+    pub fn variant(mut self, variant: BuildVariant) -> Self {
+        self.0.variant = variant;
+        self
+    }
+
+    /// 同时下载与 revision 对应的 DevTools 协议定义。
+    pub fn devtools_protocol(mut self, devtools_protocol: bool) -> Self {
+        self.0.devtools_protocol = devtools_protocol;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.0.strict = strict;
+        self
+    }
+
+    pub fn with_driver(mut self, with_driver: bool) -> Self {
+        self.0.with_driver = with_driver;
+        self
+    }
+
+    /// 缓存的 chromedriver 的有效天数，默认为 30。
+    pub fn driver_cache_days(mut self, days: u64) -> Self {
+        self.0.driver_cache_days = days;
+        self
+    }
+
+    /// 同时下载 Firefox 的完整 MAR 更新包（`update/` 目录下的 `.complete.mar`）。
+    pub fn with_mar(mut self, with_mar: bool) -> Self {
+        self.0.with_mar = with_mar;
+        self
+    }
+
+    /// Firefox 安装包的语言，例如 `de`，必须在该版本发布的语言中；未指定时使用系统语言，检测不到时为 `en-US`。
+    pub fn firefox_lang(mut self, lang: impl Into<String>) -> Self {
+        self.0.firefox_lang = Some(lang.into());
+        self
+    }
+
+    /// 快照目录 `prefix`（如 `Linux_x64`）下依次尝试的压缩包文件名，例如 `chrome-linux.zip`。
+    pub fn snapshot_archives(
+        mut self,
+        prefix: impl Into<String>,
+        names: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.0
+            .snapshot_archives
+            .insert(prefix.into(), names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn build(self) -> DownloadOptions {
+        self.0
+    }
+}
+
+/// 下载 `fetcher` 中与 `version` 匹配的版本，匹配到多个时按 `pick` 选择。
+pub async fn download_browser(
+    fetcher: &dyn BrowserReleases,
+    version: &str,
+    pick: &VersionPick,
+) -> Result<PathBuf> {
+    select_release(fetcher, version, pick)
+        .await?
+        .download()
+        .await
+}
+
+pub(crate) async fn select_release(
+    fetcher: &dyn BrowserReleases,
+    version: &str,
+    pick: &VersionPick,
+) -> Result<Box<dyn BrowserReleaseItem>> {
+    let matched_version_list: Vec<_> = fetcher.match_version(version).try_collect().await?;
+    Ok(pick
+        .select(matched_version_list, |release| release.version())
+        .ok_or_else(|| {
+            FetchError::VersionNotFound(format!("No matched version found for {version}."))
+        })?)
+}
+
+/// Chromium 构建的种类：普通快照（`chromium-browser-snapshots`）或 ASAN 构建（`chromium-browser-asan`）。
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub enum BuildVariant {
+    #[default]
+    Snapshot,
+    Asan,
+}
+
+impl BuildVariant {
+    pub(crate) fn bucket(&self) -> &'static str {
+        match self {
+            BuildVariant::Snapshot => "chromium-browser-snapshots",
+            BuildVariant::Asan => "chromium-browser-asan",
+        }
+    }
+
+    /// 安装目录、压缩包和 metadata 中使用的浏览器名称。
+    pub(crate) fn browser_name(&self) -> &'static str {
+        match self {
+            BuildVariant::Snapshot => "chromium",
+            BuildVariant::Asan => "chromium-asan",
+        }
+    }
+
+    /// 该平台在 bucket 中的目录，ASAN 构建只发布了部分平台。
+    pub(crate) fn prefix(&self, platform: Platform) -> Option<&'static str> {
+        match self {
+            BuildVariant::Snapshot => match (platform.os(), platform.arch()) {
+                (Os::Linux, Arch::Arm64) => None,
+                _ => Some(platform.prefix()),
+            },
+            BuildVariant::Asan => match (platform.os(), platform.arch()) {
+                (Os::Windows, Arch::X86) => Some("win32-release"),
+                (Os::Windows, Arch::X86_64) => Some("win32-release_x64"),
+                (Os::Linux, Arch::X86_64) => Some("linux-release"),
+                (Os::Mac, Arch::X86_64) => Some("mac-release"),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// 浏览器的发布渠道。
+///
+/// Firefox 的 Developer Edition、Nightly 分别对应 `Dev`、`Canary`，`Esr` 只用于 Firefox。
+/// `Extended`（extended stable，企业使用的每 8 周更新的稳定版）只用于 Chromium，不包含在 `Any` 中。
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
-pub(crate) enum ReleaseChannel {
+pub enum ReleaseChannel {
     Stable,
     Beta,
+    #[value(alias = "devedition")]
     Dev,
+    #[value(alias = "nightly")]
     Canary,
+    Esr,
+    #[value(alias = "extended-stable")]
+    Extended,
+    Any,
 }
 
 impl ReleaseChannel {
@@ -36,6 +326,21 @@ impl ReleaseChannel {
             ReleaseChannel::Beta => "Beta",
             ReleaseChannel::Dev => "Dev",
             ReleaseChannel::Canary => "Canary",
+            ReleaseChannel::Esr => "Esr",
+            ReleaseChannel::Extended => "Extended",
+            ReleaseChannel::Any => "Any",
+        }
+    }
+
+    pub(crate) fn concrete(&self) -> Vec<ReleaseChannel> {
+        match self {
+            ReleaseChannel::Any => vec![
+                ReleaseChannel::Stable,
+                ReleaseChannel::Beta,
+                ReleaseChannel::Dev,
+                ReleaseChannel::Canary,
+            ],
+            channel => vec![*channel],
         }
     }
 }
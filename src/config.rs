@@ -0,0 +1,179 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use toml_edit::{table, value, DocumentMut};
+
+use crate::{extract::ArchiveFormat, paths::default_cache_dir};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub(crate) struct Config {
+    pub(crate) drift_warn: Option<usize>,
+    /// 每隔多少天在下载前与上游比较一次缓存。
+    pub(crate) verify_cache_days: Option<u64>,
+    /// 版本列表缓存的有效期，例如 `12h`，`0` 表示永不过期，`--cache-ttl` 优先。
+    pub(crate) cache_ttl: Option<String>,
+    /// 缓存的 chromedriver 的有效天数。
+    pub(crate) driver_cache_days: Option<u64>,
+    /// 缓存目录共享时用于签名缓存文件的密钥，`FETCHBROWSER_CACHE_KEY` 优先。
+    pub(crate) cache_signing_key: Option<String>,
+    pub(crate) providers: Vec<CustomProviderConfig>,
+    /// 主机后缀到每秒最大请求数的映射，覆盖内置的默认值。
+    pub(crate) rate_limits: BTreeMap<String, f64>,
+    /// 版本别名，命令行中以 `@name` 引用。
+    pub(crate) aliases: BTreeMap<String, String>,
+    /// 上游地址的镜像，每个镜像可以单独指定信任的证书。
+    pub(crate) mirrors: Vec<MirrorConfig>,
+    /// 快照目录到依次尝试的压缩包文件名，上游改名时不必等待新版本。
+    pub(crate) snapshot_archives: BTreeMap<String, Vec<String>>,
+}
+
+/// 内置的请求频率限制，避免抓取快照列表等批量操作触发 GCS 的配额错误。
+const DEFAULT_RATE_LIMITS: &[(&str, f64)] = &[("googleapis.com", 10.0)];
+
+impl Config {
+    pub(crate) fn load() -> Result<Self> {
+        let path = config_file_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content)
+            .map_err(|err| anyhow!("Invalid config file {}: {err}", path.display()))
+    }
+
+    pub(crate) fn rate_limits(&self) -> BTreeMap<String, f64> {
+        let mut limits: BTreeMap<_, _> = DEFAULT_RATE_LIMITS
+            .iter()
+            .map(|(host, rate)| (host.to_string(), *rate))
+            .collect();
+        limits.extend(self.rate_limits.clone());
+        limits
+    }
+
+    /// 将 `@name` 形式的别名替换为配置中的版本，其他版本原样返回。
+    pub(crate) fn resolve_alias(&self, version: &str) -> Result<String> {
+        let Some(name) = version.strip_prefix('@') else {
+            return Ok(version.to_owned());
+        };
+        let resolved = self.aliases.get(name).ok_or_else(|| {
+            anyhow!(
+                "Unknown version alias @{name}, define it with `fb alias set {name} <VERSION>`."
+            )
+        })?;
+        status!("==> resolved @{name} to {resolved}");
+        Ok(resolved.clone())
+    }
+
+    pub(crate) fn provider(&self, name: &str) -> Option<&CustomProviderConfig> {
+        self.providers.iter().find(|p| p.name == name)
+    }
+}
+
+pub(crate) fn config_file_path() -> Result<PathBuf> {
+    match std::env::var_os("FETCHBROWSER_CONFIG") {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => Ok(default_cache_dir()?.join("config.toml")),
+    }
+}
+
+/// 设置或删除（`version` 为 `None`）别名，使用 toml_edit 修改配置文件以保留其中的注释和格式。
+pub(crate) fn update_alias(name: &str, version: Option<&str>) -> Result<()> {
+    let name = name.strip_prefix('@').unwrap_or(name);
+    if name.is_empty() || name.contains(['@', ' ']) {
+        return Err(anyhow!("Invalid alias name: {name}"));
+    }
+    let path = config_file_path()?;
+    let content = match path.exists() {
+        true => std::fs::read_to_string(&path)?,
+        false => String::new(),
+    };
+    let mut doc = content
+        .parse::<DocumentMut>()
+        .map_err(|err| anyhow!("Invalid config file {}: {err}", path.display()))?;
+    let aliases = doc
+        .entry("aliases")
+        .or_insert_with(table)
+        .as_table_mut()
+        .ok_or_else(|| anyhow!("`aliases` in {} is not a table.", path.display()))?;
+    match version {
+        Some(version) => {
+            aliases.insert(name, value(version));
+            status!("==> @{name} = {version}");
+        }
+        None => {
+            aliases
+                .remove(name)
+                .ok_or_else(|| anyhow!("No alias named @{name}."))?;
+            status!("==> removed @{name}");
+        }
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, doc.to_string())?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct MirrorConfig {
+    /// 被替换的上游地址前缀，例如 `https://storage.googleapis.com/`。
+    pub(crate) upstream: String,
+    /// 替换成的镜像地址前缀。
+    pub(crate) url: String,
+    /// PEM 格式的 CA 证书，在系统内置的根证书之外额外信任。
+    #[serde(default)]
+    pub(crate) ca_cert: Option<PathBuf>,
+    /// PEM 格式的 CA 证书，指定后只信任该 CA 签发的证书，不再信任系统内置的根证书。
+    ///
+    /// 这只是替换信任的根证书，并不校验服务器公钥（SPKI），不是证书固定。
+    #[serde(default)]
+    pub(crate) exclusive_ca_cert: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct CustomProviderConfig {
+    pub(crate) name: String,
+    pub(crate) versions_url: String,
+    #[serde(default)]
+    pub(crate) versions_json_pointer: Option<String>,
+    #[serde(default)]
+    pub(crate) version_field: Option<String>,
+    #[serde(default)]
+    pub(crate) versions_regex: Option<String>,
+    pub(crate) download_url: String,
+    #[serde(default)]
+    pub(crate) archive: ArchiveType,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ArchiveType {
+    /// 根据下载内容的文件头识别格式。
+    #[default]
+    Auto,
+    Zip,
+    #[serde(rename = "7z")]
+    SevenZip,
+    TarGz,
+    TarBz2,
+    TarXz,
+    None,
+}
+
+impl ArchiveType {
+    /// 配置中明确声明的格式，`auto` 和 `none` 没有。
+    pub(crate) fn format(&self) -> Option<ArchiveFormat> {
+        match self {
+            ArchiveType::Zip => Some(ArchiveFormat::Zip),
+            ArchiveType::SevenZip => Some(ArchiveFormat::SevenZip),
+            ArchiveType::TarGz => Some(ArchiveFormat::TarGz),
+            ArchiveType::TarBz2 => Some(ArchiveFormat::TarBz2),
+            ArchiveType::TarXz => Some(ArchiveFormat::TarXz),
+            ArchiveType::Auto | ArchiveType::None => None,
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::{collections::BTreeMap, net::SocketAddr, sync::Mutex};
+
+use reqwest::Version;
+
+/// 一次请求使用的连接，以本地和远端地址区分。
+struct ConnectionUse {
+    host: String,
+    local: Option<SocketAddr>,
+    remote: Option<SocketAddr>,
+    version: Version,
+}
+
+static CONNECTIONS: Mutex<Vec<ConnectionUse>> = Mutex::new(Vec::new());
+
+pub(crate) fn record(
+    host: &str,
+    local: Option<SocketAddr>,
+    remote: Option<SocketAddr>,
+    version: Version,
+) {
+    CONNECTIONS.lock().unwrap().push(ConnectionUse {
+        host: host.to_owned(),
+        local,
+        remote,
+        version,
+    });
+}
+
+/// `--audit-connections`：按主机统计请求数和新建的 TCP/TLS 连接数。
+///
+/// 地址未知（例如经过代理）的请求无法判断是否复用了连接，单独计数。
+pub(crate) fn report() {
+    let connections = CONNECTIONS.lock().unwrap();
+    let mut hosts: BTreeMap<&str, Vec<&ConnectionUse>> = BTreeMap::new();
+    for connection in connections.iter() {
+        hosts.entry(&connection.host).or_default().push(connection);
+    }
+    let mut opened = 0;
+    status!("==> connections:");
+    for (host, uses) in &hosts {
+        let mut addrs: Vec<_> = uses
+            .iter()
+            .filter_map(|c| Some((c.local?, c.remote?)))
+            .collect();
+        addrs.sort();
+        addrs.dedup();
+        let unknown = uses
+            .iter()
+            .filter(|c| c.local.is_none() || c.remote.is_none())
+            .count();
+        let mut versions: Vec<_> = uses.iter().map(|c| format!("{:?}", c.version)).collect();
+        versions.sort();
+        versions.dedup();
+        opened += addrs.len();
+        status!(
+            "    {host:<32} {:>4} requests {:>4} connections{} ({})",
+            uses.len(),
+            addrs.len(),
+            match unknown {
+                0 => String::new(),
+                unknown => format!(", {unknown} unknown"),
+            },
+            versions.join(", ")
+        );
+    }
+    status!(
+        "    {:<32} {:>4} requests {:>4} connections",
+        "total",
+        connections.len(),
+        opened
+    );
+}
@@ -0,0 +1,72 @@
+use std::{io::Cursor, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use zip::ZipArchive;
+
+use crate::{downloads::DownloadManager, downloads::DownloadRequest, paths::Paths};
+
+const CRX_MAGIC: &[u8] = b"Cr24";
+
+/// 从 Chrome 的扩展更新服务下载指定浏览器版本下可用的扩展 CRX 文件。
+pub(crate) async fn download_crx(
+    extension_id: &str,
+    chrome_version: &str,
+    downloads: &DownloadManager,
+    paths: &Paths,
+) -> Result<PathBuf> {
+    if extension_id.len() != 32 || !extension_id.chars().all(|ch| ('a'..='p').contains(&ch)) {
+        return Err(anyhow!("Invalid extension id: {extension_id}"));
+    }
+    let url = format!(
+        "https://clients2.google.com/service/update2/crx?response=redirect&prodversion={chrome_version}&acceptformat=crx2,crx3&x=id%3D{extension_id}%26uc"
+    );
+    let content = downloads
+        .fetch(&DownloadRequest::new(&url))
+        .await
+        .map_err(|err| anyhow!("Download extension failed: {err}"))?
+        .bytes()?;
+    if !content.starts_with(CRX_MAGIC) {
+        return Err(anyhow!(
+            "Extension {extension_id} is not available for Chrome {chrome_version}."
+        ));
+    }
+
+    let version = crx_version(&content)?;
+    std::fs::create_dir_all(&paths.output_dir)?;
+    let crx_path = paths
+        .output_dir
+        .join(format!("{extension_id}-{version}.crx"));
+    std::fs::write(&crx_path, &content)?;
+    status!(
+        "==> extension {extension_id} {version}: {}",
+        crx_path.display()
+    );
+    Ok(crx_path)
+}
+
+/// 从 CRX 中的 manifest.json 读取扩展版本。
+fn crx_version(content: &[u8]) -> Result<String> {
+    let read_u32 = |offset: usize| {
+        content
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| anyhow!("Truncated CRX header."))
+    };
+    // CRX2：magic、版本、公钥长度、签名长度；CRX3：magic、版本、header 长度。
+    let zip_offset = match read_u32(4)? {
+        2 => 16 + read_u32(8)? + read_u32(12)?,
+        3 => 12 + read_u32(8)?,
+        version => return Err(anyhow!("Unsupported CRX version: {version}")),
+    };
+    let zip = content
+        .get(zip_offset..)
+        .ok_or_else(|| anyhow!("Truncated CRX header."))?;
+    let mut archive = ZipArchive::new(Cursor::new(zip))?;
+    let manifest: Value = serde_json::from_reader(archive.by_name("manifest.json")?)?;
+    manifest
+        .get("version")
+        .and_then(Value::as_str)
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("No version found in manifest.json."))
+}
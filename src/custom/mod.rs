@@ -0,0 +1,247 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{
+    archive::keep_archive,
+    common::{BrowserReleaseItem, BrowserReleases, DownloadPlan, ReleaseMatches},
+    config::{ArchiveType, CustomProviderConfig},
+    downloads::{url_file_name, DownloadRequest, Downloads},
+    error::FetchError,
+    extract::{extract_archive_as, ArchiveFormat},
+    http::Http,
+    lock::with_install_lock,
+    metadata::{mark_complete, mark_incomplete, InstallMetadata},
+    paths::Paths,
+    platform::Platform,
+    timings::{self, Phase},
+    utils::blocking,
+    version::VersionSpec,
+};
+
+pub(crate) struct CustomReleases {
+    provider: CustomProviderConfig,
+    platform: Platform,
+    versions: Vec<String>,
+    downloads: Downloads,
+    paths: Paths,
+    keep_archive: bool,
+}
+
+impl CustomReleases {
+    pub(crate) async fn init(
+        provider: CustomProviderConfig,
+        platform: Platform,
+        client: Http,
+        downloads: Downloads,
+        paths: &Paths,
+        keep_archive: bool,
+    ) -> Result<Self> {
+        status!(
+            "==> retrieving {} versions from {} ...",
+            provider.name,
+            provider.versions_url
+        );
+        let body = timings::time_async(Phase::History, async {
+            let response = client.get(&provider.versions_url).await?;
+            if !response.is_success() {
+                return Err(FetchError::Network(format!(
+                    "Retrieving {} versions failed: {}",
+                    provider.name,
+                    response.status()
+                ))
+                .into());
+            }
+            response.text().await
+        })
+        .await?;
+        let versions = extract_versions(&provider, &body)?;
+        Ok(Self {
+            provider,
+            platform,
+            versions,
+            downloads,
+            paths: paths.clone(),
+            keep_archive,
+        })
+    }
+}
+
+fn extract_versions(provider: &CustomProviderConfig, body: &str) -> Result<Vec<String>> {
+    if let Some(pattern) = &provider.versions_regex {
+        let regex = Regex::new(pattern)?;
+        return Ok(regex
+            .captures_iter(body)
+            .filter_map(|cap| cap.get(1).or_else(|| cap.get(0)))
+            .map(|m| m.as_str().to_owned())
+            .collect());
+    }
+
+    let json: Value = serde_json::from_str(body)?;
+    let pointer = provider.versions_json_pointer.as_deref().unwrap_or("");
+    let list = json
+        .pointer(pointer)
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("No version list found at json pointer `{pointer}`"))?;
+    Ok(list
+        .iter()
+        .filter_map(|item| match &provider.version_field {
+            Some(field) => item.get(field).and_then(Value::as_str),
+            None => item.as_str(),
+        })
+        .map(str::to_owned)
+        .collect())
+}
+
+impl BrowserReleases for CustomReleases {
+    fn match_version<'r>(&'r self, version: &str) -> ReleaseMatches<'r> {
+        let spec = VersionSpec::parse(version);
+        let matches = self
+            .versions
+            .iter()
+            .filter(move |v| spec.matches(v))
+            .map(|v| {
+                let item: Box<dyn BrowserReleaseItem> = Box::new(CustomReleaseItem {
+                    name: self.provider.name.clone(),
+                    version: v.clone(),
+                    url: self.download_url(v),
+                    platform: self.platform,
+                    archive: self.provider.archive,
+                    downloads: self.downloads.clone(),
+                    paths: self.paths.clone(),
+                    keep_archive: self.keep_archive,
+                });
+                Ok(item)
+            });
+        stream::iter(matches).boxed()
+    }
+}
+
+impl CustomReleases {
+    fn download_url(&self, version: &str) -> String {
+        self.provider
+            .download_url
+            .replace("{version}", version)
+            .replace("{os}", self.platform.os().as_str())
+            .replace("{arch}", self.platform.arch().as_str())
+            .replace("{prefix}", self.platform.prefix())
+    }
+}
+
+pub(crate) struct CustomReleaseItem {
+    name: String,
+    version: String,
+    url: String,
+    platform: Platform,
+    archive: ArchiveType,
+    downloads: Downloads,
+    paths: Paths,
+    keep_archive: bool,
+}
+
+#[async_trait]
+impl BrowserReleaseItem for CustomReleaseItem {
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    async fn download(&self) -> Result<PathBuf> {
+        with_install_lock(&self.paths, &self.name, &self.version, self.install()).await
+    }
+
+    async fn prefetch_requests(&self) -> Result<Vec<DownloadRequest>> {
+        Ok(vec![self.request()])
+    }
+
+    async fn plan(&self) -> Result<DownloadPlan> {
+        Ok(DownloadPlan {
+            requests: vec![self.request()],
+            revision: None,
+            install_dir: self.paths.install_dir(&self.name, &self.version),
+        })
+    }
+}
+
+impl CustomReleaseItem {
+    fn request(&self) -> DownloadRequest {
+        DownloadRequest::new(&self.url).with_cache_key(
+            &self.name,
+            &self.version,
+            &format!(
+                "{}-{}",
+                self.platform.os().as_str(),
+                self.platform.arch().as_str()
+            ),
+            url_file_name(&self.url),
+        )
+    }
+
+    async fn install(&self) -> Result<PathBuf> {
+        let content = self
+            .downloads
+            .fetch(&self.request())
+            .await
+            .map_err(|err| anyhow!("Download {} failed: {err}", self.name))?
+            .bytes()?;
+
+        let base_path = self.paths.install_dir(&self.name, &self.version);
+        mark_incomplete(&base_path)?;
+        // 配置中明确声明的格式优先，文件头不符时报错，而不是按识别出的格式解压。
+        let format = match self.archive {
+            ArchiveType::None => None,
+            archive => match (archive.format(), ArchiveFormat::detect(&content)) {
+                (Some(expected), Some(detected)) if expected != detected => {
+                    return Err(FetchError::Extraction(format!(
+                        "{} is configured as {expected}, but looks like {detected}.",
+                        self.url
+                    ))
+                    .into())
+                }
+                (Some(format), _) | (None, Some(format)) => Some(format),
+                (None, None) => return Err(anyhow!("Unrecognized archive format of {}", self.url)),
+            },
+        };
+        let archive_path = format.filter(|_| self.keep_archive).map(|format| {
+            self.paths.output_dir.join(format!(
+                "{}-{}.{}",
+                self.name,
+                self.version,
+                format.extension()
+            ))
+        });
+        if let Some(archive_path) = &archive_path {
+            std::fs::write(archive_path, &content)?;
+        }
+        match format {
+            None => {
+                let file_name = self
+                    .url
+                    .rsplit('/')
+                    .next()
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or(&self.name);
+                std::fs::write(base_path.join(file_name), &content)?;
+            }
+            Some(format) => {
+                let base_path = base_path.clone();
+                blocking(move || {
+                    timings::time(Phase::Extract, || {
+                        extract_archive_as(content, &base_path, format)
+                    })
+                })
+                .await?;
+            }
+        }
+        let metadata = InstallMetadata::new(&self.name, &self.version);
+        metadata.write(&base_path)?;
+        if let Some(archive_path) = &archive_path {
+            keep_archive(archive_path, &metadata)?;
+        }
+        mark_complete(&base_path)?;
+        Ok(base_path)
+    }
+}
@@ -0,0 +1,447 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::metrics;
+
+/// 请求体的最大长度，请求只包含几个字段。
+const MAX_BODY: usize = 64 * 1024;
+
+/// 请求行和请求头的最大总长度。
+const MAX_HEADERS: u64 = 16 * 1024;
+
+/// 读完一个请求（请求行、请求头和请求体）的最长时间，超时的连接直接关闭。
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 处理连接的线程数，处理一个请求最多占用 [`REQUEST_TIMEOUT`]。
+const CONNECTION_WORKERS: usize = 8;
+
+/// 等待处理的连接数，超过时直接关闭新的连接。
+const CONNECTION_BACKLOG: usize = 64;
+
+/// 排队等待执行的任务数，超过时 `POST /fetch` 返回 503。
+const MAX_QUEUED_JOBS: usize = 64;
+
+/// 保留的已结束任务数，超过时丢弃最早的，`GET /status/<id>` 对它们返回 404。
+const MAX_FINISHED_JOBS: usize = 256;
+
+/// `POST /fetch` 的请求体。
+#[derive(Debug, Deserialize)]
+pub(crate) struct FetchRequest {
+    /// `chromium`、`chrome`、`firefox`、`edge` 或配置文件中的自定义 provider。
+    pub(crate) browser: String,
+    pub(crate) version: String,
+    /// `linux`、`windows-x86` 等，默认为 daemon 的 `--os`、`--arch`。
+    #[serde(default)]
+    pub(crate) platform: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum JobState {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Job {
+    id: usize,
+    browser: String,
+    version: String,
+    platform: Option<String>,
+    state: JobState,
+    path: Option<PathBuf>,
+    error: Option<String>,
+}
+
+/// 内存中的任务，编号从 1 开始递增，不因丢弃结束的任务而重复。
+#[derive(Debug, Default)]
+struct Jobs {
+    last_id: usize,
+    list: VecDeque<Job>,
+}
+
+impl Jobs {
+    fn next_id(&self) -> usize {
+        self.last_id + 1
+    }
+
+    fn push(&mut self, job: Job) {
+        self.last_id = job.id;
+        self.list.push_back(job);
+    }
+
+    fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.list.iter_mut().find(|job| job.id == id)
+    }
+
+    /// 结束的任务超过 [`MAX_FINISHED_JOBS`] 个时，丢弃最早提交的。
+    fn evict_finished(&mut self) {
+        let finished = |job: &Job| matches!(job.state, JobState::Finished | JobState::Failed);
+        let mut count = self.list.iter().filter(|job| finished(job)).count();
+        while count > MAX_FINISHED_JOBS {
+            if let Some(index) = self.list.iter().position(finished) {
+                self.list.remove(index);
+            }
+            count -= 1;
+        }
+    }
+}
+
+/// `fb daemon`：在 `listen` 上提供 HTTP 接口，`POST /fetch` 提交的任务由 `workers` 个线程调用 `fetch` 执行。
+///
+/// - `POST /fetch`：`{"browser", "version", "platform"}`，返回 202 及任务编号，排队的任务过多时返回 503；
+/// - `GET /status/<id>`：任务的状态（`queued`、`running`、`finished`、`failed`）、安装目录或错误；
+/// - `GET /jobs`：所有任务；
+/// - `GET /metrics`：Prometheus 文本格式的下载和缓存统计，与 `--metrics-file` 的内容相同。
+///
+/// 连接由固定数量的线程处理，读取请求有超时和长度限制。任务只保存在内存中，daemon 重启后编号从 1 重新开始，
+/// 只保留最近 [`MAX_FINISHED_JOBS`] 个已结束的任务。`fetch` 的第一个参数为任务编号。
+pub(crate) fn serve(
+    listen: &str,
+    workers: usize,
+    fetch: impl Fn(usize, &FetchRequest) -> Result<PathBuf> + Sync,
+) -> Result<()> {
+    let listener =
+        TcpListener::bind(listen).map_err(|err| anyhow!("Listening on {listen} failed: {err}"))?;
+    status!("==> listening on http://{}", listener.local_addr()?);
+    let jobs = Mutex::new(Jobs::default());
+    let (connection_sender, connections) = mpsc::sync_channel(CONNECTION_BACKLOG);
+    let connections = Mutex::new(connections);
+    let (queue, queued) = mpsc::sync_channel(MAX_QUEUED_JOBS);
+    let queued = Mutex::new(queued);
+    std::thread::scope(|scope| {
+        for _ in 0..CONNECTION_WORKERS {
+            scope.spawn(|| loop {
+                let Ok(stream) = connections.lock().unwrap().recv() else {
+                    break;
+                };
+                if let Err(err) = handle(stream, &jobs, &queue) {
+                    status!("==> warning[daemon]: {err}");
+                }
+            });
+        }
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| loop {
+                let Ok((id, request)) = queued.lock().unwrap().recv() else {
+                    break;
+                };
+                run_job(id, &request, &jobs, &fetch);
+            });
+        }
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            if let Err(TrySendError::Full(_)) = connection_sender.try_send(stream) {
+                status!("==> warning[daemon]: too many pending connections, dropping one");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// 执行一个任务，`fetch` panic 时同样把任务标记为失败。
+fn run_job(
+    id: usize,
+    request: &FetchRequest,
+    jobs: &Mutex<Jobs>,
+    fetch: &dyn Fn(usize, &FetchRequest) -> Result<PathBuf>,
+) {
+    if let Some(job) = jobs.lock().unwrap().get_mut(id) {
+        job.state = JobState::Running;
+    }
+    status!(
+        "==> job {id}: fetching {} {}",
+        request.browser,
+        request.version
+    );
+    let result =
+        panic::catch_unwind(AssertUnwindSafe(|| fetch(id, request))).unwrap_or_else(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(anyhow!("The fetch panicked: {message}"))
+        });
+    let mut jobs = jobs.lock().unwrap();
+    let Some(job) = jobs.get_mut(id) else {
+        return;
+    };
+    match result {
+        Ok(path) => {
+            status!("==> job {id}: installed {}", path.display());
+            job.state = JobState::Finished;
+            job.path = Some(path);
+        }
+        Err(err) => {
+            status!("==> job {id}: failed: {err}");
+            job.state = JobState::Failed;
+            job.error = Some(err.to_string());
+        }
+    }
+    jobs.evict_finished();
+}
+
+fn handle(
+    mut stream: TcpStream,
+    jobs: &Mutex<Jobs>,
+    queue: &SyncSender<(usize, FetchRequest)>,
+) -> Result<()> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let (method, path, body) = read_request(&stream)?;
+    match (method.as_str(), path.as_str()) {
+        ("POST", "/fetch") => {
+            let request: FetchRequest = match serde_json::from_slice(&body) {
+                Ok(request) => request,
+                Err(err) => return respond(&mut stream, 400, &json!({ "error": err.to_string() })),
+            };
+            let job = Job {
+                id: 0,
+                browser: request.browser.clone(),
+                version: request.version.clone(),
+                platform: request.platform.clone(),
+                state: JobState::Queued,
+                path: None,
+                error: None,
+            };
+            // 持有锁入队，保证任务开始执行时已经记录在 `jobs` 中。
+            let mut jobs = jobs.lock().unwrap();
+            let id = jobs.next_id();
+            match queue.try_send((id, request)) {
+                Ok(()) => {
+                    status!("==> job {id}: queued {} {}", job.browser, job.version);
+                    jobs.push(Job { id, ..job });
+                }
+                Err(_) => {
+                    drop(jobs);
+                    return respond(
+                        &mut stream,
+                        503,
+                        &json!({ "error": "too many queued jobs" }),
+                    );
+                }
+            }
+            drop(jobs);
+            respond(&mut stream, 202, &json!({ "id": id }))
+        }
+        ("GET", "/metrics") => respond_text(&mut stream, 200, &metrics::render()),
+        ("GET", "/jobs") => {
+            let jobs = jobs.lock().unwrap().list.clone();
+            respond(&mut stream, 200, &json!(jobs))
+        }
+        ("GET", path) if path.starts_with("/status/") => {
+            let job = path["/status/".len()..]
+                .parse::<usize>()
+                .ok()
+                .and_then(|id| jobs.lock().unwrap().get_mut(id).cloned());
+            match job {
+                Some(job) => respond(&mut stream, 200, &json!(job)),
+                None => respond(&mut stream, 404, &json!({ "error": "no such job" })),
+            }
+        }
+        _ => respond(&mut stream, 404, &json!({ "error": "not found" })),
+    }
+}
+
+/// 读取请求行、请求头和 `Content-Length` 指定长度的请求体。
+///
+/// 请求头不超过 [`MAX_HEADERS`]，请求体不超过 [`MAX_BODY`]，整个请求须在 [`REQUEST_TIMEOUT`] 内读完。
+fn read_request(stream: &TcpStream) -> Result<(String, String, Vec<u8>)> {
+    let started = Instant::now();
+    // 每次读取前把超时设为剩余的时间，逐字节发送的慢速客户端同样会超时。
+    let set_timeout = || -> Result<()> {
+        let remaining = REQUEST_TIMEOUT
+            .checked_sub(started.elapsed())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or_else(|| anyhow!("Reading the request timed out"))?;
+        stream.set_read_timeout(Some(remaining))?;
+        Ok(())
+    };
+    let mut reader = BufReader::new(stream.take(MAX_HEADERS));
+    let mut read_line = |line: &mut String| -> Result<()> {
+        line.clear();
+        set_timeout()?;
+        reader.read_line(line)?;
+        if !line.ends_with('\n') {
+            return Err(anyhow!("Request headers are truncated or too large"));
+        }
+        Ok(())
+    };
+    let mut line = String::new();
+    read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(anyhow!("Malformed request line: {}", line.trim()));
+    };
+    let (method, path) = (method.to_owned(), path.to_owned());
+    let mut content_length = 0;
+    loop {
+        read_line(&mut line)?;
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(anyhow!(
+            "Request body of {content_length} bytes is too large"
+        ));
+    }
+    reader.get_mut().set_limit(content_length as u64);
+    let mut body = vec![0; content_length];
+    let mut read = 0;
+    while read < content_length {
+        set_timeout()?;
+        match reader.read(&mut body[read..])? {
+            0 => {
+                return Err(anyhow!(
+                    "Connection closed before the request body was read"
+                ))
+            }
+            len => read += len,
+        }
+    }
+    Ok((method, path, body))
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &Value) -> Result<()> {
+    write_response(stream, status, "application/json", &body.to_string())
+}
+
+fn respond_text(stream: &mut TcpStream, status: u16, body: &str) -> Result<()> {
+    write_response(stream, status, "text/plain; version=0.0.4", body)
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        503 => "Service Unavailable",
+        _ => "Not Found",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 把 `request` 发给 `read_request`，返回解析结果。
+    fn read(request: Vec<u8>) -> Result<(String, String, Vec<u8>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let writer = std::thread::spawn(move || {
+            let _ = client.write_all(&request);
+            client
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let result = read_request(&stream);
+        drop(writer.join().unwrap());
+        result
+    }
+
+    #[test]
+    fn requests_are_parsed() {
+        let body = r#"{"browser":"chromium","version":"114"}"#;
+        let request = format!(
+            "POST /fetch HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        let (method, path, read_body) = read(request.into_bytes()).unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("POST", "/fetch"));
+        assert_eq!(read_body, body.as_bytes());
+    }
+
+    #[test]
+    fn oversized_requests_are_rejected() {
+        let mut headers = b"GET /jobs HTTP/1.1\r\n".to_vec();
+        headers.extend(b"X-Padding: ".iter().chain(&[b'a'; MAX_HEADERS as usize]));
+        headers.extend(b"\r\n\r\n");
+        assert!(read(headers).is_err());
+        let body = format!(
+            "POST /fetch HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert!(read(body.into_bytes()).is_err());
+    }
+
+    fn job(id: usize) -> Job {
+        Job {
+            id,
+            browser: "chromium".to_owned(),
+            version: "114".to_owned(),
+            platform: None,
+            state: JobState::Queued,
+            path: None,
+            error: None,
+        }
+    }
+
+    fn request() -> FetchRequest {
+        FetchRequest {
+            browser: "chromium".to_owned(),
+            version: "114".to_owned(),
+            platform: None,
+        }
+    }
+
+    #[test]
+    fn panicking_fetches_fail_the_job() {
+        let mut jobs = Jobs::default();
+        jobs.push(job(1));
+        let jobs = Mutex::new(jobs);
+        run_job(1, &request(), &jobs, &|_, _| panic!("boom"));
+        let mut jobs = jobs.lock().unwrap();
+        let job = jobs.get_mut(1).unwrap();
+        assert!(matches!(job.state, JobState::Failed));
+        assert_eq!(job.error.as_deref(), Some("The fetch panicked: boom"));
+    }
+
+    #[test]
+    fn oldest_finished_jobs_are_evicted() {
+        let mut jobs = Jobs::default();
+        let running = jobs.next_id();
+        jobs.push(job(running));
+        let jobs = Mutex::new(jobs);
+        for _ in 0..MAX_FINISHED_JOBS + 2 {
+            let id = jobs.lock().unwrap().next_id();
+            jobs.lock().unwrap().push(job(id));
+            run_job(id, &request(), &jobs, &|_, _| Ok(PathBuf::new()));
+        }
+        let mut jobs = jobs.lock().unwrap();
+        assert_eq!(jobs.list.len(), MAX_FINISHED_JOBS + 1);
+        assert!(jobs.get_mut(running).is_some());
+        assert!(jobs.get_mut(2).is_none() && jobs.get_mut(3).is_none());
+        assert_eq!(jobs.next_id(), MAX_FINISHED_JOBS + 4);
+    }
+}
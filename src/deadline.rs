@@ -0,0 +1,66 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+
+use crate::{lock, summary};
+
+/// 超过 `--deadline` 时的退出码，与 `timeout(1)` 相同。
+pub(crate) const DEADLINE_EXIT_CODE: i32 = 124;
+
+/// 超时后等待正在进行的安装停下的最长时间。
+const GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// 已超过 `--deadline`，下载和解压在下一次检查时停止。
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// 下载和解压的循环中调用，超过 `--deadline` 后返回错误，让安装尽快停下。
+pub(crate) fn check() -> std::io::Result<()> {
+    match cancelled() {
+        true => Err(std::io::Error::other("Deadline exceeded")),
+        false => Ok(()),
+    }
+}
+
+/// 已超过 `--deadline` 时阻塞当前线程，由计时线程输出摘要并退出进程。
+pub(crate) fn wait_if_cancelled() {
+    while cancelled() {
+        std::thread::park();
+    }
+}
+
+/// 在后台计时，超过 `limit` 后通知正在进行的安装停止，等它们清理完再退出进程。
+///
+/// 安装在停止后自行删除临时目录和未完成的安装目录（见 [`lock::with_install_lock`]），
+/// 这里不在安装仍在写入时删除目录。等待超过 [`GRACE_PERIOD`] 时直接退出，
+/// 留下的安装目录带有未完成标记，下次运行时重新安装。
+/// 下载中的 `.part` 文件保留，下次运行时可以续传。
+pub(crate) fn start(limit: Duration) {
+    let started = Instant::now();
+    std::thread::spawn(move || {
+        std::thread::sleep(limit);
+        CANCELLED.store(true, Ordering::Relaxed);
+        status!();
+        status!(
+            "==> error[deadline]: the run exceeded --deadline {}s, aborting",
+            limit.as_secs()
+        );
+        if !lock::wait_for_installs(GRACE_PERIOD) {
+            status!(
+                "==> warning[deadline]: installs still running after {}s, leaving them marked incomplete",
+                GRACE_PERIOD.as_secs()
+            );
+        }
+        summary::print_summary(
+            &Err(anyhow!("Deadline of {}s exceeded", limit.as_secs())),
+            started.elapsed(),
+        );
+        std::process::exit(DEADLINE_EXIT_CODE);
+    });
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// 解析版本时失败的阶段。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum ResolutionStage {
+    /// 发布历史中没有与请求匹配的版本。
+    History,
+    /// 匹配的版本都没有记录分支位置。
+    BasePosition,
+    /// 分支位置附近（容差范围内）没有快照。
+    Snapshot,
+}
+
+impl ResolutionStage {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ResolutionStage::History => "history",
+            ResolutionStage::BasePosition => "base-position",
+            ResolutionStage::Snapshot => "snapshot",
+        }
+    }
+}
+
+/// 版本解析失败时的诊断信息：失败的阶段、原因，以及该阶段最接近的候选项。
+///
+/// 作为错误的上下文返回，文本输出时打印为多行说明，`--format json` 时写入摘要的 `diagnostic` 字段。
+#[derive(Debug, Serialize)]
+pub(crate) struct ResolutionDiagnostic {
+    pub(crate) browser: String,
+    pub(crate) version: String,
+    pub(crate) platform: String,
+    pub(crate) channel: String,
+    pub(crate) stage: ResolutionStage,
+    pub(crate) reason: String,
+    pub(crate) candidates: Vec<String>,
+}
+
+impl fmt::Display for ResolutionDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Could not resolve {} {} for {} (channel {})",
+            self.browser, self.version, self.platform, self.channel
+        )?;
+        writeln!(f, "  stage:  {}", self.stage.as_str())?;
+        write!(f, "  reason: {}", self.reason)?;
+        if !self.candidates.is_empty() {
+            write!(f, "\n  nearest candidates:")?;
+            for candidate in &self.candidates {
+                write!(f, "\n    - {candidate}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ResolutionDiagnostic {}
@@ -0,0 +1,556 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::mpsc,
+};
+
+use crate::{
+    cache::CacheSettings,
+    checksums::{verify_enabled, Checksum},
+    deadline,
+    error::FetchError,
+    http::Http,
+    metrics, net,
+    paths::Paths,
+    progress::DownloadProgress,
+    summary,
+    timings::{self, Phase},
+    utils::{blocking, run_bounded, sha256_file},
+};
+
+pub(crate) type Downloads = Arc<DownloadManager>;
+
+/// 缓存目录中保存压缩包的子目录。
+pub(crate) const ARCHIVE_CACHE: &str = "archives";
+
+/// 网络错误和服务端错误（5xx）的重试次数。
+const DOWNLOAD_RETRIES: u32 = 3;
+
+/// 压缩包缓存的使用方式。
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub(crate) enum ArchiveCache {
+    /// 有缓存时直接使用，下载后写入缓存。
+    Use,
+    /// `--refresh`：忽略已有的缓存重新下载，并更新缓存。
+    Refresh,
+    /// `--no-cache`：不读也不写缓存。
+    #[default]
+    Disabled,
+}
+
+/// 一次下载请求，`size`、`sha256`、`checksum` 已知时下载完成后校验。
+#[derive(Debug, Clone)]
+pub struct DownloadRequest {
+    pub(crate) url: String,
+    pub(crate) size: Option<u64>,
+    pub(crate) sha256: Option<String>,
+    pub(crate) checksum: Option<Checksum>,
+    /// 压缩包在缓存目录 `archives` 下的相对路径，为 `None` 时不缓存。
+    pub(crate) cache_key: Option<PathBuf>,
+}
+
+impl DownloadRequest {
+    pub(crate) fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            size: None,
+            sha256: None,
+            checksum: None,
+            cache_key: None,
+        }
+    }
+
+    /// 缓存为 `archives/<browser>/<version>/<platform>/<file>`。
+    pub(crate) fn with_cache_key(
+        mut self,
+        browser: &str,
+        version: &str,
+        platform: &str,
+        file: &str,
+    ) -> Self {
+        self.cache_key = Some([browser, version, platform, file].iter().collect());
+        self
+    }
+
+    pub(crate) fn with_size(mut self, size: Option<u64>) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub(crate) fn with_checksum(mut self, checksum: Option<Checksum>) -> Self {
+        self.checksum = checksum;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Downloaded {
+    pub(crate) path: PathBuf,
+    pub(crate) size: u64,
+    pub(crate) sha256: String,
+}
+
+impl Downloaded {
+    pub(crate) fn bytes(&self) -> Result<Bytes> {
+        Ok(Bytes::from(std::fs::read(&self.path)?))
+    }
+}
+
+/// 未完成的下载的状态，与 `.part` 文件一同保存。
+#[derive(Debug, Serialize, Deserialize)]
+struct PartState {
+    url: String,
+    validator: Option<String>,
+}
+
+/// 服务端返回的非成功状态码。
+#[derive(Debug)]
+pub(crate) struct StatusError(pub(crate) u16);
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP {}", self.0)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// 统一处理压缩包的下载：排队并发、失败重试、断点续传、校验，同一 URL 只下载一次。
+///
+/// 文件以 URL 的摘要命名，保存在 `<temp_dir>/.fetchbrowser-downloads` 下。
+/// 未完成的 `.part` 文件在下次下载同一 URL 时续传，下载完成的文件在管理器释放时删除。
+/// 带有 `cache_key` 的请求在启用压缩包缓存时移入缓存目录保留，下次直接使用。
+pub(crate) struct DownloadManager {
+    client: Http,
+    dir: PathBuf,
+    jobs: usize,
+    archive_dir: PathBuf,
+    archive_cache: ArchiveCache,
+    /// 单个文件同时使用的连接数，1 为不分段。
+    connections: usize,
+    cache: CacheSettings,
+    /// `dir` 是 [`DownloadManager::with_subdir`] 的子目录。
+    scratch: bool,
+    entries: Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<Downloaded>>>>>,
+}
+
+impl DownloadManager {
+    pub(crate) fn new(client: Http, temp_dir: &Path, jobs: usize) -> Self {
+        Self {
+            client,
+            dir: temp_dir.join(".fetchbrowser-downloads"),
+            jobs,
+            archive_dir: PathBuf::new(),
+            archive_cache: ArchiveCache::Disabled,
+            connections: 1,
+            cache: CacheSettings::default(),
+            scratch: false,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 压缩包缓存保存在 `<cache_dir>/archives` 下，按 `paths` 中的密钥签名。
+    pub(crate) fn with_archive_cache(mut self, paths: &Paths, mode: ArchiveCache) -> Self {
+        self.archive_dir = paths.cache_dir.join(ARCHIVE_CACHE);
+        self.archive_cache = mode;
+        self.cache = paths.cache.clone();
+        self
+    }
+
+    /// 下载到 `.fetchbrowser-downloads` 下的 `name` 子目录，管理器释放时删除整个子目录，
+    /// 与同时运行的其他管理器互不影响。
+    pub(crate) fn with_subdir(mut self, name: &str) -> Self {
+        self.dir = self.dir.join(name);
+        self.scratch = true;
+        self
+    }
+
+    /// 大文件分成若干段同时下载，见 [`net::download_segmented`]。
+    pub(crate) fn with_connections(mut self, connections: usize) -> Self {
+        self.connections = connections.max(1);
+        self
+    }
+
+    pub(crate) async fn fetch(&self, request: &DownloadRequest) -> Result<Downloaded> {
+        let entry = self
+            .entries
+            .lock()
+            .unwrap()
+            .entry(request.url.clone())
+            .or_default()
+            .clone();
+        // 同一 URL 的并发请求在这里排队，后来者直接使用已下载的文件。
+        let mut entry = entry.lock().await;
+        if let Some(downloaded) = entry.as_ref() {
+            status!("==> already downloaded {}", request.url);
+            return Ok(downloaded.clone());
+        }
+        let cached = self.cached_path(request);
+        if let Some(cached) = &cached {
+            if let Some(downloaded) = self.load_cached(request, cached).await? {
+                *entry = Some(downloaded.clone());
+                return Ok(downloaded);
+            }
+        }
+        status!("==> downloading {}", request.url);
+        summary::emit_event("download-started", json!({ "url": request.url }));
+        let downloaded = timings::time_async(Phase::Download, self.download(request))
+            .await
+            .inspect_err(|err| {
+                metrics::download_failed();
+                summary::emit_event(
+                    "download-failed",
+                    json!({ "url": request.url, "error": err.to_string() }),
+                );
+            })?;
+        let downloaded = match &cached {
+            Some(cached) => self.store_cached(downloaded, cached)?,
+            None => downloaded,
+        };
+        metrics::download_finished(downloaded.size);
+        summary::emit_event(
+            "download-finished",
+            json!({ "url": request.url, "size": downloaded.size, "sha256": downloaded.sha256 }),
+        );
+        log::debug!(
+            "==> downloaded {}: {} bytes, sha256 {}",
+            request.url,
+            downloaded.size,
+            downloaded.sha256
+        );
+        *entry = Some(downloaded.clone());
+        Ok(downloaded)
+    }
+
+    /// 按 `--jobs` 限制并发下载，结果与请求的顺序一致。
+    pub(crate) async fn fetch_all(&self, requests: &[DownloadRequest]) -> Result<Vec<Downloaded>> {
+        let requests: Vec<&DownloadRequest> = requests.iter().collect();
+        run_bounded(requests, self.jobs, |request| self.fetch(request)).await
+    }
+
+    /// 多个平台的下载与安装流水线：按顺序下载每一步的 `requests`，
+    /// 该步下载完成后调用 `install`，安装（解压）时同时在下载下一步。
+    ///
+    /// 最多提前下载一步，避免同时在磁盘上留下所有平台的压缩包。
+    /// `install` 中再次请求相同的 URL 时直接使用已下载的文件。
+    pub(crate) async fn pipeline<T, R, F>(
+        &self,
+        stages: Vec<(Vec<DownloadRequest>, T)>,
+        mut install: impl FnMut(T) -> F,
+    ) -> Result<Vec<R>>
+    where
+        F: Future<Output = Result<R>>,
+    {
+        let (requests, items): (Vec<_>, Vec<_>) = stages.into_iter().unzip();
+        let (sender, mut receiver) = mpsc::channel(1);
+        let prefetch = async move {
+            for requests in requests {
+                let fetched = self.fetch_all(&requests).await.map(drop);
+                if sender.send(fetched).await.is_err() {
+                    break;
+                }
+            }
+        };
+        let installs = async move {
+            let mut installed = Vec::new();
+            for item in items {
+                receiver
+                    .recv()
+                    .await
+                    .ok_or_else(|| anyhow!("Prefetching the archives stopped unexpectedly."))??;
+                installed.push(install(item).await?);
+            }
+            // 提前出错时 `receiver` 随之释放，不再下载后面的平台。
+            Ok(installed)
+        };
+        tokio::join!(prefetch, installs).1
+    }
+
+    async fn download(&self, request: &DownloadRequest) -> Result<Downloaded> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let name = format!("{:x}", Sha256::digest(request.url.as_bytes()));
+        let path = self.dir.join(&name);
+        let part_path = self.dir.join(format!("{name}.part"));
+        let state_path = self.dir.join(format!("{name}.part.json"));
+        // 有未完成的下载时续传，不再分段。
+        let segmented = !state_path.exists()
+            && net::download_segmented(
+                &self.client,
+                &request.url,
+                request.size,
+                &part_path,
+                self.connections,
+            )
+            .await
+            .unwrap_or_else(|err| {
+                status!(
+                    "==> segmented download of {} failed: {err}, using a single connection",
+                    request.url
+                );
+                false
+            });
+        if !segmented {
+            let mut attempt = 0;
+            while let Err(err) = self.download_part(request, &part_path, &state_path).await {
+                let retryable = err
+                    .downcast_ref::<StatusError>()
+                    .is_none_or(|StatusError(status)| *status >= 500);
+                if !retryable || attempt == DOWNLOAD_RETRIES {
+                    return Err(err);
+                }
+                attempt += 1;
+                status!(
+                    "==> download {} failed: {err}, retrying ({attempt}/{DOWNLOAD_RETRIES}) ...",
+                    request.url
+                );
+                tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+            }
+        }
+
+        let (size, sha256, mismatch) = inspect(request, &part_path).await?;
+        if let Some(mismatch) = mismatch {
+            // 内容有误时不能留给下次续传。
+            std::fs::remove_file(&part_path)?;
+            let _ = std::fs::remove_file(&state_path);
+            return Err(FetchError::ChecksumMismatch(format!(
+                "Verifying {} failed: {mismatch}",
+                request.url
+            ))
+            .into());
+        }
+        std::fs::rename(&part_path, &path)?;
+        let _ = std::fs::remove_file(&state_path);
+        Ok(Downloaded { path, size, sha256 })
+    }
+
+    /// `--dry-run`：不下载，返回请求的文件大小。大小未知时只请求第一个字节，同时确认文件存在；
+    /// 服务端不支持 `Range` 且没有 Content-Length 时为 `None`。
+    pub(crate) async fn probe(&self, request: &DownloadRequest) -> Result<Option<u64>> {
+        if request.size.is_some() {
+            return Ok(request.size);
+        }
+        let response = self.client.get_range(&request.url, 0, 0).await?;
+        match response.status() {
+            206 => Ok(response.total_length()),
+            200 => Ok(response.content_length()),
+            status => Err(FetchError::Network(format!(
+                "Retrieving {} failed: {status}",
+                request.url
+            ))
+            .into()),
+        }
+    }
+
+    /// 压缩包缓存中已有该请求的文件，安装时不需要下载。
+    pub(crate) fn is_cached(&self, request: &DownloadRequest) -> bool {
+        self.archive_cache != ArchiveCache::Refresh
+            && self
+                .cached_path(request)
+                .is_some_and(|cached| cached.exists())
+    }
+
+    fn cached_path(&self, request: &DownloadRequest) -> Option<PathBuf> {
+        match self.archive_cache {
+            ArchiveCache::Disabled => None,
+            _ => Some(self.archive_dir.join(request.cache_key.as_ref()?)),
+        }
+    }
+
+    /// 缓存的压缩包同样按请求校验，签名或内容不一致时删除并重新下载。
+    async fn load_cached(
+        &self,
+        request: &DownloadRequest,
+        cached: &Path,
+    ) -> Result<Option<Downloaded>> {
+        if self.archive_cache == ArchiveCache::Refresh || !cached.exists() {
+            metrics::cache_lookup(false);
+            return Ok(None);
+        }
+        if !self.cache.signature_valid(cached) {
+            metrics::cache_lookup(false);
+            return Ok(None);
+        }
+        let (size, sha256, mismatch) = inspect(request, cached).await?;
+        if let Some(mismatch) = mismatch {
+            status!(
+                "==> warning[archive-cache]: cached {} is invalid ({mismatch}), downloading it again",
+                cached.display()
+            );
+            std::fs::remove_file(cached)?;
+            metrics::cache_lookup(false);
+            return Ok(None);
+        }
+        metrics::cache_lookup(true);
+        status!("==> using cached archive: {}", cached.display());
+        Ok(Some(Downloaded {
+            path: cached.to_owned(),
+            size,
+            sha256,
+        }))
+    }
+
+    /// 把下载完成的文件移入缓存目录，缓存目录不可写时给出警告，继续使用下载目录中的文件。
+    fn store_cached(&self, downloaded: Downloaded, cached: &Path) -> Result<Downloaded> {
+        let stored = cached
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                // 下载目录与缓存目录可能不在同一个文件系统上。
+                std::fs::rename(&downloaded.path, cached).or_else(|_| {
+                    std::fs::copy(&downloaded.path, cached)?;
+                    std::fs::remove_file(&downloaded.path)
+                })
+            })
+            .map_err(anyhow::Error::from)
+            .and_then(|_| self.cache.sign(cached));
+        if let Err(err) = stored {
+            status!(
+                "==> warning[archive-cache]: caching {} failed: {err}",
+                cached.display()
+            );
+            return Ok(downloaded);
+        }
+        Ok(Downloaded {
+            path: cached.to_owned(),
+            ..downloaded
+        })
+    }
+
+    /// 下载到 `.part` 文件，已有部分内容时只请求剩余的部分。
+    ///
+    /// 旁边的 `.part.json` 记录开始下载时的 ETag/Last-Modified，续传时通过 `If-Range` 发送，
+    /// 文件在两次下载之间发生变化或服务端不支持 `Range` 时，服务端返回完整内容，从头下载。
+    /// 没有状态文件的 `.part` 无法确认来源，同样从头下载。
+    async fn download_part(
+        &self,
+        request: &DownloadRequest,
+        part_path: &Path,
+        state_path: &Path,
+    ) -> Result<()> {
+        let url = request.url.as_str();
+        let state = std::fs::read(state_path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<PartState>(&content).ok())
+            .filter(|state| state.url == url);
+        let offset = match &state {
+            Some(_) => std::fs::metadata(part_path)
+                .map(|metadata| metadata.len())
+                .unwrap_or_default(),
+            None => 0,
+        };
+        let mut response = match offset {
+            0 => self.client.get(url).await?,
+            offset => {
+                status!("==> resuming {url} from {offset} bytes");
+                let validator = state.as_ref().and_then(|state| state.validator.as_deref());
+                self.client.get_from(url, offset, validator).await?
+            }
+        };
+        let (mut file, resumed) = match response.status() {
+            206 => (
+                OpenOptions::new().append(true).open(part_path).await?,
+                offset,
+            ),
+            // 文件已经完整，大小和摘要留给后面校验。
+            416 if offset > 0 => return Ok(()),
+            _ if response.is_success() => {
+                if offset > 0 {
+                    status!("==> {url} cannot be resumed, downloading it again");
+                }
+                let state = PartState {
+                    url: url.to_owned(),
+                    validator: response.validator().map(str::to_owned),
+                };
+                std::fs::write(state_path, serde_json::to_vec(&state)?)?;
+                (File::create(part_path).await?, 0)
+            }
+            status => return Err(StatusError(status).into()),
+        };
+        // 优先使用存储对象记录的大小，其次是响应的 Content-Length。
+        let total = request.size.or_else(|| {
+            response
+                .content_length()
+                .map(|content_length| resumed + content_length)
+        });
+        let mut progress = DownloadProgress::new(url, resumed, total);
+        while let Some(chunk) = response.chunk().await? {
+            deadline::check()?;
+            file.write_all(&chunk).await?;
+            progress.advance(chunk.len());
+        }
+        progress.advance(0);
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// URL 路径的最后一段，用作缓存中的文件名，取不到时为 `archive`。
+pub(crate) fn url_file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match path.rsplit('/').next() {
+        Some(name) if !matches!(name, "" | "." | "..") && !name.contains(':') => name,
+        _ => "archive",
+    }
+}
+
+/// 计算文件的大小和摘要并按请求校验，返回大小、SHA-256 和不一致的原因。
+async fn inspect(request: &DownloadRequest, path: &Path) -> Result<(u64, String, Option<String>)> {
+    let (request, path) = (request.clone(), path.to_owned());
+    blocking(move || {
+        let size = std::fs::metadata(&path)?.len();
+        let sha256 = sha256_file(&path)?;
+        let mismatch = verify(&request, &path, size, &sha256)?;
+        Ok((size, sha256, mismatch))
+    })
+    .await
+}
+
+/// 按请求中已知的大小、摘要和校验和检查文件，不一致时返回原因。
+fn verify(
+    request: &DownloadRequest,
+    path: &Path,
+    size: u64,
+    sha256: &str,
+) -> Result<Option<String>> {
+    Ok(match (request.size, &request.sha256, &request.checksum) {
+        (Some(expected), _, _) if expected != size => {
+            Some(format!("expected {expected} bytes, got {size}"))
+        }
+        (_, Some(expected), _) if !expected.eq_ignore_ascii_case(sha256) => {
+            Some(format!("expected sha256 {expected}, got {sha256}"))
+        }
+        (_, _, Some(checksum)) if verify_enabled() => checksum.mismatch(path)?,
+        _ => None,
+    })
+}
+
+impl Drop for DownloadManager {
+    fn drop(&mut self) {
+        for entry in self.entries.get_mut().unwrap().values() {
+            // 缓存目录中的压缩包保留。管理器释放时没有进行中的下载，总能拿到锁。
+            if let Some(downloaded) = entry.try_lock().ok().and_then(|entry| entry.clone()) {
+                if downloaded.path.starts_with(&self.dir) {
+                    let _ = std::fs::remove_file(&downloaded.path);
+                }
+            }
+        }
+        // 单独的子目录不会再被续传，连同未完成的 `.part` 文件一起删除。
+        if self.scratch {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
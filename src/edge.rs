@@ -0,0 +1,308 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+
+use crate::{
+    common::{BrowserReleaseItem, BrowserReleases, DownloadPlan, ReleaseChannel, ReleaseMatches},
+    downloads::{url_file_name, DownloadRequest, Downloads},
+    error::FetchError,
+    extract::extract_archive,
+    http::Http,
+    lock::with_install_lock,
+    metadata::{mark_complete, mark_incomplete, InstallMetadata},
+    paths::Paths,
+    platform::{Arch, Os, Platform},
+    timings::{self, Phase},
+    utils::{blocking, move_dir},
+    version::VersionSpec,
+};
+
+/// Edge 各渠道当前发布的版本及安装包，只包含每个渠道最近的几个版本。
+const EDGE_PRODUCTS_URL: &str = "https://edgeupdates.microsoft.com/api/products";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EdgeProduct {
+    /// `Stable`、`Beta`、`Dev`、`Canary`，另有 `Policy`、`EdgeUpdate` 等非浏览器的条目。
+    product: String,
+    releases: Vec<EdgeRelease>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EdgeRelease {
+    platform: String,
+    architecture: String,
+    product_version: String,
+    #[serde(default)]
+    artifacts: Vec<EdgeArtifact>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct EdgeArtifact {
+    artifact_name: String,
+    location: String,
+    #[serde(default)]
+    hash: Option<String>,
+    #[serde(default)]
+    hash_algorithm: Option<String>,
+    #[serde(default)]
+    size_in_bytes: Option<u64>,
+}
+
+/// API 中的平台、架构名称及使用的安装包：Windows 为企业版 msi，Linux 为 deb，macOS 为 pkg。
+fn edge_platform(platform: Platform) -> Option<(&'static str, &'static str, &'static str)> {
+    match (platform.os(), platform.arch()) {
+        (Os::Windows, Arch::X86) => Some(("Windows", "x86", "msi")),
+        (Os::Windows, Arch::X86_64) => Some(("Windows", "x64", "msi")),
+        (Os::Windows, Arch::Arm64) => Some(("Windows", "arm64", "msi")),
+        (Os::Linux, Arch::X86_64) => Some(("Linux", "x64", "deb")),
+        (Os::Mac, _) => Some(("MacOS", "universal", "pkg")),
+        _ => None,
+    }
+}
+
+pub(crate) struct EdgeReleases {
+    platform: Platform,
+    /// 渠道、版本及安装包。
+    releases: Vec<(String, String, EdgeArtifact)>,
+    downloads: Downloads,
+    paths: Paths,
+}
+
+impl EdgeReleases {
+    pub(crate) async fn init(
+        platform: Platform,
+        channel: ReleaseChannel,
+        client: Http,
+        downloads: Downloads,
+        paths: Paths,
+    ) -> Result<Self> {
+        if channel == ReleaseChannel::Esr {
+            return Err(anyhow!("The esr channel is only published for Firefox."));
+        }
+        if channel == ReleaseChannel::Extended {
+            return Err(anyhow!(
+                "The extended channel is only published for Chromium."
+            ));
+        }
+        let (edge_os, edge_arch, artifact_name) = edge_platform(platform).ok_or_else(|| {
+            FetchError::UnsupportedPlatform(format!(
+                "Edge is not available for {} {}.",
+                platform.os().as_str(),
+                platform.arch().as_str()
+            ))
+        })?;
+        status!("==> retrieving edge releases from {EDGE_PRODUCTS_URL} ...");
+        let products: Vec<EdgeProduct> = timings::time_async(Phase::History, async {
+            let response = client.get(EDGE_PRODUCTS_URL).await?;
+            if !response.is_success() {
+                return Err(FetchError::Network(format!(
+                    "Retrieving {EDGE_PRODUCTS_URL} failed: {}",
+                    response.status()
+                ))
+                .into());
+            }
+            response.json().await
+        })
+        .await?;
+        let channels: Vec<_> = channel
+            .concrete()
+            .iter()
+            .map(ReleaseChannel::as_constant)
+            .collect();
+        let releases = products
+            .into_iter()
+            .filter(|product| channels.contains(&product.product.as_str()))
+            .flat_map(|product| {
+                product.releases.into_iter().filter_map(move |release| {
+                    if release.platform != edge_os || release.architecture != edge_arch {
+                        return None;
+                    }
+                    let artifact = release
+                        .artifacts
+                        .into_iter()
+                        .find(|artifact| artifact.artifact_name == artifact_name)?;
+                    Some((product.product.clone(), release.product_version, artifact))
+                })
+            })
+            .collect();
+        Ok(Self {
+            platform,
+            releases,
+            downloads,
+            paths,
+        })
+    }
+}
+
+impl BrowserReleases for EdgeReleases {
+    fn match_version<'r>(&'r self, version: &str) -> ReleaseMatches<'r> {
+        let spec = VersionSpec::parse(version);
+        let matches = self
+            .releases
+            .iter()
+            .filter(move |(_, version, _)| spec.matches(version))
+            .map(|(channel, version, artifact)| {
+                let item: Box<dyn BrowserReleaseItem> = Box::new(EdgeReleaseItem {
+                    version: version.clone(),
+                    channel: channel.clone(),
+                    platform: self.platform,
+                    artifact: artifact.clone(),
+                    downloads: self.downloads.clone(),
+                    paths: self.paths.clone(),
+                });
+                Ok(item)
+            });
+        stream::iter(matches).boxed()
+    }
+}
+
+pub(crate) struct EdgeReleaseItem {
+    version: String,
+    channel: String,
+    platform: Platform,
+    artifact: EdgeArtifact,
+    downloads: Downloads,
+    paths: Paths,
+}
+
+#[async_trait]
+impl BrowserReleaseItem for EdgeReleaseItem {
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    async fn download(&self) -> Result<PathBuf> {
+        with_install_lock(&self.paths, "edge", &self.version, self.install()).await
+    }
+
+    async fn prefetch_requests(&self) -> Result<Vec<DownloadRequest>> {
+        Ok(vec![self.request()])
+    }
+
+    async fn plan(&self) -> Result<DownloadPlan> {
+        Ok(DownloadPlan {
+            requests: vec![self.request()],
+            revision: None,
+            install_dir: self.paths.install_dir("edge", &self.version),
+        })
+    }
+}
+
+impl EdgeReleaseItem {
+    fn request(&self) -> DownloadRequest {
+        let url = &self.artifact.location;
+        let mut request = DownloadRequest::new(url).with_cache_key(
+            "edge",
+            &self.version,
+            &format!(
+                "{}-{}",
+                self.platform.os().as_str(),
+                self.platform.arch().as_str()
+            ),
+            url_file_name(url),
+        );
+        request.size = self.artifact.size_in_bytes;
+        if self.artifact.hash_algorithm.as_deref() == Some("SHA256") {
+            request.sha256 = self.artifact.hash.as_deref().map(str::to_ascii_lowercase);
+        }
+        request
+    }
+
+    async fn install(&self) -> Result<PathBuf> {
+        status!(
+            "==> matched edge {} from channel {}",
+            self.version,
+            self.channel
+        );
+        let content = self.downloads.fetch(&self.request()).await?.bytes()?;
+
+        let temp_path = self.paths.temp_install_dir("edge", &self.version);
+        if temp_path.exists() {
+            std::fs::remove_dir_all(&temp_path)?;
+        }
+        std::fs::create_dir_all(&temp_path)?;
+        {
+            let temp_path = temp_path.clone();
+            blocking(move || {
+                timings::time(Phase::Extract, || extract_archive(content, &temp_path))
+            })
+            .await?;
+        }
+
+        // 安装包中浏览器位于较深的目录下（如 deb 的 opt/microsoft/msedge），以其所在目录作为安装目录。
+        let (content_path, executable) = self.find_executable(&temp_path).ok_or_else(|| {
+            anyhow!(
+                "No Edge executable found in {}",
+                url_file_name(&self.artifact.location)
+            )
+        })?;
+        let metadata = InstallMetadata {
+            platform: edge_platform(self.platform).map(|(os, _, _)| os.to_owned()),
+            arch: Some(self.platform.arch().as_str().to_owned()),
+            channel: Some(self.channel.clone()),
+            executable: Some(executable),
+            ..InstallMetadata::new("edge", &self.version)
+        };
+        mark_incomplete(&content_path)?;
+        metadata.write(&content_path)?;
+
+        let base_path = self.paths.install_dir("edge", &self.version);
+        if base_path.exists() {
+            std::fs::remove_dir_all(&base_path)?;
+        }
+        std::fs::create_dir_all(&self.paths.output_dir)?;
+        move_dir(&content_path, &base_path)?;
+        mark_complete(&base_path)?;
+        if temp_path.exists() {
+            std::fs::remove_dir_all(&temp_path)?;
+        }
+        Ok(base_path)
+    }
+
+    /// 返回浏览器所在的目录及可执行文件相对该目录的路径。
+    fn find_executable(&self, dir: &Path) -> Option<(PathBuf, String)> {
+        match self.platform.os() {
+            Os::Windows => find_entry(dir, false, &|name| name == "msedge.exe")
+                .map(|path| (path.parent().unwrap().to_owned(), "msedge.exe".to_owned())),
+            // Beta、Dev 渠道的可执行文件名为 `msedge-beta` 等，同目录下的 `msedge` 为实际的程序。
+            Os::Linux => find_entry(dir, false, &|name| name == "msedge")
+                .map(|path| (path.parent().unwrap().to_owned(), "msedge".to_owned())),
+            Os::Mac => {
+                let app = find_entry(dir, true, &|name| {
+                    name.starts_with("Microsoft Edge") && name.ends_with(".app")
+                })?;
+                let name = app.file_name()?.to_str()?.to_owned();
+                let executable = format!("{name}/Contents/MacOS/{}", name.trim_end_matches(".app"));
+                Some((app.parent()?.to_owned(), executable))
+            }
+        }
+    }
+}
+
+/// 按广度优先查找名称满足 `matches` 的文件（`want_dir` 时为目录）。
+fn find_entry(dir: &Path, want_dir: bool, matches: &dyn Fn(&str) -> bool) -> Option<PathBuf> {
+    let mut queue = vec![dir.to_owned()];
+    while !queue.is_empty() {
+        let mut next = Vec::new();
+        for dir in queue {
+            for entry in std::fs::read_dir(&dir).ok()?.flatten() {
+                let path = entry.path();
+                let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+                if is_dir == want_dir && entry.file_name().to_str().is_some_and(matches) {
+                    return Some(path);
+                }
+                if is_dir {
+                    next.push(path);
+                }
+            }
+        }
+        queue = next;
+    }
+    None
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+
+use crate::downloads::StatusError;
+
+/// 库返回的 `anyhow::Error` 中可以 downcast 出的错误类别，便于调用方区分处理。
+///
+/// 其他错误（网络、I/O 等）保留原始的错误链，可以 downcast 为 `reqwest::Error`、`std::io::Error`。
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FetchError {
+    /// 没有与请求的版本匹配的发布。
+    VersionNotFound(String),
+    /// 不支持的平台，或该浏览器没有发布该平台的构建。
+    UnsupportedPlatform(String),
+    /// 下载的文件与上游发布的摘要不一致，可以用 `--no-verify` 跳过校验。
+    ChecksumMismatch(String),
+    /// 服务器返回了错误的状态码。连接失败等保留为 `reqwest::Error`，同样归为网络错误。
+    Network(String),
+    /// 压缩包无法识别或解压失败，原始错误保留在错误链中。
+    Extraction(String),
+}
+
+/// 命令行根据错误链归类，决定退出码和给出的提示。
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum ErrorCategory {
+    Network,
+    VersionNotFound,
+    UnsupportedPlatform,
+    Extraction,
+    Io,
+    ChecksumMismatch,
+    Other,
+}
+
+impl ErrorCategory {
+    /// 从外到内查找错误链中第一个能归类的错误，外层的类别更具体（例如解压时的 I/O 错误归为解压失败）。
+    pub(crate) fn of(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|cause| {
+                if let Some(err) = cause.downcast_ref::<FetchError>() {
+                    return Some(match err {
+                        FetchError::VersionNotFound(_) => Self::VersionNotFound,
+                        FetchError::UnsupportedPlatform(_) => Self::UnsupportedPlatform,
+                        FetchError::ChecksumMismatch(_) => Self::ChecksumMismatch,
+                        FetchError::Network(_) => Self::Network,
+                        FetchError::Extraction(_) => Self::Extraction,
+                    });
+                }
+                if cause.is::<reqwest::Error>() || cause.is::<StatusError>() {
+                    return Some(Self::Network);
+                }
+                cause.is::<std::io::Error>().then_some(Self::Io)
+            })
+            .unwrap_or(Self::Other)
+    }
+
+    /// 进程的退出码：`1` 为其他错误，`2` 为命令行参数错误（clap），`124` 为超过 `--deadline`。
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::Network => 3,
+            Self::VersionNotFound => 4,
+            Self::UnsupportedPlatform => 5,
+            Self::Extraction => 6,
+            Self::Io => 7,
+            Self::ChecksumMismatch => 8,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Network => "network",
+            Self::VersionNotFound => "version-not-found",
+            Self::UnsupportedPlatform => "unsupported-platform",
+            Self::Extraction => "extraction",
+            Self::Io => "io",
+            Self::ChecksumMismatch => "checksum-mismatch",
+            Self::Other => "other",
+        }
+    }
+
+    pub(crate) fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Network => Some("check the network connection, or route requests through --proxy, --pac-url or --system-proxy."),
+            Self::VersionNotFound => Some("run with --list-matches to see the matching versions, or search every channel with --channel any."),
+            Self::UnsupportedPlatform => Some("--os accepts windows, linux and macos, --arch accepts x86, x64 and arm64."),
+            Self::Extraction => Some("the archive may be damaged; download it again with --refresh."),
+            Self::Io => Some("check the free disk space and the permissions of the output, temp and cache directories."),
+            Self::ChecksumMismatch => Some("the download may be damaged; download it again with --refresh, or skip verification with --no-verify."),
+            Self::Other => None,
+        }
+    }
+}
+
+impl FetchError {
+    /// 摘要不一致说明文件已损坏或被篡改，不应再尝试其他语言或架构。
+    pub(crate) fn is_checksum_mismatch(err: &anyhow::Error) -> bool {
+        matches!(
+            err.downcast_ref::<FetchError>(),
+            Some(FetchError::ChecksumMismatch(_))
+        )
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::VersionNotFound(message)
+            | FetchError::UnsupportedPlatform(message)
+            | FetchError::ChecksumMismatch(message)
+            | FetchError::Network(message)
+            | FetchError::Extraction(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
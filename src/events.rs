@@ -0,0 +1,113 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{metadata::InstallMetadata, paths::Paths, utils::format_date};
+
+const EVENTS_FILE: &str = "events.jsonl";
+
+/// 安装日志中的一条记录，便于共享机器上追溯谁在何时安装了哪个浏览器。
+#[derive(Debug, Serialize, Deserialize)]
+struct InstallEvent {
+    timestamp: u64,
+    action: String,
+    browser: String,
+    version: String,
+    path: String,
+    user: String,
+    hostname: String,
+}
+
+/// 追加一条记录，写入失败只给出警告，不影响安装结果。
+pub(crate) fn record(paths: &Paths, action: &str, install_dir: &Path) {
+    if let Err(err) = try_record(paths, action, install_dir) {
+        status!("==> warning: recording the {action} event failed: {err}");
+    }
+}
+
+fn try_record(paths: &Paths, action: &str, install_dir: &Path) -> Result<()> {
+    let metadata = InstallMetadata::read(install_dir).unwrap_or_default();
+    let event = InstallEvent {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        action: action.to_owned(),
+        browser: metadata.browser,
+        version: metadata.version,
+        path: install_dir.display().to_string(),
+        user: std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_default(),
+        hostname: hostname(),
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(paths.cached_file(EVENTS_FILE)?)?;
+    writeln!(file, "{}", serde_json::to_string(&event)?)?;
+    Ok(())
+}
+
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .or_else(|_| std::fs::read_to_string("/etc/hostname").map(|name| name.trim().to_owned()))
+        .unwrap_or_default()
+}
+
+/// 按时间顺序打印最近的 `limit` 条记录。
+pub(crate) fn print_history(paths: &Paths, limit: Option<usize>) -> Result<()> {
+    let path = paths.cache_dir.join(EVENTS_FILE);
+    if !path.exists() {
+        status!("==> no install events recorded yet.");
+        return Ok(());
+    }
+    let mut events = Vec::new();
+    for line in BufReader::new(File::open(&path)?).lines() {
+        let line = line?;
+        match serde_json::from_str::<InstallEvent>(&line) {
+            Ok(event) => events.push(event),
+            Err(err) => status!("==> warning: skipping malformed event: {err}"),
+        }
+    }
+    let skip = limit.map_or(0, |limit| events.len().saturating_sub(limit));
+    status!("==> install events ({}):", path.display());
+    for event in &events[skip..] {
+        let secs = event.timestamp % 86400;
+        println!(
+            "    {} {:02}:{:02}:{:02} UTC  {:<14} {} {}  {}@{}  {}",
+            format_date(event.timestamp * 1000),
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            event.action,
+            event.browser,
+            event.version,
+            event.user,
+            event.hostname,
+            event.path
+        );
+    }
+    Ok(())
+}
+
+/// 日志中记录过的安装目录，按首次出现的顺序去重，可能已被删除。
+pub(crate) fn recorded_installs(paths: &Paths) -> Vec<PathBuf> {
+    let Ok(file) = File::open(paths.cache_dir.join(EVENTS_FILE)) else {
+        return Vec::new();
+    };
+    let mut installs = Vec::new();
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        if let Ok(event) = serde_json::from_str::<InstallEvent>(&line) {
+            let path = PathBuf::from(event.path);
+            if !installs.contains(&path) {
+                installs.push(path);
+            }
+        }
+    }
+    installs
+}
@@ -0,0 +1,423 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{Cursor, ErrorKind, Write},
+    path::{Component, Path},
+};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use compress_tools::{ArchiveContents, ArchiveIterator};
+
+use crate::{deadline, error::FetchError, progress::ExtractProgress, utils::retry_locked};
+
+use self::libarchive::{ArchiveReader, EntryHeader, EntryKind};
+
+mod libarchive;
+
+/// 根据文件头（dmg 为文件尾）识别出的压缩包格式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ArchiveFormat {
+    Zip,
+    SevenZip,
+    TarGz,
+    TarBz2,
+    TarXz,
+    Dmg,
+    Msi,
+    Deb,
+    Pkg,
+}
+
+impl ArchiveFormat {
+    pub(crate) fn detect(content: &[u8]) -> Option<Self> {
+        const MAGIC: &[(&[u8], ArchiveFormat)] = &[
+            (b"PK\x03\x04", ArchiveFormat::Zip),
+            (b"PK\x05\x06", ArchiveFormat::Zip),
+            (b"7z\xbc\xaf\x27\x1c", ArchiveFormat::SevenZip),
+            (b"\x1f\x8b", ArchiveFormat::TarGz),
+            (b"BZh", ArchiveFormat::TarBz2),
+            (b"\xfd7zXZ\x00", ArchiveFormat::TarXz),
+            (b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1", ArchiveFormat::Msi),
+            (b"!<arch>\n", ArchiveFormat::Deb),
+            (b"xar!", ArchiveFormat::Pkg),
+        ];
+        if let Some((_, format)) = MAGIC.iter().find(|(magic, _)| content.starts_with(magic)) {
+            return Some(*format);
+        }
+        // dmg 的 koly 块位于文件末尾 512 字节处。
+        let trailer = content
+            .len()
+            .checked_sub(512)
+            .map(|start| &content[start..]);
+        trailer
+            .filter(|trailer| trailer.starts_with(b"koly"))
+            .map(|_| ArchiveFormat::Dmg)
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::SevenZip => "7z",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarBz2 => "tar.bz2",
+            ArchiveFormat::TarXz => "tar.xz",
+            ArchiveFormat::Dmg => "dmg",
+            ArchiveFormat::Msi => "msi",
+            ArchiveFormat::Deb => "deb",
+            ArchiveFormat::Pkg => "pkg",
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// 识别压缩包格式并解压到 dest，返回识别出的格式。
+pub(crate) fn extract_archive(content: Bytes, dest: &Path) -> Result<ArchiveFormat> {
+    let format = ArchiveFormat::detect(&content)
+        .ok_or_else(|| FetchError::Extraction("Unrecognized archive format.".to_owned()))?;
+    extract_archive_as(content, dest, format)?;
+    Ok(format)
+}
+
+/// 按指定的格式解压到 dest，不再识别文件头。
+pub(crate) fn extract_archive_as(content: Bytes, dest: &Path, format: ArchiveFormat) -> Result<()> {
+    let result = match format {
+        ArchiveFormat::Dmg => extract_dmg(&content, dest),
+        ArchiveFormat::Msi => extract_msi(&content, dest),
+        ArchiveFormat::Deb => extract_deb(content, dest),
+        ArchiveFormat::Pkg => extract_pkg(&content, dest),
+        _ => extract_with_libarchive(content, dest),
+    };
+    result.map_err(|err| {
+        err.context(FetchError::Extraction(format!(
+            "Extracting the {} archive failed.",
+            format.extension()
+        )))
+    })
+}
+
+fn extract_with_libarchive(content: Bytes, dest: &Path) -> Result<()> {
+    // 总数要多解压一遍才能拿到，这里边解压边计数。
+    let mut progress = ExtractProgress::new(None);
+    let mut reader = ArchiveReader::new(&content)?;
+    let mut buf = vec![0; 64 * 1024];
+    while let Some(EntryHeader { name, kind, mode }) = reader.next_header()? {
+        deadline::check()?;
+        let relative = checked_relative(&name)?;
+        check_parents(dest, relative)?;
+        let path = dest.join(relative);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut written = 0;
+        match kind {
+            EntryKind::Directory => std::fs::create_dir_all(&path)?,
+            EntryKind::File => {
+                let mut file = retry_locked(|| File::create(&path))
+                    .map_err(|err| anyhow!("解压文件 {} 时出错：{:?}", path.display(), err))?;
+                loop {
+                    let read = reader.read_data(&mut buf)?;
+                    if read == 0 {
+                        break;
+                    }
+                    file.write_all(&buf[..read])?;
+                    written += read as u64;
+                }
+                drop(file);
+                set_mode(&path, mode)?;
+            }
+            EntryKind::Symlink(target) => {
+                let parent = relative.parent().unwrap_or(Path::new(""));
+                if !stays_inside(parent, Path::new(&target)) {
+                    return Err(anyhow!(
+                        "Symlink in archive escapes the destination: {name} -> {target}"
+                    ));
+                }
+                create_symlink(&target, &path)?;
+            }
+            EntryKind::Hardlink(target) => {
+                let target = checked_relative(&target)?;
+                check_parents(dest, target)?;
+                let original = dest.join(target);
+                std::fs::hard_link(&original, &path)?;
+            }
+        }
+        progress.file(&name, written);
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// 条目路径只能由普通路径段组成，不能是绝对路径或包含 `..`。
+fn checked_relative(name: &str) -> Result<&Path> {
+    let relative = Path::new(name);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(anyhow!("Invalid path in archive: {name}"));
+    }
+    Ok(relative)
+}
+
+/// `relative` 的各级上级目录中已经存在的不能是符号链接。
+///
+/// [`stays_inside`] 只按字面检查链接目标，先解压的链接可以让后面的链接或文件经由它离开解压目录，
+/// 例如 `x/y -> ..`、`x/y/w -> ..` 之后的 `x/y/w/evil`。
+pub(crate) fn check_parents(dest: &Path, relative: &Path) -> Result<()> {
+    let mut parent = dest.to_owned();
+    let Some(dir) = relative.parent() else {
+        return Ok(());
+    };
+    for component in dir.components() {
+        parent.push(component);
+        match std::fs::symlink_metadata(&parent) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(anyhow!(
+                    "Archive entry {} is placed under a symlink",
+                    relative.display()
+                ))
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// 从 base（相对解压目录）出发按 target 逐段走，判断是否始终不离开解压目录。
+pub(crate) fn stays_inside(base: &Path, target: &Path) -> bool {
+    let mut depth = base.components().count();
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(parent) => depth = parent,
+                None => return false,
+            },
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+#[cfg(unix)]
+pub(crate) fn create_symlink(target: &str, path: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, path)
+        .map_err(|err| anyhow!("创建符号链接 {} 时出错：{:?}", path.display(), err))
+}
+
+/// Windows 上创建符号链接需要额外权限，按目标类型分别创建，失败时直接报错。
+#[cfg(windows)]
+pub(crate) fn create_symlink(target: &str, path: &Path) -> Result<()> {
+    let resolved = path.parent().unwrap_or(Path::new("")).join(target);
+    let result = if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, path)
+    } else {
+        std::os::windows::fs::symlink_file(target, path)
+    };
+    result.map_err(|err| anyhow!("创建符号链接 {} 时出错：{:?}", path.display(), err))
+}
+
+/// deb 是 ar 包，安装的文件在其中的 `data.tar.*` 中，取出后再解压一次。
+fn extract_deb(content: Bytes, dest: &Path) -> Result<()> {
+    let mut data = None;
+    let mut in_data = false;
+    for item in ArchiveIterator::from_read(Cursor::new(content))? {
+        match item {
+            ArchiveContents::StartOfEntry(name, _) => {
+                in_data = name.starts_with("data.tar");
+                if in_data {
+                    data = Some(Vec::new());
+                }
+            }
+            ArchiveContents::DataChunk(chunk) if in_data => {
+                data.get_or_insert_with(Vec::new).extend_from_slice(&chunk)
+            }
+            ArchiveContents::Err(err) => return Err(err.into()),
+            _ => {}
+        }
+    }
+    let data = data.ok_or_else(|| anyhow!("No data.tar found in the deb package."))?;
+    extract_with_libarchive(Bytes::from(data), dest)
+}
+
+/// 写入临时文件后交给系统工具处理，dmg 和 msi 没有可以直接流式解压的实现。
+#[cfg(any(windows, target_os = "macos"))]
+fn with_temp_file<T>(content: &[u8], ext: &str, f: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // 同一进程内可能并发解压多个安装包，文件名加上序号避免互相覆盖。
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "fetchbrowser-{}-{}.{ext}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)?
+        .write_all(content)?;
+    let result = f(&path);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+#[cfg(target_os = "macos")]
+fn extract_dmg(content: &[u8], dest: &Path) -> Result<()> {
+    use std::process::Command;
+
+    with_temp_file(content, "dmg", |image| {
+        let mount_point = dest.with_extension("mount");
+        std::fs::create_dir_all(&mount_point)?;
+        let status = Command::new("hdiutil")
+            .args(["attach", "-nobrowse", "-readonly", "-mountpoint"])
+            .arg(&mount_point)
+            .arg(image)
+            .status()?;
+        if !status.success() {
+            return Err(anyhow!("hdiutil attach failed: {status}"));
+        }
+        let result = crate::utils::copy_dir(&mount_point, dest);
+        let _ = Command::new("hdiutil")
+            .arg("detach")
+            .arg(&mount_point)
+            .status();
+        let _ = std::fs::remove_dir(&mount_point);
+        result
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+fn extract_dmg(_content: &[u8], _dest: &Path) -> Result<()> {
+    Err(anyhow!("Extracting dmg images is only supported on macOS."))
+}
+
+/// pkg 是 xar 包，其中的 Payload 为压缩的 cpio，交给 `pkgutil --expand-full` 全部展开。
+#[cfg(target_os = "macos")]
+fn extract_pkg(content: &[u8], dest: &Path) -> Result<()> {
+    with_temp_file(content, "pkg", |package| {
+        // pkgutil 要求目标目录不存在。
+        let status = std::process::Command::new("pkgutil")
+            .arg("--expand-full")
+            .arg(package)
+            .arg(dest.join("pkg"))
+            .status()?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(anyhow!("pkgutil --expand-full failed: {status}")),
+        }
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+fn extract_pkg(_content: &[u8], _dest: &Path) -> Result<()> {
+    Err(anyhow!(
+        "Extracting pkg installers is only supported on macOS."
+    ))
+}
+
+#[cfg(windows)]
+fn extract_msi(content: &[u8], dest: &Path) -> Result<()> {
+    with_temp_file(content, "msi", |package| {
+        // 管理安装（/a）只解出文件，不会真正安装到系统。
+        let status = std::process::Command::new("msiexec")
+            .arg("/a")
+            .arg(package)
+            .arg("/qn")
+            .arg(format!("TARGETDIR={}", dest.display()))
+            .status()?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(anyhow!("msiexec failed: {status}")),
+        }
+    })
+}
+
+#[cfg(not(windows))]
+fn extract_msi(_content: &[u8], _dest: &Path) -> Result<()> {
+    Err(anyhow!(
+        "Extracting msi packages is only supported on Windows."
+    ))
+}
+
+/// 按压缩包中记录的权限设置文件权限，主要是保留可执行位。
+#[cfg(unix)]
+pub(crate) fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if mode & 0o7777 != 0 {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::{write::FileOptions, ZipWriter};
+
+    use super::*;
+
+    fn zip_with(
+        build: impl FnOnce(&mut ZipWriter<Cursor<Vec<u8>>>) -> zip::result::ZipResult<()>,
+    ) -> Bytes {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        build(&mut zip).unwrap();
+        Bytes::from(zip.finish().unwrap().into_inner())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chained_symlinks_cannot_escape() {
+        let content = zip_with(|zip| {
+            zip.add_symlink("x/y", "..", FileOptions::default())?;
+            zip.add_symlink("x/y/w", "..", FileOptions::default())?;
+            zip.start_file("x/y/w/evil", FileOptions::default())?;
+            zip.write_all(b"evil")?;
+            Ok(())
+        });
+        let base =
+            std::env::temp_dir().join(format!("fetchbrowser-extract-{}", std::process::id()));
+        let dest = base.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        let result = extract_archive(content, &dest);
+        let escaped = base.join("evil").exists() || base.join("w").exists();
+        let _ = std::fs::remove_dir_all(&base);
+
+        assert!(result.is_err());
+        assert!(!escaped);
+    }
+
+    #[test]
+    fn files_under_real_directories_are_extracted() {
+        let content = zip_with(|zip| {
+            zip.add_directory("a/b", FileOptions::default())?;
+            zip.start_file("a/b/c.txt", FileOptions::default())?;
+            zip.write_all(b"c")?;
+            Ok(())
+        });
+        let dest =
+            std::env::temp_dir().join(format!("fetchbrowser-extract-ok-{}", std::process::id()));
+        let result = extract_archive(content, &dest);
+        let written = std::fs::read(dest.join("a/b/c.txt"));
+        let _ = std::fs::remove_dir_all(&dest);
+
+        result.unwrap();
+        assert_eq!(written.unwrap(), b"c");
+    }
+}
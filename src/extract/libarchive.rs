@@ -0,0 +1,155 @@
+//! libarchive 的最小绑定。
+//!
+//! compress-tools 的 `ArchiveIterator` 不提供符号链接和硬链接的目标，这里直接读取条目头，
+//! 链接库由 compress-tools 的构建脚本负责。
+
+use std::{
+    ffi::{c_char, c_int, c_void, CStr},
+    marker::PhantomData,
+    ptr,
+};
+
+use anyhow::{anyhow, Result};
+
+#[repr(C)]
+struct RawArchive {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+struct RawEntry {
+    _private: [u8; 0],
+}
+
+const ARCHIVE_EOF: c_int = 1;
+const ARCHIVE_OK: c_int = 0;
+const ARCHIVE_WARN: c_int = -20;
+
+extern "C" {
+    fn archive_read_new() -> *mut RawArchive;
+    fn archive_read_support_filter_all(archive: *mut RawArchive) -> c_int;
+    fn archive_read_support_format_all(archive: *mut RawArchive) -> c_int;
+    fn archive_read_open_memory(archive: *mut RawArchive, buf: *const c_void, size: usize)
+        -> c_int;
+    fn archive_read_next_header(archive: *mut RawArchive, entry: *mut *mut RawEntry) -> c_int;
+    fn archive_read_data(archive: *mut RawArchive, buf: *mut c_void, size: usize) -> isize;
+    fn archive_read_free(archive: *mut RawArchive) -> c_int;
+    fn archive_error_string(archive: *mut RawArchive) -> *const c_char;
+    fn archive_entry_pathname(entry: *mut RawEntry) -> *const c_char;
+    fn archive_entry_symlink(entry: *mut RawEntry) -> *const c_char;
+    fn archive_entry_hardlink(entry: *mut RawEntry) -> *const c_char;
+    fn archive_entry_mode(entry: *mut RawEntry) -> libc::mode_t;
+}
+
+/// 条目的类型，链接类型带上目标路径（原样取自压缩包，未做校验）。
+#[derive(Debug)]
+pub(crate) enum EntryKind {
+    Directory,
+    File,
+    Symlink(String),
+    Hardlink(String),
+}
+
+#[derive(Debug)]
+pub(crate) struct EntryHeader {
+    pub(crate) name: String,
+    pub(crate) kind: EntryKind,
+    pub(crate) mode: u32,
+}
+
+/// 从内存中顺序读取压缩包，只解压一遍。
+pub(crate) struct ArchiveReader<'a> {
+    archive: *mut RawArchive,
+    _content: PhantomData<&'a [u8]>,
+}
+
+impl<'a> ArchiveReader<'a> {
+    pub(crate) fn new(content: &'a [u8]) -> Result<Self> {
+        // SAFETY: archive 创建后立即交给 Self，Drop 时释放；content 的生命周期由 'a 约束。
+        unsafe {
+            let archive = archive_read_new();
+            if archive.is_null() {
+                return Err(anyhow!("Allocating the libarchive reader failed."));
+            }
+            let reader = Self {
+                archive,
+                _content: PhantomData,
+            };
+            reader.check(archive_read_support_filter_all(archive))?;
+            reader.check(archive_read_support_format_all(archive))?;
+            reader.check(archive_read_open_memory(
+                archive,
+                content.as_ptr().cast(),
+                content.len(),
+            ))?;
+            Ok(reader)
+        }
+    }
+
+    /// 读取下一个条目头，读完时返回 None。
+    pub(crate) fn next_header(&mut self) -> Result<Option<EntryHeader>> {
+        let mut entry = ptr::null_mut();
+        // SAFETY: entry 指向的内存归 archive 所有，在下一次 next_header 之前有效，这里只拷贝其中的字符串。
+        unsafe {
+            match archive_read_next_header(self.archive, &mut entry) {
+                ARCHIVE_EOF => return Ok(None),
+                code => self.check(code)?,
+            }
+            let name = to_string(archive_entry_pathname(entry))
+                .ok_or_else(|| anyhow!("Archive entry without a path."))?;
+            #[allow(clippy::unnecessary_cast)]
+            let mode = archive_entry_mode(entry) as u32;
+            let kind = if let Some(target) = to_string(archive_entry_hardlink(entry)) {
+                EntryKind::Hardlink(target)
+            } else if mode & 0o170000 == 0o120000 {
+                let target = to_string(archive_entry_symlink(entry))
+                    .ok_or_else(|| anyhow!("Symlink without a target in archive: {name}"))?;
+                EntryKind::Symlink(target)
+            } else if name.ends_with('/') || mode & 0o170000 == 0o040000 {
+                EntryKind::Directory
+            } else {
+                EntryKind::File
+            };
+            Ok(Some(EntryHeader { name, kind, mode }))
+        }
+    }
+
+    /// 读取当前条目的数据，返回 0 表示当前条目已读完。
+    pub(crate) fn read_data(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // SAFETY: buf 在调用期间有效且可写。
+        let read = unsafe { archive_read_data(self.archive, buf.as_mut_ptr().cast(), buf.len()) };
+        usize::try_from(read).map_err(|_| self.error())
+    }
+
+    fn check(&self, code: c_int) -> Result<()> {
+        match code {
+            ARCHIVE_OK | ARCHIVE_WARN => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn error(&self) -> anyhow::Error {
+        // SAFETY: archive 在 Self 存活期间有效。
+        let message = unsafe { to_string(archive_error_string(self.archive)) };
+        anyhow!(
+            "libarchive: {}",
+            message.as_deref().unwrap_or("unknown error")
+        )
+    }
+}
+
+impl Drop for ArchiveReader<'_> {
+    fn drop(&mut self) {
+        // SAFETY: archive 只在这里释放一次。
+        unsafe {
+            archive_read_free(self.archive);
+        }
+    }
+}
+
+/// # Safety
+///
+/// ptr 为空或指向以 NUL 结尾的字符串。
+unsafe fn to_string(ptr: *const c_char) -> Option<String> {
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_string_lossy().into_owned())
+}
@@ -0,0 +1,119 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    downloads::{DownloadManager, DownloadRequest},
+    extract::extract_archive,
+    paths::Paths,
+    platform::{Arch, Os, Platform},
+    timings::{self, Phase},
+    utils::blocking,
+};
+
+const GECKODRIVER_RELEASES_URL: &str = "https://github.com/mozilla/geckodriver/releases/download/";
+
+/// geckodriver 各版本支持的最低 Firefox 主版本号，按版本从新到旧排列。
+///
+/// 来自 geckodriver 文档的 “Supported platforms” 一节，发布新版本时需要补充。
+const GECKODRIVER_SUPPORT: [(&str, usize); 14] = [
+    ("0.36.0", 128),
+    ("0.35.0", 115),
+    ("0.34.0", 115),
+    ("0.33.0", 102),
+    ("0.32.2", 102),
+    ("0.31.0", 91),
+    ("0.30.0", 78),
+    ("0.29.1", 60),
+    ("0.26.0", 60),
+    ("0.24.0", 57),
+    ("0.21.0", 57),
+    ("0.20.1", 55),
+    ("0.19.1", 53),
+    ("0.17.0", 52),
+];
+
+/// 支持 Firefox `version` 的最新 geckodriver 版本。
+fn compatible_version(version: &str) -> Result<&'static str> {
+    let major = version
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|major| major.parse::<usize>().ok())
+        .ok_or_else(|| anyhow!("Invalid firefox version {version}."))?;
+    GECKODRIVER_SUPPORT
+        .iter()
+        .find(|(_, min_firefox)| *min_firefox <= major)
+        .map(|(driver, _)| *driver)
+        .ok_or_else(|| anyhow!("No geckodriver supports firefox {version}."))
+}
+
+/// 发布资源中的平台名称，较早的版本没有 arm64 的构建，下载时返回 404。
+fn driver_platform(platform: Platform) -> (&'static str, &'static str) {
+    match (platform.os(), platform.arch()) {
+        (Os::Windows, Arch::X86) => ("win32", "zip"),
+        (Os::Windows, Arch::Arm64) => ("win-aarch64", "zip"),
+        (Os::Windows, _) => ("win64", "zip"),
+        (Os::Linux, Arch::X86) => ("linux32", "tar.gz"),
+        (Os::Linux, Arch::Arm64) => ("linux-aarch64", "tar.gz"),
+        (Os::Linux, _) => ("linux64", "tar.gz"),
+        (Os::Mac, Arch::Arm64) => ("macos-aarch64", "tar.gz"),
+        (Os::Mac, _) => ("macos", "tar.gz"),
+    }
+}
+
+fn driver_executable(os: Os) -> &'static str {
+    match os {
+        Os::Windows => "geckodriver.exe",
+        _ => "geckodriver",
+    }
+}
+
+/// 与 Firefox `version` 兼容的 geckodriver 版本及其下载请求。
+pub(super) fn geckodriver_request(
+    version: &str,
+    platform: Platform,
+) -> Result<(&'static str, DownloadRequest)> {
+    let driver_version = compatible_version(version)?;
+    let (driver_platform, extension) = driver_platform(platform);
+    let file = format!("geckodriver-v{driver_version}-{driver_platform}.{extension}");
+    let request = DownloadRequest::new(&format!(
+        "{GECKODRIVER_RELEASES_URL}v{driver_version}/{file}"
+    ))
+    .with_cache_key("geckodriver", driver_version, driver_platform, &file);
+    Ok((driver_version, request))
+}
+
+/// 下载与 Firefox `version` 兼容的 geckodriver，放入 Firefox 的安装目录 `ff_path`。
+pub(super) async fn install_geckodriver(
+    version: &str,
+    platform: Platform,
+    ff_path: &Path,
+    downloads: &DownloadManager,
+    paths: &Paths,
+) -> Result<PathBuf> {
+    let (driver_version, request) = geckodriver_request(version, platform)?;
+    status!("==> firefox {version} is supported by geckodriver {driver_version}");
+    let content = downloads.fetch(&request).await?.bytes()?;
+
+    let temp_path = paths.temp_install_dir("geckodriver", driver_version);
+    if temp_path.exists() {
+        std::fs::remove_dir_all(&temp_path)?;
+    }
+    std::fs::create_dir_all(&temp_path)?;
+    {
+        let temp_path = temp_path.clone();
+        blocking(move || timings::time(Phase::Extract, || extract_archive(content, &temp_path)))
+            .await?;
+    }
+    // 压缩包中只有 geckodriver 一个文件。
+    let executable = driver_executable(platform.os());
+    let driver_path = ff_path.join(executable);
+    std::fs::copy(temp_path.join(executable), &driver_path)
+        .map_err(|err| anyhow!("No {executable} found in {}: {err}", request.url))?;
+    std::fs::remove_dir_all(&temp_path)?;
+    status!(
+        "==> installed geckodriver {driver_version} into {}",
+        driver_path.display()
+    );
+    Ok(driver_path)
+}
@@ -1,40 +1,560 @@
-use std::{cmp::Ordering, env::current_dir, fs::create_dir_all, io::Cursor};
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::{anyhow, Result};
+use async_trait::async_trait;
 use bytes::Bytes;
-use compress_tools::{uncompress_archive, Ownership};
-use reqwest::blocking::Client;
+use futures::{stream, StreamExt};
+use regex::Regex;
 use select::{
     document::Document,
     predicate::{self, Predicate},
 };
+use tokio::sync::OnceCell;
 
-use crate::utils::{find_sequence, get_cached_file_path};
+use crate::{
+    archive::{keep_archive, kept_archive_name},
+    cache::CacheStatus,
+    checksums::{find_checksum, verify_enabled},
+    common::{
+        BrowserReleaseItem, BrowserReleases, DownloadOptions, DownloadPlan, ReleaseChannel,
+        ReleaseMatches,
+    },
+    downloads::{DownloadManager, DownloadRequest, Downloaded, Downloads},
+    error::FetchError,
+    extract::{extract_archive, ArchiveFormat},
+    http::Http,
+    lock::with_install_lock,
+    metadata::{mark_complete, mark_incomplete, InstallMetadata},
+    metrics,
+    paths::Paths,
+    platform::{Arch, Os, Platform},
+    session::Session,
+    timings::{self, Phase},
+    utils::{blocking, find_sequence, move_dir},
+    version::{BrowserVersion, VersionSpec},
+};
 
-pub(crate) fn download_firefox(version: &str, client: &Client) -> Result<()> {
-    let cur_dir = current_dir()?;
+use self::driver::{geckodriver_request, install_geckodriver};
 
-    let spider = FirefoxVersionSpider::init(client)?;
-    let matched_version_list = spider.find(version);
-    let matched_version = matched_version_list
-        .first()
-        .ok_or_else(|| anyhow!("No matched version found"))?;
+mod driver;
 
-    let zip_content = download_firefox_zip(matched_version, "win64", client).or_else(|err| {
-        println!("==> download firefox win64 failed: {err}, trying win32 ...");
-        download_firefox_zip(matched_version, "win32", client)
-    })?;
+/// ftp.mozilla.org 上发布的 Firefox 版本。
+pub struct FirefoxReleases {
+    spider: FirefoxVersionSpider,
+    channel: ReleaseChannel,
+    platform: Platform,
+    client: Http,
+    downloads: Downloads,
+    paths: Paths,
+    options: DownloadOptions,
+}
 
-    let base_path = cur_dir.join(format!(".tmp-firefox-{matched_version}"));
-    create_dir_all(&base_path)?;
+impl FirefoxReleases {
+    /// 获取 `channel` 的版本列表，已缓存时使用缓存。`options` 中只使用 `keep_archive`、`strict`、`with_driver`、`with_mar` 和 `firefox_lang`。
+    ///
+    /// `Any` 为 releases 目录下的所有版本（正式版、beta 和 esr），`Dev`、`Canary` 为 Developer Edition 和 Nightly。
+    pub async fn new(
+        session: &Session,
+        platform: Platform,
+        channel: ReleaseChannel,
+        options: DownloadOptions,
+    ) -> Result<Self> {
+        Self::init(
+            platform,
+            channel,
+            &session.client,
+            session.downloads.clone(),
+            options,
+            session.paths.clone(),
+        )
+        .await
+    }
+
+    pub(crate) async fn init(
+        platform: Platform,
+        channel: ReleaseChannel,
+        client: &Http,
+        downloads: Downloads,
+        options: DownloadOptions,
+        paths: Paths,
+    ) -> Result<Self> {
+        if channel == ReleaseChannel::Extended {
+            return Err(anyhow!(
+                "The extended channel is only published for Chromium, use esr for Firefox."
+            ));
+        }
+        let spider = timings::time_async(
+            Phase::History,
+            FirefoxVersionSpider::init(channel, client, &paths),
+        )
+        .await?;
+        Ok(Self {
+            spider,
+            channel,
+            platform,
+            client: client.clone(),
+            downloads,
+            paths,
+            options,
+        })
+    }
+}
+
+impl BrowserReleases for FirefoxReleases {
+    fn match_version<'r>(&'r self, version: &str) -> ReleaseMatches<'r> {
+        let matches = self.spider.find(version).into_iter().map(|version| {
+            let item: Box<dyn BrowserReleaseItem> = Box::new(FirefoxReleaseItem {
+                version: version.clone(),
+                channel: self.channel,
+                platform: self.platform,
+                client: self.client.clone(),
+                downloads: self.downloads.clone(),
+                paths: self.paths.clone(),
+                options: self.options.clone(),
+                locales: OnceCell::new(),
+            });
+            Ok(item)
+        });
+        stream::iter(matches).boxed()
+    }
+}
+
+struct FirefoxReleaseItem {
+    version: String,
+    channel: ReleaseChannel,
+    platform: Platform,
+    client: Http,
+    downloads: Downloads,
+    paths: Paths,
+    options: DownloadOptions,
+    /// 预先解析（`plan`）和安装时共用，避免重复获取语言列表。
+    locales: OnceCell<Vec<String>>,
+}
+
+#[async_trait]
+impl BrowserReleaseItem for FirefoxReleaseItem {
+    fn version(&self) -> &str {
+        &self.version
+    }
+
+    async fn download(&self) -> Result<PathBuf> {
+        let browser = browser_name(self.channel);
+        with_install_lock(&self.paths, browser, &self.version, async {
+            let release = FirefoxReleaseFiles::load(
+                &self.version,
+                self.channel,
+                &self.client,
+                &self.downloads,
+            )
+            .await;
+            let locales = self.resolve_locales().await?;
+            let locales: Vec<_> = locales.iter().map(String::as_str).collect();
+            install_firefox(
+                &release,
+                self.platform,
+                &locales,
+                &self.paths,
+                &self.options,
+            )
+            .await
+        })
+        .await
+    }
+
+    /// 只探测首选架构的安装包，语言的选择与安装时相同。
+    async fn plan(&self) -> Result<DownloadPlan> {
+        let release =
+            FirefoxReleaseFiles::load(&self.version, self.channel, &self.client, &self.downloads)
+                .await;
+        let ff_platform = firefox_platforms(self.platform)[0].0;
+        let mut errors = Vec::new();
+        for locale in self.resolve_locales().await? {
+            for file in release.installer_files(self.platform.os(), ff_platform, locale) {
+                let request = release.installer_request(ff_platform, locale, &file);
+                let size = match self.downloads.probe(&request).await {
+                    Ok(size) => size,
+                    Err(err) => {
+                        errors.push(format!("{locale}/{file}: {err}"));
+                        continue;
+                    }
+                };
+                let mut requests = vec![request.with_size(size)];
+                if self.options.with_mar {
+                    requests.push(release.request(&release.complete_mar(ff_platform, locale)));
+                }
+                if self.options.with_driver {
+                    requests.push(geckodriver_request(&self.version, self.platform)?.1);
+                }
+                return Ok(DownloadPlan {
+                    requests,
+                    revision: None,
+                    install_dir: self
+                        .paths
+                        .install_dir(browser_name(self.channel), &self.version),
+                });
+            }
+        }
+        Err(FetchError::VersionNotFound(format!(
+            "No firefox {} installer found for {ff_platform} ({})",
+            self.version,
+            errors.join(", ")
+        ))
+        .into())
+    }
+}
+
+impl FirefoxReleaseItem {
+    /// 依次尝试的安装包语言，部分版本没有发布所有语言的安装包，因此最后尝试 `en-US`（`--strict` 时不尝试）。
+    ///
+    /// `--lang` 指定的语言必须在该版本的语言列表中；自动检测的语言不在列表中时依次尝试只有语言部分的（如 `de`）和 `en-US`。
+    async fn resolve_locales(&self) -> Result<&[String]> {
+        if let Some(locales) = self.locales.get() {
+            return Ok(locales);
+        }
+        let (wanted, explicit) = match &self.options.firefox_lang {
+            Some(lang) => (lang.clone(), true),
+            None => (
+                system_locale().unwrap_or_else(|| DEFAULT_LOCALE.to_owned()),
+                false,
+            ),
+        };
+        let locale = match self.channel {
+            // latest-mozilla-central 下没有按语言分的目录，其他语言的构建在单独的 l10n 目录中。
+            ReleaseChannel::Canary => wanted,
+            _ => self.available_locale(&wanted, explicit).await?,
+        };
+        let mut locales = vec![locale];
+        if !self.options.strict && locales[0] != DEFAULT_LOCALE {
+            locales.push(DEFAULT_LOCALE.to_owned());
+        }
+        Ok(self.locales.get_or_init(|| async { locales }).await)
+    }
+
+    /// 在首选平台目录下的语言列表中查找 `wanted`，获取不到列表时给出警告并直接使用 `wanted`。
+    async fn available_locale(&self, wanted: &str, explicit: bool) -> Result<String> {
+        let ff_platform = firefox_platforms(self.platform)[0].0;
+        let url = format!(
+            "{}{}/{ff_platform}/",
+            releases_url(self.channel),
+            self.version
+        );
+        let available: Vec<String> = match directory_entries(&self.client, &url).await {
+            Ok(entries) => entries
+                .iter()
+                .map(|entry| entry.trim_end_matches('/').to_owned())
+                .filter(|entry| !entry.is_empty() && entry != "..")
+                .collect(),
+            Err(err) => {
+                status!("==> warning[no-locales]: fetching {url} failed: {err}, using {wanted} without checking");
+                return Ok(wanted.to_owned());
+            }
+        };
+        let find = |locale: &str| {
+            available
+                .iter()
+                .find(|entry| entry.eq_ignore_ascii_case(locale))
+                .cloned()
+        };
+        if let Some(locale) = find(wanted) {
+            return Ok(locale);
+        }
+        if explicit {
+            return Err(FetchError::VersionNotFound(format!(
+                "Firefox {} is not published in {wanted} for {ff_platform}, available locales: {}",
+                self.version,
+                available.join(", ")
+            ))
+            .into());
+        }
+        let language = wanted.split('-').next().unwrap_or(wanted);
+        let locale = find(language).unwrap_or_else(|| DEFAULT_LOCALE.to_owned());
+        status!(
+            "==> firefox {} is not published in {wanted}, using {locale}",
+            self.version
+        );
+        Ok(locale)
+    }
+}
+
+/// 没有指定 `--lang`、也检测不到系统语言时使用的安装包语言。
+const DEFAULT_LOCALE: &str = "en-US";
+
+/// 依次读取 `LC_ALL`、`LC_MESSAGES`、`LANG` 中的系统语言，例如 `zh_CN.UTF-8` 为 `zh-CN`。
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter().find_map(|name| {
+        let value = std::env::var(name).ok()?;
+        let locale = value.split(['.', '@']).next()?.replace('_', "-");
+        (!locale.is_empty() && locale != "C" && locale != "POSIX").then_some(locale)
+    })
+}
+
+/// ftp.mozilla.org 上各平台的目录名，依次尝试（后面的依赖系统的模拟运行）。
+fn firefox_platforms(platform: Platform) -> &'static [(&'static str, Arch)] {
+    match (platform.os(), platform.arch()) {
+        (Os::Windows, Arch::X86) => &[("win32", Arch::X86)],
+        (Os::Windows, Arch::X86_64) => &[("win64", Arch::X86_64), ("win32", Arch::X86)],
+        (Os::Windows, Arch::Arm64) => &[
+            ("win64-aarch64", Arch::Arm64),
+            ("win64", Arch::X86_64),
+            ("win32", Arch::X86),
+        ],
+        (Os::Linux, Arch::X86) => &[("linux-i686", Arch::X86)],
+        (Os::Linux, Arch::X86_64) => &[("linux-x86_64", Arch::X86_64), ("linux-i686", Arch::X86)],
+        (Os::Linux, Arch::Arm64) => &[("linux-aarch64", Arch::Arm64)],
+        // macOS 的 dmg 是同时包含 x64 和 arm64 的通用版本。
+        (Os::Mac, arch) => match arch {
+            Arch::Arm64 => &[("mac", Arch::Arm64)],
+            _ => &[("mac", Arch::X86_64)],
+        },
+    }
+}
+
+fn firefox_executable(os: Os) -> &'static str {
+    match os {
+        Os::Windows => "firefox.exe",
+        Os::Linux => "firefox",
+        Os::Mac => "Firefox.app/Contents/MacOS/firefox",
+    }
+}
+
+/// 解压后浏览器文件所在的目录：Windows 安装包在 core 下，Linux 压缩包和 Nightly 的 Windows zip 在 firefox 下，
+/// dmg 直接是 Firefox.app。
+pub(crate) fn firefox_content_dir(extracted: &Path, format: ArchiveFormat) -> PathBuf {
+    match format {
+        ArchiveFormat::SevenZip => extracted.join("core"),
+        ArchiveFormat::TarBz2 | ArchiveFormat::TarXz | ArchiveFormat::Zip => {
+            extracted.join("firefox")
+        }
+        _ => extracted.to_owned(),
+    }
+}
 
-    uncompress_archive(Cursor::new(zip_content), &base_path, Ownership::Preserve)?;
+/// 某个版本的发布目录，下载时用目录下的 SHA512SUMS 校验。
+struct FirefoxReleaseFiles<'a> {
+    version: &'a str,
+    channel: ReleaseChannel,
+    sums: Option<String>,
+    downloads: &'a DownloadManager,
+}
+
+impl<'a> FirefoxReleaseFiles<'a> {
+    /// 获取 SHA512SUMS，获取不到时给出警告，下载的文件不做校验。
+    async fn load(
+        version: &'a str,
+        channel: ReleaseChannel,
+        client: &Http,
+        downloads: &'a DownloadManager,
+    ) -> Self {
+        let sums = match verify_enabled() {
+            false => None,
+            true if channel == ReleaseChannel::Canary => {
+                status!("==> warning[no-checksums]: nightly builds publish no SHA512SUMS, downloads will not be verified");
+                None
+            }
+            true => {
+                let url = format!("{}{version}/SHA512SUMS", releases_url(channel));
+                let response = match client.get(&url).await {
+                    Ok(response) if response.is_success() => response.text().await,
+                    Ok(response) => Err(anyhow!("status {}", response.status())),
+                    Err(err) => Err(err),
+                };
+                response
+                    .map_err(|err| {
+                        status!("==> warning[no-checksums]: fetching {url} failed: {err}, downloads will not be verified");
+                    })
+                    .ok()
+            }
+        };
+        Self {
+            version,
+            channel,
+            sums,
+            downloads,
+        }
+    }
+
+    fn nightly(&self) -> bool {
+        self.channel == ReleaseChannel::Canary
+    }
+
+    /// Nightly 的文件直接位于 latest-mozilla-central 下，其余渠道位于 `<version>/` 下。
+    fn url(&self, path: &str) -> String {
+        let path = path.replace(' ', "%20");
+        match self.nightly() {
+            true => format!("{NIGHTLY_URL}{path}"),
+            false => format!("{}{}/{path}", releases_url(self.channel), self.version),
+        }
+    }
+
+    /// 安装包的文件名：Nightly 的文件名包含语言和平台，Windows 为 zip 压缩包；
+    /// 其余渠道按平台、语言分目录，Linux 较新的版本改用 tar.xz 发布。
+    fn installer_files(&self, os: Os, ff_platform: &str, locale: &str) -> Vec<String> {
+        let version = self.version;
+        match (self.nightly(), os) {
+            (true, Os::Windows) => vec![format!("firefox-{version}.{locale}.{ff_platform}.zip")],
+            (true, Os::Linux) => vec![
+                format!("firefox-{version}.{locale}.{ff_platform}.tar.xz"),
+                format!("firefox-{version}.{locale}.{ff_platform}.tar.bz2"),
+            ],
+            (true, Os::Mac) => vec![format!("firefox-{version}.{locale}.mac.dmg")],
+            (false, Os::Windows) => vec![
+                format!("Firefox Setup {version}.exe"),
+                format!("Firefox Setup {version}.msi"),
+            ],
+            (false, Os::Linux) => vec![
+                format!("firefox-{version}.tar.xz"),
+                format!("firefox-{version}.tar.bz2"),
+            ],
+            (false, Os::Mac) => vec![format!("Firefox {version}.dmg")],
+        }
+    }
+
+    /// 与安装包相同平台、语言的完整 MAR 更新包。
+    fn complete_mar(&self, ff_platform: &str, locale: &str) -> String {
+        let version = self.version;
+        match self.nightly() {
+            true => format!("firefox-{version}.{locale}.{ff_platform}.complete.mar"),
+            false => format!("update/{ff_platform}/{locale}/firefox-{version}.complete.mar"),
+        }
+    }
+
+    /// 下载发布目录下的 `path`（例如 `win64/en-US/Firefox Setup 116.0.exe`）。
+    async fn fetch(&self, path: &str) -> Result<Downloaded> {
+        self.downloads.fetch(&self.request(path)).await
+    }
+
+    /// 下载 `<platform>/<locale>/<file>` 安装包。
+    async fn fetch_installer(
+        &self,
+        platform: &str,
+        locale: &str,
+        file: &str,
+    ) -> Result<Downloaded> {
+        self.downloads
+            .fetch(&self.installer_request(platform, locale, file))
+            .await
+    }
 
-    let ff_path = cur_dir.join(format!("firefox-{matched_version}"));
+    /// `<platform>/<locale>/<file>` 安装包的下载请求，启用压缩包缓存时缓存。
+    ///
+    /// Nightly 每天以同样的版本号发布新的构建，不缓存。
+    fn installer_request(&self, platform: &str, locale: &str, file: &str) -> DownloadRequest {
+        if self.nightly() {
+            return self.request(file);
+        }
+        let request = self.request(&format!("{platform}/{locale}/{file}"));
+        request.with_cache_key(
+            browser_name(self.channel),
+            self.version,
+            &format!("{platform}-{locale}"),
+            file,
+        )
+    }
+
+    fn request(&self, path: &str) -> DownloadRequest {
+        let checksum = self
+            .sums
+            .as_deref()
+            .and_then(|sums| find_checksum(sums, path));
+        DownloadRequest::new(&self.url(path)).with_checksum(checksum)
+    }
+}
+
+async fn install_firefox(
+    release: &FirefoxReleaseFiles<'_>,
+    platform: Platform,
+    locales: &[&str],
+    paths: &Paths,
+    options: &DownloadOptions,
+) -> Result<PathBuf> {
+    let matched_version = release.version;
+    let browser = browser_name(release.channel);
+    // --strict 时只使用首选的架构。
+    let candidates = match options.strict {
+        true => &firefox_platforms(platform)[..1],
+        false => firefox_platforms(platform),
+    };
+    let mut result = Err(anyhow!("No firefox build for {}", platform.os().as_str()));
+    for (index, (name, arch)) in candidates.iter().enumerate() {
+        if let (Err(err), Some((previous, _))) =
+            (&result, index.checked_sub(1).map(|i| candidates[i]))
+        {
+            status!("==> download firefox {previous} failed: {err}, trying {name} ...");
+        }
+        result = download_firefox_package(release, platform.os(), name, locales, paths)
+            .await
+            .map(|(content, locale)| (content, *name, *arch, locale));
+        match &result {
+            Err(err) if !FetchError::is_checksum_mismatch(err) => {}
+            _ => break,
+        }
+    }
+    let (zip_content, ff_platform, arch, locale) = result?;
+    let format = ArchiveFormat::detect(&zip_content)
+        .ok_or_else(|| anyhow!("Unrecognized firefox package for {ff_platform}."))?;
+
+    let base_path = paths.temp_install_dir(browser, matched_version);
+    create_dir_all(&base_path)?;
+    let archive_path = options.keep_archive.then(|| {
+        paths.output_dir.join(kept_archive_name(
+            browser,
+            matched_version,
+            Platform::new(platform.os(), arch),
+            format.extension(),
+        ))
+    });
+    if archive_path.is_some() {
+        create_dir_all(&paths.output_dir)?;
+    }
+    {
+        let (archive_path, base_path) = (archive_path.clone(), base_path.clone());
+        blocking(move || {
+            if let Some(archive_path) = &archive_path {
+                std::fs::write(archive_path, &zip_content)?;
+            }
+            timings::time(Phase::Extract, || extract_archive(zip_content, &base_path))
+        })
+        .await?;
+    }
+
+    let ff_path = paths.install_dir(browser, matched_version);
     if ff_path.exists() {
         std::fs::remove_dir_all(&ff_path)?;
     }
-    std::fs::rename(base_path.join("core"), ff_path)?;
+    create_dir_all(&paths.output_dir)?;
+    let content_path = firefox_content_dir(&base_path, format);
+    mark_incomplete(&content_path)?;
+    move_dir(&content_path, &ff_path)?;
+    let metadata = InstallMetadata {
+        executable: Some(firefox_executable(platform.os()).to_owned()),
+        platform: Some(ff_platform.to_owned()),
+        arch: Some(arch.as_str().to_owned()),
+        locales: Some(vec![locale.to_owned()]),
+        channel: channel_name(release.channel).map(str::to_owned),
+        ..InstallMetadata::new(browser, matched_version)
+    };
+    metadata.write(&ff_path)?;
+    if let Some(archive_path) = &archive_path {
+        keep_archive(archive_path, &metadata)?;
+    }
+    if options.with_mar {
+        download_complete_mar(release, ff_platform, locale, paths).await?;
+    }
+    if options.with_driver {
+        install_geckodriver(
+            matched_version,
+            platform,
+            &ff_path,
+            release.downloads,
+            paths,
+        )
+        .await?;
+    }
+    mark_complete(&ff_path)?;
     if base_path.exists() {
         std::fs::remove_dir_all(&base_path)?;
     }
@@ -44,101 +564,323 @@ pub(crate) fn download_firefox(version: &str, client: &Client) -> Result<()> {
         std::fs::remove_file(setup_path)?;
     }
 
-    Ok(())
+    Ok(ff_path)
 }
 
-fn download_firefox_zip(version: &str, arch: &str, client: &Client) -> Result<Bytes> {
-    let cur_dir = current_dir()?;
-    let url = format!(
-        "https://ftp.mozilla.org/pub/firefox/releases/{version}/{arch}/zh-CN/Firefox%20Setup%20{version}.exe"
-    );
-    println!("==> download firefox: {url}");
-    let response = client.get(url).send()?;
-    if !response.status().is_success() {
-        return Err(anyhow!("Download firefox failed: {}", response.status()));
+/// 下载与安装包相同平台、语言的完整 MAR 更新包，保存在安装目录旁。
+async fn download_complete_mar(
+    release: &FirefoxReleaseFiles<'_>,
+    arch: &str,
+    locale: &str,
+    paths: &Paths,
+) -> Result<PathBuf> {
+    let version = release.version;
+    let downloaded = release.fetch(&release.complete_mar(arch, locale)).await?;
+    let mar_path = paths
+        .output_dir
+        .join(format!("firefox-{version}-{arch}-{locale}.complete.mar"));
+    std::fs::copy(&downloaded.path, &mar_path)?;
+    status!("==> saved complete MAR to {}", mar_path.display());
+    Ok(mar_path)
+}
+
+const FIREFOX_RELEASES_URL: &str = "https://ftp.mozilla.org/pub/firefox/releases/";
+/// Developer Edition 的发布目录，结构与 releases 相同。
+const DEVEDITION_RELEASES_URL: &str = "https://ftp.mozilla.org/pub/devedition/releases/";
+/// 最新的 Nightly 构建，文件名中包含版本、语言和平台，没有按平台、语言分的子目录。
+const NIGHTLY_URL: &str = "https://ftp.mozilla.org/pub/firefox/nightly/latest-mozilla-central/";
+
+/// 渠道的版本列表所在的目录，正式版、beta 和 esr 都在 releases 下。
+fn releases_url(channel: ReleaseChannel) -> &'static str {
+    match channel {
+        ReleaseChannel::Dev => DEVEDITION_RELEASES_URL,
+        ReleaseChannel::Canary => NIGHTLY_URL,
+        _ => FIREFOX_RELEASES_URL,
+    }
+}
+
+/// 安装目录和 metadata 中的浏览器名称，Developer Edition 与 beta 的版本号相同，单独命名。
+fn browser_name(channel: ReleaseChannel) -> &'static str {
+    match channel {
+        ReleaseChannel::Dev => "firefox-devedition",
+        _ => "firefox",
+    }
+}
+
+/// 记录在 metadata 中的渠道，`Any` 时不记录。
+fn channel_name(channel: ReleaseChannel) -> Option<&'static str> {
+    match channel {
+        ReleaseChannel::Stable => Some("release"),
+        ReleaseChannel::Beta => Some("beta"),
+        ReleaseChannel::Esr => Some("esr"),
+        ReleaseChannel::Dev => Some("devedition"),
+        ReleaseChannel::Canary => Some("nightly"),
+        ReleaseChannel::Extended | ReleaseChannel::Any => None,
     }
-    let exe_response = response.bytes()?;
+}
+
+/// releases 目录中的版本是否属于 `channel`：beta 以 `b<n>` 结尾，esr 以 `esr` 结尾。
+fn in_channel(channel: ReleaseChannel, version: &str) -> bool {
+    let tag = version.trim_end_matches(|ch: char| ch.is_ascii_digit());
+    match channel {
+        ReleaseChannel::Stable => !version.contains(|ch: char| ch.is_ascii_alphabetic()),
+        ReleaseChannel::Beta => tag.ends_with('b'),
+        ReleaseChannel::Esr => version.ends_with("esr"),
+        _ => true,
+    }
+}
+
+/// 下载 `ff_platform` 目录下的安装包，返回可以解压的内容和实际使用的语言。
+async fn download_firefox_package<'l>(
+    release: &FirefoxReleaseFiles<'_>,
+    os: Os,
+    ff_platform: &str,
+    locales: &[&'l str],
+    paths: &Paths,
+) -> Result<(Bytes, &'l str)> {
+    if os == Os::Windows && !release.nightly() {
+        return download_firefox_zip(release, ff_platform, locales, paths).await;
+    }
+    let mut errors = Vec::new();
+    for locale in locales {
+        for file in &release.installer_files(os, ff_platform, locale) {
+            match release.fetch_installer(ff_platform, locale, file).await {
+                Ok(downloaded) => return Ok((downloaded.bytes()?, locale)),
+                Err(err) if FetchError::is_checksum_mismatch(&err) => return Err(err),
+                Err(err) => errors.push(format!("{locale}/{file}: {err}")),
+            }
+        }
+    }
+    Err(anyhow!("Download firefox failed ({})", errors.join(", ")))
+}
+
+/// 返回安装包中的 7z 数据，以及实际使用的语言。
+///
+/// 只有下载失败（例如该语言没有发布）时才换下一个语言；下载到 stub 安装包时在同一语言下改用 msi，
+/// 仍然没有时报错，不悄悄换成其他语言。
+async fn download_firefox_zip<'l>(
+    release: &FirefoxReleaseFiles<'_>,
+    arch: &str,
+    locales: &[&'l str],
+    paths: &Paths,
+) -> Result<(Bytes, &'l str)> {
+    let version = release.version;
+    let mut errors = Vec::new();
+    for locale in locales {
+        let mut stub = None;
+        for file in release.installer_files(Os::Windows, arch, locale) {
+            let path = format!("{arch}/{locale}/{file}");
+            let content = match release.fetch_installer(arch, locale, &file).await {
+                Ok(downloaded) => downloaded.bytes()?,
+                Err(err) if FetchError::is_checksum_mismatch(&err) => return Err(err),
+                Err(err) => {
+                    errors.push(format!("{locale}/{file}: {err}"));
+                    continue;
+                }
+            };
+            let payload = match file.ends_with(".msi") {
+                true => {
+                    let dir = paths.temp_dir.join(format!(".msi-{version}"));
+                    let msi = content.clone();
+                    match blocking(move || msi_payload(msi, &dir)).await {
+                        Ok(Some(payload)) => Some(payload),
+                        Ok(None) => {
+                            errors.push(format!("{locale}/{file}: no 7z payload"));
+                            None
+                        }
+                        Err(err) => {
+                            errors.push(format!("{locale}/{file}: {err}"));
+                            None
+                        }
+                    }
+                }
+                false => seven_zip_payload(&content),
+            };
+            match payload {
+                Some(payload) => return Ok((payload, locale)),
+                None if file.ends_with(".exe") => {
+                    status!(
+                        "==> {} is a stub installer ({} bytes, no 7z payload), trying the msi.",
+                        release.url(&path),
+                        content.len()
+                    );
+                    errors.push(format!("{locale}/{file}: stub installer"));
+                    stub.get_or_insert(content);
+                }
+                None => {}
+            }
+        }
+        let Some(stub) = stub else {
+            continue;
+        };
+        // stub 安装包运行时才会联网下载完整的安装包，无法从中解压出 firefox。
+        let reason = errors.join(", ");
+        let exe_path = paths
+            .output_dir
+            .join(format!("Firefox Setup {version}.exe"));
+        return match std::fs::write(&exe_path, stub.as_ref()) {
+            Ok(_) => Err(anyhow!(
+                "Only a stub installer without a 7z payload is published for firefox {version} ({arch}/{locale}: {reason}), setup.exe saved at: {}",
+                exe_path.display()
+            )),
+            Err(_) => Err(anyhow!(
+                "Only a stub installer without a 7z payload is published for firefox {version} ({arch}/{locale}: {reason})"
+            )),
+        };
+    }
+    Err(anyhow!("Download firefox failed ({})", errors.join(", ")))
+}
+
+/// 完整安装包在 7z 自解压程序之后附带 7z 数据，stub 安装包没有。
+fn seven_zip_payload(installer: &Bytes) -> Option<Bytes> {
     let signature = b"7z\xbc\xaf\x27\x1c";
-    let index_of_sig = find_sequence(exe_response.as_ref(), signature).ok_or_else(|| {
-        let exe_path = cur_dir.join(format!("Firefox Setup {version}.exe"));
-        match std::fs::write(&exe_path, exe_response.as_ref()) {
-            Ok(_) => anyhow!(
-                "No 7zip signature found, setup.exe saved at: {}",
-                exe_path.to_str().unwrap_or_default()
-            ),
-            Err(_) => anyhow!("No 7zip signature found"),
+    find_sequence(installer.as_ref(), signature).map(|index| installer.slice(index..))
+}
+
+/// msi 中打包的是完整的安装程序，解压到 `dir` 后从中取出 7z 数据。
+fn msi_payload(msi: Bytes, dir: &Path) -> Result<Option<Bytes>> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)?;
+    }
+    let result = extract_archive(msi, dir).and_then(|_| find_payload(dir));
+    let _ = std::fs::remove_dir_all(dir);
+    result
+}
+
+fn find_payload(dir: &Path) -> Result<Option<Bytes>> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let payload = if path.is_dir() {
+            find_payload(&path)?
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+        {
+            seven_zip_payload(&Bytes::from(std::fs::read(&path)?))
+        } else {
+            None
+        };
+        if payload.is_some() {
+            return Ok(payload);
         }
-    })?;
-    Ok(exe_response.slice(index_of_sig..))
+    }
+    Ok(None)
 }
 
 #[derive(Debug)]
 struct FirefoxVersionSpider(Vec<String>);
 
 impl FirefoxVersionSpider {
-    fn init(client: &Client) -> Result<Self> {
-        let cached_releases_path = get_cached_file_path("firefox-releases.json")?;
-        if cached_releases_path.exists() {
-            println!(
+    /// Nightly 只有最新的构建，每次重新获取；其余渠道的版本列表按目录缓存。
+    async fn init(channel: ReleaseChannel, client: &Http, paths: &Paths) -> Result<Self> {
+        if channel == ReleaseChannel::Canary {
+            status!("==> fetching the latest firefox nightly from {NIGHTLY_URL} ...");
+            return fetch_nightly_versions(client).await.map(Self);
+        }
+        let mut releases = Self::load(channel, client, paths).await?;
+        releases.retain(|version| in_channel(channel, version));
+        Ok(Self(releases))
+    }
+
+    async fn load(channel: ReleaseChannel, client: &Http, paths: &Paths) -> Result<Vec<String>> {
+        let cached_releases_path =
+            paths.cached_file(&format!("{}-releases.json", browser_name(channel)))?;
+        let cached = paths.cache.read_usable(&cached_releases_path)?;
+        metrics::cache_lookup(cached.is_some());
+        if let Some(releases) = cached {
+            status!(
                 "==> using cached firefox releases: {}",
                 cached_releases_path.display()
             );
-            let releases = serde_json::from_reader(std::fs::File::open(cached_releases_path)?)?;
-            Ok(Self(releases))
+            Ok(releases)
         } else {
-            println!("==> fetching firefox releases from ftp.mozilla.org ...");
-            let response = client
-                .get("https://ftp.mozilla.org/pub/firefox/releases/")
-                .send()?
-                .text()?;
-            let doc = Document::from(response.as_str());
-            let releases = doc
-                .find(
-                    predicate::Name("tr")
-                        .descendant(predicate::Name("td"))
-                        .descendant(predicate::Name("a")),
-                )
-                .map(|node| node.text().trim_end_matches('/').to_owned())
-                .filter(|name| is_valid_ff_version(name.as_str()))
-                .collect::<Vec<_>>();
+            let url = releases_url(channel);
+            status!("==> fetching firefox releases from {url} ...");
+            let releases = match fetch_firefox_releases(client, url).await {
+                Ok(releases) => releases,
+                Err(err) => return paths.cache.read_stale(&cached_releases_path, err),
+            };
 
-            std::fs::write(&cached_releases_path, serde_json::to_string(&releases)?)?;
+            paths
+                .cache
+                .write_signed(&cached_releases_path, serde_json::to_string(&releases)?)?;
 
-            Ok(Self(releases))
+            Ok(releases)
         }
     }
 
     fn find(&self, version: &str) -> Vec<&String> {
-        let mut matched_list = self
-            .0
-            .iter()
-            .filter(|v| {
-                v.starts_with(version)
-                    && match v.chars().nth(version.chars().count()) {
-                        None => true,
-                        Some(ch) => !ch.is_numeric(),
-                    }
-            })
-            .collect::<Vec<_>>();
-        matched_list.sort_by(|a, b| {
-            let a_pure_num = a.chars().all(|ch| ch == '.' || ch.is_numeric());
-            let b_pure_num = b.chars().all(|ch| ch == '.' || ch.is_numeric());
-            match (a_pure_num, b_pure_num) {
-                (true, true) | (false, false) => a.cmp(b),
-                (true, false) => Ordering::Less,
-                (false, true) => Ordering::Greater,
-            }
-        });
-        matched_list
+        let spec = VersionSpec::parse(version);
+        self.0.iter().filter(|v| spec.matches(v)).collect()
     }
 }
 
-fn is_valid_ff_version(version: &str) -> bool {
-    let mut split = version.split('.');
-    match (split.next(), split.next()) {
-        (Some(first), Some(second)) => {
-            first.parse::<u32>().is_ok() && second.parse::<u32>().is_ok()
-        }
-        _ => false,
+async fn fetch_firefox_releases(client: &Http, url: &str) -> Result<Vec<String>> {
+    Ok(directory_entries(client, url)
+        .await?
+        .into_iter()
+        .map(|name| name.trim_end_matches('/').to_owned())
+        .filter(|name| is_valid_ff_version(name.as_str()))
+        .collect())
+}
+
+/// 从 latest-mozilla-central 下的文件名（如 `firefox-120.0a1.en-US.linux-x86_64.tar.bz2`）中取出版本号。
+async fn fetch_nightly_versions(client: &Http) -> Result<Vec<String>> {
+    let pattern = Regex::new(r"^firefox-(\d+\.\d+a1)\.").unwrap();
+    let mut versions: Vec<String> = directory_entries(client, NIGHTLY_URL)
+        .await?
+        .iter()
+        .filter_map(|name| Some(pattern.captures(name)?[1].to_owned()))
+        .collect();
+    versions.sort();
+    versions.dedup();
+    Ok(versions)
+}
+
+/// ftp.mozilla.org 目录列表页面中的文件和子目录名。
+async fn directory_entries(client: &Http, url: &str) -> Result<Vec<String>> {
+    let response = client.get(url).await?;
+    if !response.is_success() {
+        return Err(
+            FetchError::Network(format!("Retrieving {url} failed: {}", response.status())).into(),
+        );
     }
+    let response = response.text().await?;
+    let doc = Document::from(response.as_str());
+    Ok(doc
+        .find(
+            predicate::Name("tr")
+                .descendant(predicate::Name("td"))
+                .descendant(predicate::Name("a")),
+        )
+        .map(|node| node.text())
+        .collect())
+}
+
+/// 与 ftp.mozilla.org 上的版本列表比较缓存。
+pub(crate) async fn verify_cached_releases(client: &Http, paths: &Paths) -> Result<CacheStatus> {
+    let cached_releases_path = paths.cache_dir.join("firefox-releases.json");
+    let Some(cached) = paths
+        .cache
+        .read_verified::<Vec<String>>(&cached_releases_path)?
+    else {
+        return Ok(CacheStatus::Missing);
+    };
+    let upstream = fetch_firefox_releases(client, FIREFOX_RELEASES_URL).await?;
+    let missing: Vec<_> = upstream
+        .iter()
+        .filter(|version| !cached.contains(version))
+        .collect();
+    Ok(match missing.as_slice() {
+        [] => CacheStatus::Fresh,
+        [first, ..] => CacheStatus::Diverged(format!(
+            "{} of {} upstream releases are missing, e.g. {first}",
+            missing.len(),
+            upstream.len()
+        )),
+    })
+}
+
+fn is_valid_ff_version(version: &str) -> bool {
+    BrowserVersion::from_str(version).is_ok() && version.contains('.')
 }
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::http::Http;
+
+/// Brave 发布的各版本信息，`dependencies.chrome` 为所基于的 Chromium 版本。
+const BRAVE_VERSIONS_URL: &str = "https://versions.brave.com/latest/brave-versions.json";
+/// Electron 的发布列表，`chrome` 为内置的 Chromium 版本。
+const ELECTRON_RELEASES_URL: &str = "https://releases.electronjs.org/releases.json";
+
+/// 基于 Chromium 的下游浏览器，用于将其版本号换算为 Chromium 版本。
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum ChromiumFork {
+    Edge,
+    Brave,
+    Electron,
+}
+
+impl ChromiumFork {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ChromiumFork::Edge => "Edge",
+            ChromiumFork::Brave => "Brave",
+            ChromiumFork::Electron => "Electron",
+        }
+    }
+
+    /// 返回与 `version` 最接近的 Chromium 版本选择（前缀或区间），交给通常的版本解析。
+    pub(crate) async fn translate(&self, version: &str, client: &Http) -> Result<String> {
+        let spec = match self {
+            // Edge 的主版本号与 Chromium 一致，其余部分是 Edge 自己的构建号，没有公开的对应关系。
+            ChromiumFork::Edge => {
+                let major = components(version)?[0];
+                status!(
+                    "==> Edge {version} is based on Chromium {major}, using the closest Chromium {major} build"
+                );
+                major.to_string()
+            }
+            ChromiumFork::Brave => {
+                let chromium = brave_chromium_version(version, client).await?;
+                status!("==> Brave {version} is based on Chromium {chromium}");
+                branch_range(&chromium)?
+            }
+            ChromiumFork::Electron => {
+                let chromium = electron_chromium_version(version, client).await?;
+                status!("==> Electron {version} embeds Chromium {chromium}");
+                branch_range(&chromium)?
+            }
+        };
+        Ok(spec)
+    }
+}
+
+/// 同一分支（前三段相同）中不高于 `chromium` 的版本，配合默认的 `--pick newest` 选出最接近的构建。
+fn branch_range(chromium: &str) -> Result<String> {
+    let branch = components(chromium)?
+        .iter()
+        .take(3)
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(".");
+    Ok(format!("{branch}..{chromium}"))
+}
+
+fn components(version: &str) -> Result<Vec<usize>> {
+    version
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()
+        .ok_or_else(|| anyhow!("Invalid version {version}."))
+}
+
+#[derive(Deserialize)]
+struct BraveRelease {
+    dependencies: BraveDependencies,
+}
+
+#[derive(Deserialize)]
+struct BraveDependencies {
+    chrome: String,
+}
+
+async fn brave_chromium_version(version: &str, client: &Http) -> Result<String> {
+    status!("==> retrieving Brave versions from {BRAVE_VERSIONS_URL} ...");
+    let releases: HashMap<String, BraveRelease> =
+        client.get(BRAVE_VERSIONS_URL).await?.json().await?;
+    let version = version.trim_start_matches('v');
+    releases
+        .into_iter()
+        .find(|(tag, _)| tag.trim_start_matches('v') == version)
+        .map(|(_, release)| release.dependencies.chrome)
+        .ok_or_else(|| anyhow!("Brave {version} was not found in {BRAVE_VERSIONS_URL}."))
+}
+
+#[derive(Deserialize)]
+struct ElectronRelease {
+    version: String,
+    /// 尚未发布的夜间构建等可能没有该字段。
+    #[serde(default)]
+    chrome: Option<String>,
+}
+
+async fn electron_chromium_version(version: &str, client: &Http) -> Result<String> {
+    status!("==> retrieving Electron releases from {ELECTRON_RELEASES_URL} ...");
+    let releases: Vec<ElectronRelease> = client.get(ELECTRON_RELEASES_URL).await?.json().await?;
+    let version = version.trim_start_matches('v');
+    releases
+        .into_iter()
+        .find(|release| release.version == version)
+        .ok_or_else(|| anyhow!("Electron {version} was not found in {ELECTRON_RELEASES_URL}."))?
+        .chrome
+        .ok_or_else(|| anyhow!("Electron {version} does not list its Chromium version."))
+}
@@ -0,0 +1,792 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{future::BoxFuture, stream::BoxStream, StreamExt, TryStreamExt};
+use hyper::client::connect::HttpInfo;
+use reqwest::{
+    header::{CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE, RETRY_AFTER},
+    Client, RequestBuilder, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+
+use crate::connections;
+
+pub(crate) type Http = Arc<dyn HttpTransport>;
+
+static RECEIVED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn received_bytes() -> u64 {
+    RECEIVED_BYTES.load(Ordering::Relaxed)
+}
+
+/// 响应的内容，按收到的顺序逐块返回。
+pub(crate) type HttpBody = BoxStream<'static, Result<Bytes>>;
+
+#[async_trait]
+pub(crate) trait HttpTransport: Send + Sync {
+    async fn get(&self, url: &str) -> Result<HttpResponse>;
+
+    /// 从 offset 处开始请求（`Range`），不支持时返回完整内容（状态码 200）。
+    /// `if_range` 为之前响应的 ETag 或 Last-Modified，文件已变化时服务端同样返回完整内容。
+    async fn get_from(
+        &self,
+        url: &str,
+        _offset: u64,
+        _if_range: Option<&str>,
+    ) -> Result<HttpResponse> {
+        self.get(url).await
+    }
+
+    /// 请求 `start..=end` 这一段（`Range`），不支持时返回完整内容（状态码 200）。
+    async fn get_range(&self, url: &str, _start: u64, _end: u64) -> Result<HttpResponse> {
+        self.get(url).await
+    }
+}
+
+pub(crate) struct HttpResponse {
+    status: u16,
+    retry_after: Option<Duration>,
+    /// ETag（没有时为 Last-Modified），续传时用于确认文件没有变化。
+    validator: Option<String>,
+    /// Content-Length，续传时只是剩余部分的长度。
+    content_length: Option<u64>,
+    /// 分段响应的 Content-Range 中的文件总大小。
+    total_length: Option<u64>,
+    body: HttpBody,
+}
+
+impl HttpResponse {
+    pub(crate) fn new(status: u16, body: HttpBody) -> Self {
+        Self {
+            status,
+            retry_after: None,
+            validator: None,
+            content_length: None,
+            total_length: None,
+            body,
+        }
+    }
+
+    pub(crate) fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub(crate) fn with_validator(mut self, validator: Option<String>) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    pub(crate) fn validator(&self) -> Option<&str> {
+        self.validator.as_deref()
+    }
+
+    pub(crate) fn with_content_length(mut self, content_length: Option<u64>) -> Self {
+        self.content_length = content_length;
+        self
+    }
+
+    pub(crate) fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    pub(crate) fn with_total_length(mut self, total_length: Option<u64>) -> Self {
+        self.total_length = total_length;
+        self
+    }
+
+    pub(crate) fn total_length(&self) -> Option<u64> {
+        self.total_length
+    }
+
+    pub(crate) fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub(crate) fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// 下一块内容，读完时返回 `None`。
+    pub(crate) async fn chunk(&mut self) -> Result<Option<Bytes>> {
+        let chunk = self.body.try_next().await?;
+        if let Some(chunk) = &chunk {
+            RECEIVED_BYTES.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }
+        Ok(chunk)
+    }
+
+    pub(crate) async fn bytes(mut self) -> Result<Bytes> {
+        let mut buf = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(Bytes::from(buf))
+    }
+
+    pub(crate) async fn text(self) -> Result<String> {
+        Ok(String::from_utf8(self.bytes().await?.to_vec())?)
+    }
+
+    pub(crate) async fn json<T: DeserializeOwned>(self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.bytes().await?)?)
+    }
+}
+
+pub(crate) struct ReqwestTransport(Client);
+
+impl ReqwestTransport {
+    pub(crate) fn new(client: Client) -> Self {
+        Self(client)
+    }
+}
+
+impl ReqwestTransport {
+    async fn send(request: RequestBuilder) -> Result<HttpResponse> {
+        let response = request.send().await?;
+        let info = response.extensions().get::<HttpInfo>();
+        connections::record(
+            response.url().host_str().unwrap_or_default(),
+            info.map(HttpInfo::local_addr),
+            info.map(HttpInfo::remote_addr),
+            response.version(),
+        );
+        // 只处理秒数形式的 Retry-After，HTTP 日期形式按未提供处理。
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+            .map(Duration::from_secs);
+        let validator = [ETAG, LAST_MODIFIED]
+            .iter()
+            .find_map(|name| Some(response.headers().get(name)?.to_str().ok()?.to_owned()));
+        let content_length = response.content_length();
+        // `bytes 0-99/1234` 中斜杠后的部分，总大小未知时为 `*`。
+        let total_length = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok()?.rsplit('/').next()?.parse().ok());
+        let status = response.status().as_u16();
+        let body = response.bytes_stream().map_err(anyhow::Error::from).boxed();
+        Ok(HttpResponse::new(status, body)
+            .with_retry_after(retry_after)
+            .with_validator(validator)
+            .with_content_length(content_length)
+            .with_total_length(total_length))
+    }
+}
+
+#[async_trait]
+impl HttpTransport for ReqwestTransport {
+    async fn get(&self, url: &str) -> Result<HttpResponse> {
+        Self::send(self.0.get(url)).await
+    }
+
+    async fn get_from(
+        &self,
+        url: &str,
+        offset: u64,
+        if_range: Option<&str>,
+    ) -> Result<HttpResponse> {
+        let request = self.0.get(url).header(RANGE, format!("bytes={offset}-"));
+        match if_range {
+            Some(validator) => Self::send(request.header(IF_RANGE, validator)).await,
+            None => Self::send(request).await,
+        }
+    }
+
+    async fn get_range(&self, url: &str, start: u64, end: u64) -> Result<HttpResponse> {
+        Self::send(
+            self.0
+                .get(url)
+                .header(RANGE, format!("bytes={start}-{end}")),
+        )
+        .await
+    }
+}
+
+/// 将匹配上游前缀的请求改写到镜像地址，并交给该镜像自己的客户端发送，
+/// 这样每个镜像可以信任各自的 CA，而不影响访问其他地址。
+pub(crate) struct MirrorTransport {
+    inner: Http,
+    /// 上游前缀、镜像前缀及访问该镜像使用的客户端。
+    mirrors: Vec<(String, String, Http)>,
+}
+
+impl MirrorTransport {
+    pub(crate) fn new(inner: Http, mut mirrors: Vec<(String, String, Http)>) -> Self {
+        // 前缀较长的优先匹配。
+        mirrors.sort_by_key(|(upstream, _, _)| std::cmp::Reverse(upstream.len()));
+        Self { inner, mirrors }
+    }
+
+    fn route(&self, url: &str) -> (String, &Http) {
+        self.mirrors
+            .iter()
+            .find_map(|(upstream, mirror, client)| {
+                let rest = url.strip_prefix(upstream.as_str())?;
+                Some((format!("{mirror}{rest}"), client))
+            })
+            .unwrap_or_else(|| (url.to_owned(), &self.inner))
+    }
+}
+
+#[async_trait]
+impl HttpTransport for MirrorTransport {
+    async fn get(&self, url: &str) -> Result<HttpResponse> {
+        let (url, client) = self.route(url);
+        client.get(&url).await
+    }
+
+    async fn get_from(
+        &self,
+        url: &str,
+        offset: u64,
+        if_range: Option<&str>,
+    ) -> Result<HttpResponse> {
+        let (url, client) = self.route(url);
+        client.get_from(&url, offset, if_range).await
+    }
+
+    async fn get_range(&self, url: &str, start: u64, end: u64) -> Result<HttpResponse> {
+        let (url, client) = self.route(url);
+        client.get_range(&url, start, end).await
+    }
+}
+
+/// 收到 429 后最多重试的次数。
+const RATE_LIMIT_RETRIES: u32 = 5;
+
+/// 按主机限制请求频率，收到 429 时按 Retry-After（没有则指数退避）等待后重试。
+pub(crate) struct RateLimitedTransport {
+    inner: Http,
+    /// 主机后缀及两次请求之间的最小间隔。
+    limits: Vec<(String, Duration)>,
+    next_slots: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimitedTransport {
+    /// `limits` 为主机后缀到每秒最大请求数的映射，0 表示不限制。
+    pub(crate) fn new(inner: Http, limits: &BTreeMap<String, f64>) -> Self {
+        let limits = limits
+            .iter()
+            .filter(|(_, rate)| **rate > 0.0)
+            .map(|(host, rate)| (host.clone(), Duration::from_secs_f64(1.0 / rate)))
+            .collect();
+        Self {
+            inner,
+            limits,
+            next_slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn wait_for_slot(&self, host: &str) {
+        let Some((suffix, interval)) = self
+            .limits
+            .iter()
+            .find(|(suffix, _)| host == suffix || host.ends_with(&format!(".{suffix}")))
+        else {
+            return;
+        };
+        let now = Instant::now();
+        let slot = {
+            let mut next_slots = self.next_slots.lock().unwrap();
+            let next = next_slots.entry(suffix.clone()).or_insert(now);
+            let slot = (*next).max(now);
+            *next = slot + *interval;
+            slot
+        };
+        tokio::time::sleep(slot - now).await;
+    }
+
+    async fn send<'a>(
+        &'a self,
+        url: &str,
+        get: impl Fn(&'a Http) -> BoxFuture<'a, Result<HttpResponse>>,
+    ) -> Result<HttpResponse> {
+        let host = Url::parse(url)?.host_str().unwrap_or_default().to_owned();
+        let mut attempt = 0;
+        loop {
+            self.wait_for_slot(&host).await;
+            let response = get(&self.inner).await?;
+            if response.status() != 429 || attempt == RATE_LIMIT_RETRIES {
+                return Ok(response);
+            }
+            let delay = response
+                .retry_after
+                .unwrap_or_else(|| Duration::from_secs(1 << attempt));
+            status!(
+                "==> rate limited by {host}, retrying in {}s ...",
+                delay.as_secs_f64()
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl HttpTransport for RateLimitedTransport {
+    async fn get(&self, url: &str) -> Result<HttpResponse> {
+        self.send(url, |inner| inner.get(url)).await
+    }
+
+    async fn get_from(
+        &self,
+        url: &str,
+        offset: u64,
+        if_range: Option<&str>,
+    ) -> Result<HttpResponse> {
+        self.send(url, |inner| inner.get_from(url, offset, if_range))
+            .await
+    }
+
+    async fn get_range(&self, url: &str, start: u64, end: u64) -> Result<HttpResponse> {
+        self.send(url, |inner| inner.get_range(url, start, end))
+            .await
+    }
+}
+
+/// 分段请求在 fixture 索引和 [`MockTransport`] 中的键，`end` 为 `None` 时为续传（`bytes=<start>-`）。
+fn range_key(url: &str, start: u64, end: Option<u64>) -> String {
+    match end {
+        Some(end) => format!("{url} bytes={start}-{end}"),
+        None => format!("{url} bytes={start}-"),
+    }
+}
+
+#[derive(Clone)]
+enum MockBody {
+    #[cfg(test)]
+    Bytes(Bytes),
+    /// 回放的 fixture 文件，每次请求时打开，不整个读入内存。
+    File(PathBuf),
+}
+
+#[derive(Clone)]
+struct MockResponse {
+    status: u16,
+    body: MockBody,
+    validator: Option<String>,
+    total_length: Option<u64>,
+}
+
+/// 按 URL 返回预先设置的响应。分段请求先按 [`range_key`] 查找，没有时返回完整内容。
+#[derive(Default)]
+pub(crate) struct MockTransport {
+    responses: Mutex<HashMap<String, MockResponse>>,
+}
+
+impl MockTransport {
+    #[cfg(test)]
+    pub(crate) fn respond(&self, url: &str, status: u16, body: impl Into<Bytes>) {
+        self.insert(
+            url,
+            MockResponse {
+                status,
+                body: MockBody::Bytes(body.into()),
+                validator: None,
+                total_length: None,
+            },
+        );
+    }
+
+    fn insert(&self, key: &str, response: MockResponse) {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), response);
+    }
+
+    async fn lookup(&self, key: &str) -> Result<Option<HttpResponse>> {
+        let Some(response) = self.responses.lock().unwrap().get(key).cloned() else {
+            return Ok(None);
+        };
+        let (body, content_length): (HttpBody, u64) = match response.body {
+            #[cfg(test)]
+            MockBody::Bytes(bytes) => {
+                let len = bytes.len() as u64;
+                (futures::stream::once(async { Ok(bytes) }).boxed(), len)
+            }
+            MockBody::File(path) => {
+                let file = tokio::fs::File::open(&path).await?;
+                let len = file.metadata().await?.len();
+                (file_body(file), len)
+            }
+        };
+        Ok(Some(
+            HttpResponse::new(response.status, body)
+                .with_validator(response.validator)
+                .with_content_length(Some(content_length))
+                .with_total_length(response.total_length),
+        ))
+    }
+}
+
+/// 逐块读取回放的 fixture 文件。
+fn file_body(file: tokio::fs::File) -> HttpBody {
+    futures::stream::try_unfold(file, |mut file| async move {
+        let mut buf = vec![0; 64 * 1024];
+        match file.read(&mut buf).await? {
+            0 => Ok(None),
+            len => {
+                buf.truncate(len);
+                Ok(Some((Bytes::from(buf), file)))
+            }
+        }
+    })
+    .boxed()
+}
+
+#[async_trait]
+impl HttpTransport for MockTransport {
+    async fn get(&self, url: &str) -> Result<HttpResponse> {
+        self.lookup(url)
+            .await?
+            .ok_or_else(|| anyhow!("No mocked response for {url}"))
+    }
+
+    async fn get_from(
+        &self,
+        url: &str,
+        offset: u64,
+        _if_range: Option<&str>,
+    ) -> Result<HttpResponse> {
+        match self.lookup(&range_key(url, offset, None)).await? {
+            Some(response) => Ok(response),
+            None => self.get(url).await,
+        }
+    }
+
+    async fn get_range(&self, url: &str, start: u64, end: u64) -> Result<HttpResponse> {
+        match self.lookup(&range_key(url, start, Some(end))).await? {
+            Some(response) => Ok(response),
+            None => self.get(url).await,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FixtureIndex(BTreeMap<String, FixtureEntry>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FixtureEntry {
+    status: u16,
+    file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    validator: Option<String>,
+    /// 分段响应的 Content-Range 中的文件总大小。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    total_length: Option<u64>,
+}
+
+impl FixtureIndex {
+    fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join("index.json");
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        std::fs::write(dir.join("index.json"), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct RecordingTransport {
+    inner: Http,
+    dir: PathBuf,
+    index: Arc<Mutex<FixtureIndex>>,
+}
+
+impl RecordingTransport {
+    pub(crate) fn new(inner: Http, dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            inner,
+            dir: dir.to_owned(),
+            index: Arc::new(Mutex::new(FixtureIndex::load(dir)?)),
+        })
+    }
+}
+
+impl RecordingTransport {
+    /// 记录 `key`（URL，分段请求见 [`range_key`]）的响应。
+    async fn record(&self, key: String, response: HttpResponse) -> Result<HttpResponse> {
+        // 同一 URL 可能被同时请求，各自写入不同的临时文件，读完后再重命名。
+        static PART_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let file = format!("{:x}", Sha256::digest(key.as_bytes()));
+        let part_path = self.dir.join(format!(
+            "{file}.{}-{}.part",
+            std::process::id(),
+            PART_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let HttpResponse {
+            status,
+            retry_after,
+            validator,
+            content_length,
+            total_length,
+            mut body,
+        } = response;
+        let mut recording = RecordingBody {
+            output: Some(File::create(&part_path)?),
+            part_path,
+            dir: self.dir.clone(),
+            index: self.index.clone(),
+            url: key,
+            entry: FixtureEntry {
+                status,
+                file,
+                validator: validator.clone(),
+                total_length,
+            },
+        };
+        // 外层的 HttpResponse 统计收到的字节数，这里直接读内层的内容。
+        let body = match (200..300).contains(&status) {
+            // 出错的响应通常只检查状态码，不读内容，这里先读完再记录。
+            false => {
+                let mut content = Vec::new();
+                while let Some(chunk) = body.try_next().await? {
+                    content.extend_from_slice(&chunk);
+                }
+                recording.write(&content)?;
+                recording.finish()?;
+                futures::stream::once(async { Ok(Bytes::from(content)) }).boxed()
+            }
+            true => futures::stream::try_unfold(
+                (body, recording),
+                |(mut body, mut recording)| async move {
+                    match body.try_next().await? {
+                        Some(chunk) => {
+                            recording.write(&chunk)?;
+                            Ok(Some((chunk, (body, recording))))
+                        }
+                        None => {
+                            recording.finish()?;
+                            Ok(None)
+                        }
+                    }
+                },
+            )
+            .boxed(),
+        };
+        Ok(HttpResponse::new(status, body)
+            .with_retry_after(retry_after)
+            .with_validator(validator)
+            .with_content_length(content_length)
+            .with_total_length(total_length))
+    }
+}
+
+#[async_trait]
+impl HttpTransport for RecordingTransport {
+    async fn get(&self, url: &str) -> Result<HttpResponse> {
+        self.record(url.to_owned(), self.inner.get(url).await?)
+            .await
+    }
+
+    async fn get_from(
+        &self,
+        url: &str,
+        offset: u64,
+        if_range: Option<&str>,
+    ) -> Result<HttpResponse> {
+        let response = self.inner.get_from(url, offset, if_range).await?;
+        self.record(range_key(url, offset, None), response).await
+    }
+
+    async fn get_range(&self, url: &str, start: u64, end: u64) -> Result<HttpResponse> {
+        let response = self.inner.get_range(url, start, end).await?;
+        self.record(range_key(url, start, Some(end)), response)
+            .await
+    }
+}
+
+/// 边读边把响应写入 fixture 文件（先写到 `.part`），读完后再记入索引，
+/// 下载大文件时不必整个放在内存中。未读完就丢弃的成功响应不记录，避免留下不完整的 fixture。
+struct RecordingBody {
+    /// 读完或放弃后为 `None`。
+    output: Option<File>,
+    part_path: PathBuf,
+    dir: PathBuf,
+    index: Arc<Mutex<FixtureIndex>>,
+    url: String,
+    entry: FixtureEntry,
+}
+
+impl RecordingBody {
+    fn write(&mut self, chunk: &[u8]) -> Result<()> {
+        if let Some(output) = &mut self.output {
+            output.write_all(chunk)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let Some(output) = self.output.take() else {
+            return Ok(());
+        };
+        drop(output);
+        std::fs::rename(&self.part_path, self.dir.join(&self.entry.file))?;
+        let mut index = self.index.lock().unwrap();
+        index.0.insert(self.url.clone(), self.entry.clone());
+        index.save(&self.dir)
+    }
+}
+
+impl Drop for RecordingBody {
+    fn drop(&mut self) {
+        // 没读完说明下载被中断。
+        if self.output.take().is_some() {
+            let _ = std::fs::remove_file(&self.part_path);
+        }
+    }
+}
+
+impl MockTransport {
+    pub(crate) fn from_fixtures(dir: &Path) -> Result<Self> {
+        let index = FixtureIndex::load(dir)?;
+        if index.0.is_empty() {
+            return Err(anyhow!("No fixtures found in {}", dir.display()));
+        }
+        let mock = Self::default();
+        for (key, entry) in index.0 {
+            mock.insert(
+                &key,
+                MockResponse {
+                    status: entry.status,
+                    body: MockBody::File(dir.join(entry.file)),
+                    validator: entry.validator,
+                    total_length: entry.total_length,
+                },
+            );
+        }
+        Ok(mock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn recording_streams_bodies_to_fixtures() {
+        let dir = std::env::temp_dir().join(format!("fetchbrowser-record-{}", std::process::id()));
+        let mock = Arc::new(MockTransport::default());
+        mock.respond("https://example.com/ok", 200, "content");
+        mock.respond("https://example.com/partial", 200, "a".repeat(1024));
+        mock.respond("https://example.com/missing", 404, "not found");
+        let recording = RecordingTransport::new(mock, &dir).unwrap();
+
+        assert_eq!(
+            recording
+                .get("https://example.com/ok")
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap(),
+            "content"
+        );
+        // 只检查状态码的错误响应同样记录。
+        assert_eq!(
+            recording
+                .get("https://example.com/missing")
+                .await
+                .unwrap()
+                .status(),
+            404
+        );
+        // 没读完的成功响应不记录。
+        let mut partial = recording.get("https://example.com/partial").await.unwrap();
+        partial.chunk().await.unwrap();
+        drop(partial);
+
+        let replay = MockTransport::from_fixtures(&dir).unwrap();
+        assert_eq!(
+            replay
+                .get("https://example.com/ok")
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap(),
+            "content"
+        );
+        let missing = replay.get("https://example.com/missing").await.unwrap();
+        assert_eq!(missing.status(), 404);
+        assert_eq!(missing.text().await.unwrap(), "not found");
+        assert!(replay.get("https://example.com/partial").await.is_err());
+        assert!(!std::fs::read_dir(&dir).unwrap().any(|entry| entry
+            .unwrap()
+            .path()
+            .extension()
+            .is_some_and(|ext| ext == "part")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn ranges_and_concurrent_requests_are_recorded() {
+        let dir =
+            std::env::temp_dir().join(format!("fetchbrowser-record-range-{}", std::process::id()));
+        let mock = Arc::new(MockTransport::default());
+        mock.respond("https://example.com/file", 200, "full");
+        mock.insert(
+            &range_key("https://example.com/file", 0, Some(1)),
+            MockResponse {
+                status: 206,
+                body: MockBody::Bytes(Bytes::from("fu")),
+                validator: None,
+                total_length: Some(4),
+            },
+        );
+        let recording = RecordingTransport::new(mock, &dir).unwrap();
+
+        // 同一 URL 的两个响应同时在读。
+        let first = recording.get("https://example.com/file").await.unwrap();
+        let second = recording.get("https://example.com/file").await.unwrap();
+        assert_eq!(second.text().await.unwrap(), "full");
+        assert_eq!(first.text().await.unwrap(), "full");
+        let range = recording
+            .get_range("https://example.com/file", 0, 1)
+            .await
+            .unwrap();
+        assert_eq!(range.total_length(), Some(4));
+        assert_eq!(range.text().await.unwrap(), "fu");
+
+        let replay = MockTransport::from_fixtures(&dir).unwrap();
+        let range = replay
+            .get_range("https://example.com/file", 0, 1)
+            .await
+            .unwrap();
+        assert_eq!((range.status(), range.total_length()), (206, Some(4)));
+        assert_eq!(range.text().await.unwrap(), "fu");
+        // 没有记录的分段返回完整内容。
+        let other = replay
+            .get_range("https://example.com/file", 2, 3)
+            .await
+            .unwrap();
+        assert_eq!(
+            (other.status(), other.text().await.unwrap().as_str()),
+            (200, "full")
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
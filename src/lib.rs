@@ -0,0 +1,97 @@
+//! 浏览器下载器。支持下载指定版本的 Chromium 和 Firefox。
+//!
+//! 命令行 `fb` 之外，也可以作为库使用。网络请求是异步的，需要在 tokio 的多线程运行时中执行：
+//!
+//! ```no_run
+//! use fetchbrowser::{
+//!     download_browser, Arch, ChromiumReleases, DownloadOptions, Os, Platform, ReleaseChannel,
+//!     Session, VersionPick,
+//! };
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let session = Session::new("browsers".as_ref(), None)?;
+//! let options = DownloadOptions::builder().locales(["en-US"]).build();
+//! let releases = ChromiumReleases::new(
+//!     &session,
+//!     Platform::new(Os::Linux, Arch::X86_64),
+//!     ReleaseChannel::Stable,
+//!     options,
+//! )
+//! .await?;
+//! let path = download_browser(&releases, "114", &VersionPick::Newest).await?;
+//! println!("installed into {}", path.display());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! 出错时返回 `anyhow::Error`，可以 downcast 为 [`FetchError`] 区分常见的错误类别。
+
+/// 记录运行日志（`==> ...`），用法与 `println!` 相同，见 [`summary::status_line`]。
+macro_rules! status {
+    () => {
+        $crate::summary::status_line(format_args!(""))
+    };
+    ($($arg:tt)*) => {
+        $crate::summary::status_line(format_args!($($arg)*))
+    };
+}
+
+mod archive;
+mod bundle;
+mod cache;
+mod cft;
+mod check;
+mod checksums;
+mod chromium;
+mod cli;
+mod common;
+mod config;
+mod connections;
+mod crx;
+mod custom;
+mod daemon;
+mod deadline;
+mod diagnostic;
+mod downloads;
+mod edge;
+mod error;
+mod events;
+mod extract;
+mod firefox;
+mod fork;
+mod http;
+mod lock;
+mod metadata;
+mod metrics;
+mod net;
+mod pac;
+mod paths;
+mod platform;
+mod progress;
+mod queue;
+mod schema;
+mod session;
+mod shortcut;
+mod summary;
+mod sync;
+mod system_proxy;
+mod timings;
+mod utils;
+mod version;
+mod which;
+
+pub use chromium::{
+    list_chromium_versions, resolve_chromium, ChromiumReleases, HistorySource, ResolvedBuild,
+};
+/// 命令行 `fb` 的入口，供 `src/main.rs` 调用。
+#[doc(hidden)]
+pub use cli::main;
+pub use common::{
+    download_browser, BrowserReleaseItem, BrowserReleases, BuildVariant, DownloadOptions,
+    DownloadOptionsBuilder, ReleaseChannel, ReleaseMatches, VersionPick,
+};
+pub use error::FetchError;
+pub use firefox::FirefoxReleases;
+pub use platform::{Arch, Os, Platform};
+pub use session::{Session, SessionSettings, SessionSettingsBuilder};
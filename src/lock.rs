@@ -0,0 +1,141 @@
+use std::{
+    fs::{File, TryLockError},
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::{
+    checksums::{spot_check, write_checksums},
+    deadline,
+    metadata::{is_incomplete, InstallMetadata},
+    paths::Paths,
+    utils::blocking,
+};
+
+/// `--skip-existing`：已经安装了同一版本时不再下载。
+static SKIP_EXISTING: AtomicBool = AtomicBool::new(false);
+
+/// 复用已有的安装前，从 SHA256SUMS 中抽查的文件数。
+static SPOT_CHECK: AtomicUsize = AtomicUsize::new(0);
+
+/// 正在安装的临时目录和安装目录，`--deadline` 中止时等待它们清理完。
+static INSTALLING: Mutex<Vec<(PathBuf, PathBuf)>> = Mutex::new(Vec::new());
+
+pub(crate) fn set_skip_existing(skip: bool, spot_check: usize) {
+    SKIP_EXISTING.store(skip, Ordering::Relaxed);
+    SPOT_CHECK.store(spot_check, Ordering::Relaxed);
+}
+
+/// 持有安装目录对应的锁文件执行 `install`，避免多个进程同时向同一个目录安装同一版本。
+///
+/// 锁文件位于输出目录下（`.<dir>.lock`），进程退出时系统会自动释放锁。需要等待时，
+/// 等到的若已经是其他进程装好的同一版本，则直接复用，不再重复下载。
+/// 启用了 `--skip-existing` 或 `--spot-check` 时，安装完成后在目录中写入 SHA256SUMS，供复用前抽查。
+/// 计算摘要需要读一遍所有文件，其他情况下不写。
+pub(crate) async fn with_install_lock(
+    paths: &Paths,
+    browser: &str,
+    version: &str,
+    install: impl Future<Output = Result<PathBuf>>,
+) -> Result<PathBuf> {
+    let install_dir = paths.install_dir(browser, version);
+    let dir_name = install_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    std::fs::create_dir_all(&paths.output_dir)?;
+    // 锁文件不删除：删除后再创建会让等待中的进程和新进程锁住不同的文件。
+    let lock_file = File::create(paths.output_dir.join(format!(".{dir_name}.lock")))?;
+    let (_lock_file, waited) = match lock_file.try_lock() {
+        Ok(()) => (lock_file, false),
+        Err(TryLockError::WouldBlock) => {
+            status!("==> waiting for another process installing {dir_name} ...");
+            let lock_file = blocking(move || {
+                lock_file.lock()?;
+                Ok(lock_file)
+            })
+            .await?;
+            (lock_file, true)
+        }
+        Err(TryLockError::Error(err)) => return Err(err.into()),
+    };
+    let installed = InstallMetadata::read(&install_dir)
+        .is_ok_and(|metadata| metadata.browser == browser && metadata.version == version);
+    if installed && waited {
+        status!(
+            "==> reusing {}, installed by another process",
+            install_dir.display()
+        );
+        return Ok(install_dir);
+    }
+    if installed && SKIP_EXISTING.load(Ordering::Relaxed) {
+        let checked = match SPOT_CHECK.load(Ordering::Relaxed) {
+            0 => Ok(()),
+            count => {
+                let install_dir = install_dir.clone();
+                blocking(move || spot_check(&install_dir, count)).await
+            }
+        };
+        match checked {
+            Ok(()) => {
+                status!("==> {} already installed, skipping", install_dir.display());
+                return Ok(install_dir);
+            }
+            Err(err) => status!(
+                "==> warning[corrupted-install]: {}: {err}, installing it again.",
+                install_dir.display()
+            ),
+        }
+    }
+    let entry = (paths.temp_install_dir(browser, version), install_dir);
+    INSTALLING.lock().unwrap().push(entry.clone());
+    let checksums = SKIP_EXISTING.load(Ordering::Relaxed) || SPOT_CHECK.load(Ordering::Relaxed) > 0;
+    let result = match install.await {
+        Ok(path) if checksums => {
+            blocking(move || {
+                write_checksums(&path)?;
+                Ok(path)
+            })
+            .await
+        }
+        result => result,
+    };
+    // `install` 返回时其中的下载和解压都已结束，可以安全地删除目录。
+    if result.is_err() && deadline::cancelled() {
+        abandon_install(&entry.0, &entry.1);
+    }
+    INSTALLING
+        .lock()
+        .unwrap()
+        .retain(|installing| *installing != entry);
+    result
+}
+
+/// 删除中止的安装留下的临时目录和未完成的安装目录。
+///
+/// 这些目录受本进程持有的锁保护，不会误删其他进程的安装。
+fn abandon_install(temp_dir: &Path, install_dir: &Path) {
+    let _ = std::fs::remove_dir_all(temp_dir);
+    if is_incomplete(install_dir) {
+        let _ = std::fs::remove_dir_all(install_dir);
+    }
+}
+
+/// 等待正在进行的安装全部结束，超过 `timeout` 时返回 false。
+pub(crate) fn wait_for_installs(timeout: Duration) -> bool {
+    let started = Instant::now();
+    while !INSTALLING.lock().unwrap().is_empty() {
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    true
+}
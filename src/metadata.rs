@@ -0,0 +1,139 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::platform::Os;
+
+const METADATA_FILE: &str = "metadata.json";
+/// 安装过程中存在的标记文件，安装被中断时留在目录中。
+pub(crate) const INCOMPLETE_MARKER: &str = ".incomplete";
+pub(crate) const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct InstallMetadata {
+    #[serde(default)]
+    pub(crate) schema_version: u32,
+    pub(crate) browser: String,
+    pub(crate) version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) executable: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) platform: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) arch: Option<String>,
+    /// 回退安装了其他架构的构建时，记录原本请求的架构。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) requested_arch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) channel: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) snapshot_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) revision: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) base_position: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) drift: Option<usize>,
+    #[serde(default)]
+    pub(crate) approximate: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) locales: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) devtools_protocol: Option<String>,
+    /// 从解压出的文件中读取到的实际版本号。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) binary_version: Option<String>,
+    /// 下载的快照压缩包在 GCS 中的对象，下游的缓存可以按 `generation` 区分。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) snapshot_object: Option<SnapshotObject>,
+}
+
+/// 快照压缩包对应的 GCS 存储对象。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SnapshotObject {
+    pub(crate) name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) size: Option<u64>,
+    pub(crate) updated: String,
+    pub(crate) media_link: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) generation: Option<String>,
+}
+
+impl InstallMetadata {
+    pub(crate) fn new(browser: &str, version: &str) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            browser: browser.to_owned(),
+            version: version.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// 带有未完成标记的目录按损坏的安装处理。
+    pub(crate) fn read(dir: &Path) -> Result<Self> {
+        if is_incomplete(dir) {
+            return Err(anyhow!(
+                "{} is an incomplete install, download it again.",
+                dir.display()
+            ));
+        }
+        Ok(serde_json::from_reader(File::open(
+            dir.join(METADATA_FILE),
+        )?)?)
+    }
+
+    /// 由各浏览器自己的平台名称（`Win_x64`、`linux-x86_64`、`mac-arm64`、`MacOS` 等）得到操作系统。
+    pub(crate) fn os(&self) -> Option<Os> {
+        let platform = self.platform.as_deref()?.to_ascii_lowercase();
+        Os::ALL
+            .into_iter()
+            .find(|os| platform.starts_with(&os.as_str()[..3]))
+    }
+
+    pub(crate) fn write(&self, dir: &Path) -> Result<()> {
+        std::fs::write(dir.join(METADATA_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn find_installs(dir: &Path) -> Result<Vec<(PathBuf, InstallMetadata)>> {
+    let mut installs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_incomplete(&path) {
+            status!("==> skipping incomplete install {}", path.display());
+            continue;
+        }
+        if path.join(METADATA_FILE).is_file() {
+            if let Ok(metadata) = InstallMetadata::read(&path) {
+                installs.push((path, metadata));
+            }
+        }
+    }
+    installs.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(installs)
+}
+
+/// 开始向安装目录写入文件前调用，直到 `mark_complete` 之前该目录都不是有效的安装。
+pub(crate) fn mark_incomplete(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(INCOMPLETE_MARKER), "")?;
+    Ok(())
+}
+
+pub(crate) fn mark_complete(dir: &Path) -> Result<()> {
+    let marker = dir.join(INCOMPLETE_MARKER);
+    if marker.exists() {
+        std::fs::remove_file(marker)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn is_incomplete(dir: &Path) -> bool {
+    dir.join(INCOMPLETE_MARKER).exists()
+}
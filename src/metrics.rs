@@ -0,0 +1,113 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+
+static DOWNLOADS: AtomicU64 = AtomicU64::new(0);
+static DOWNLOAD_BYTES: AtomicU64 = AtomicU64::new(0);
+static DOWNLOAD_FAILURES: AtomicU64 = AtomicU64::new(0);
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// 各平台、渠道最近一次成功从上游获取发布列表的时间（Unix 时间戳，秒）。
+static CHANNEL_CHECKS: Mutex<BTreeMap<(String, String), u64>> = Mutex::new(BTreeMap::new());
+
+pub(crate) fn download_finished(bytes: u64) {
+    DOWNLOADS.fetch_add(1, Ordering::Relaxed);
+    DOWNLOAD_BYTES.fetch_add(bytes, Ordering::Relaxed);
+}
+
+pub(crate) fn download_failed() {
+    DOWNLOAD_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// 读取版本列表等缓存文件时记录是否命中。
+pub(crate) fn cache_lookup(hit: bool) {
+    match hit {
+        true => CACHE_HITS.fetch_add(1, Ordering::Relaxed),
+        false => CACHE_MISSES.fetch_add(1, Ordering::Relaxed),
+    };
+}
+
+pub(crate) fn channel_checked(os: &str, channel: &str) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    CHANNEL_CHECKS
+        .lock()
+        .unwrap()
+        .insert((os.to_owned(), channel.to_owned()), now);
+}
+
+/// 以 Prometheus 文本格式输出当前的统计数据。
+pub(crate) fn render() -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} {kind}");
+        let _ = writeln!(out, "{name} {value}");
+    };
+    metric(
+        "fetchbrowser_downloads_total",
+        "counter",
+        "Archives downloaded successfully.",
+        DOWNLOADS.load(Ordering::Relaxed),
+    );
+    metric(
+        "fetchbrowser_download_bytes_total",
+        "counter",
+        "Bytes of the archives downloaded successfully.",
+        DOWNLOAD_BYTES.load(Ordering::Relaxed),
+    );
+    metric(
+        "fetchbrowser_download_failures_total",
+        "counter",
+        "Downloads that failed after all retries.",
+        DOWNLOAD_FAILURES.load(Ordering::Relaxed),
+    );
+    metric(
+        "fetchbrowser_cache_hits_total",
+        "counter",
+        "Release lists read from the cache.",
+        CACHE_HITS.load(Ordering::Relaxed),
+    );
+    metric(
+        "fetchbrowser_cache_misses_total",
+        "counter",
+        "Release lists fetched because they were not cached.",
+        CACHE_MISSES.load(Ordering::Relaxed),
+    );
+    let name = "fetchbrowser_last_channel_check_timestamp_seconds";
+    let _ = writeln!(
+        out,
+        "# HELP {name} Last time the release list of a channel was fetched from upstream."
+    );
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    for ((os, channel), timestamp) in CHANNEL_CHECKS.lock().unwrap().iter() {
+        let _ = writeln!(
+            out,
+            "{name}{{os=\"{os}\",channel=\"{channel}\"}} {timestamp}"
+        );
+    }
+    out
+}
+
+/// `--metrics-file`：运行结束时写入统计数据，供 node_exporter 的 textfile collector 等采集。
+///
+/// 先写入临时文件再改名，采集方不会读到写了一半的文件。
+pub(crate) fn write(path: &Path) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    std::fs::write(&temp, render())?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
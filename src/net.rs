@@ -0,0 +1,84 @@
+use std::{io::SeekFrom, path::Path, sync::Mutex};
+
+use anyhow::{anyhow, Result};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncSeekExt, AsyncWriteExt},
+};
+
+use crate::{
+    deadline, error::FetchError, http::Http, progress::DownloadProgress, utils::run_bounded,
+};
+
+/// 每段至少这么大，小文件分段得不偿失。
+const MIN_SEGMENT_SIZE: u64 = 4 * 1024 * 1024;
+
+/// 把文件按 `connections`（`--connections`）分成若干段并发下载，写入 `part_path` 中各自的位置。
+///
+/// 大小未知时先请求第一个字节，从 Content-Range 得到总大小。
+/// 服务端不支持 `Range`、文件太小或只用一个连接时返回 `false`，由调用方整体下载。
+pub(crate) async fn download_segmented(
+    client: &Http,
+    url: &str,
+    size: Option<u64>,
+    part_path: &Path,
+    connections: usize,
+) -> Result<bool> {
+    if connections < 2 {
+        return Ok(false);
+    }
+    let size = match size {
+        Some(size) => size,
+        None => {
+            let response = client.get_range(url, 0, 0).await?;
+            match (response.status(), response.total_length()) {
+                (206, Some(total)) => total,
+                _ => return Ok(false),
+            }
+        }
+    };
+    let segments = connections.min((size / MIN_SEGMENT_SIZE) as usize);
+    if segments < 2 {
+        return Ok(false);
+    }
+
+    status!("==> downloading {url} over {segments} connections");
+    File::create(part_path).await?.set_len(size).await?;
+    let segment_size = size.div_ceil(segments as u64);
+    let ranges: Vec<_> = (0..segments as u64)
+        .map(|index| {
+            let start = index * segment_size;
+            (start, (start + segment_size).min(size) - 1)
+        })
+        .collect();
+    let progress = Mutex::new(DownloadProgress::new(url, 0, Some(size)));
+    let progress = &progress;
+    run_bounded(ranges, segments, |(start, end)| async move {
+        let mut response = client.get_range(url, start, end).await?;
+        if response.status() != 206 {
+            return Err(FetchError::Network(format!(
+                "Server returned HTTP {} for bytes {start}-{end} of {url}",
+                response.status()
+            ))
+            .into());
+        }
+        let mut file = OpenOptions::new().write(true).open(part_path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        let mut remaining = end - start + 1;
+        while remaining > 0 {
+            deadline::check()?;
+            let Some(chunk) = response.chunk().await? else {
+                return Err(anyhow!("Connection closed at bytes {start}-{end} of {url}"));
+            };
+            let len = chunk.len().min(remaining as usize);
+            file.write_all(&chunk[..len]).await?;
+            remaining -= len as u64;
+            progress.lock().unwrap().advance(len);
+        }
+        file.flush().await?;
+        Ok(())
+    })
+    .await?;
+    progress.lock().unwrap().advance(0);
+    Ok(true)
+}
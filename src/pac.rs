@@ -0,0 +1,169 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, ToSocketAddrs, UdpSocket},
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::{ClientBuilder, Url};
+use rquickjs::{Context, Function, Runtime};
+
+/// PAC 文件中可以使用的辅助函数，`dnsResolve`、`myIpAddress` 由 Rust 实现。
+/// 时间相关的 `weekdayRange`、`dateRange`、`timeRange` 不做判断，总是返回 true。
+const PAC_UTILS: &str = r#"
+function isPlainHostName(host) { return host.indexOf('.') < 0; }
+function dnsDomainIs(host, domain) {
+    return host.length >= domain.length && host.substring(host.length - domain.length) == domain;
+}
+function localHostOrDomainIs(host, hostdom) {
+    return host == hostdom || hostdom.lastIndexOf(host + '.', 0) == 0;
+}
+function isResolvable(host) { return dnsResolve(host) != null; }
+function dnsDomainLevels(host) { return host.split('.').length - 1; }
+function convertAddr(ip) {
+    var bytes = ip.split('.');
+    return ((bytes[0] & 0xff) << 24 | (bytes[1] & 0xff) << 16 | (bytes[2] & 0xff) << 8 | (bytes[3] & 0xff)) >>> 0;
+}
+function isInNet(ipaddr, pattern, maskstr) {
+    if (!/^\d+\.\d+\.\d+\.\d+$/.test(ipaddr)) {
+        ipaddr = dnsResolve(ipaddr);
+        if (ipaddr == null) return false;
+    }
+    var mask = convertAddr(maskstr);
+    return ((convertAddr(ipaddr) & mask) >>> 0) == ((convertAddr(pattern) & mask) >>> 0);
+}
+function shExpMatch(str, pattern) {
+    pattern = pattern.replace(/[.+^${}()|[\]\\]/g, '\\$&').replace(/\*/g, '.*').replace(/\?/g, '.');
+    return new RegExp('^' + pattern + '$').test(str);
+}
+function weekdayRange() { return true; }
+function dateRange() { return true; }
+function timeRange() { return true; }
+"#;
+
+/// 根据 PAC 文件为每个请求的主机选择代理，结果按 `scheme://host` 缓存。
+pub(crate) struct PacResolver {
+    script: String,
+    cache: Mutex<HashMap<String, Option<Url>>>,
+}
+
+impl PacResolver {
+    /// `pac_url` 可以是 http(s) 地址、`file://` 地址或本地路径。
+    pub(crate) async fn load(pac_url: &str) -> Result<Self> {
+        status!("==> loading proxy auto-config from {pac_url} ...");
+        let script = match pac_url.split_once("://") {
+            Some(("http" | "https", _)) => {
+                // PAC 文件本身不经过代理获取。
+                let response = ClientBuilder::new()
+                    .no_proxy()
+                    .build()?
+                    .get(pac_url)
+                    .send()
+                    .await?;
+                if !response.status().is_success() {
+                    return Err(anyhow!(
+                        "Downloading {pac_url} failed: {}",
+                        response.status()
+                    ));
+                }
+                response.text().await?
+            }
+            Some(("file", path)) => std::fs::read_to_string(path)?,
+            Some(_) => return Err(anyhow!("Unsupported PAC url: {pac_url}")),
+            None => std::fs::read_to_string(pac_url)?,
+        };
+        let resolver = Self {
+            script,
+            cache: Mutex::new(HashMap::new()),
+        };
+        // 提前执行一次，PAC 文件有语法错误时在启动时就报错。
+        resolver
+            .evaluate("http://localhost/", "localhost")
+            .map_err(|err| anyhow!("Invalid PAC file {pac_url}: {err}"))?;
+        Ok(resolver)
+    }
+
+    /// 返回 `None` 表示直连。
+    pub(crate) fn find_proxy(&self, url: &Url) -> Option<Url> {
+        let host = url.host_str()?;
+        let key = format!("{}://{host}", url.scheme());
+        if let Some(proxy) = self.cache.lock().unwrap().get(&key) {
+            return proxy.clone();
+        }
+        let proxy = match self.evaluate(url.as_str(), host) {
+            Ok(result) => {
+                let proxy = parse_pac_result(&result);
+                log::debug!("==> PAC: {host} -> {result}");
+                proxy
+            }
+            Err(err) => {
+                status!("==> warning: evaluating the PAC file for {host} failed: {err}, connecting directly.");
+                None
+            }
+        };
+        self.cache.lock().unwrap().insert(key, proxy.clone());
+        proxy
+    }
+
+    fn evaluate(&self, url: &str, host: &str) -> Result<String> {
+        let runtime = Runtime::new()?;
+        let context = Context::full(&runtime)?;
+        context.with(|ctx| {
+            let globals = ctx.globals();
+            globals.set("dnsResolve", Function::new(ctx.clone(), dns_resolve)?)?;
+            globals.set("myIpAddress", Function::new(ctx.clone(), my_ip_address)?)?;
+            let run = || -> rquickjs::Result<String> {
+                ctx.eval::<(), _>(PAC_UTILS)?;
+                ctx.eval::<(), _>(self.script.as_str())?;
+                let find: Function = globals.get("FindProxyForURL")?;
+                find.call((url, host))
+            };
+            run().map_err(|err| match err {
+                rquickjs::Error::Exception => anyhow!("{:?}", ctx.catch()),
+                err => anyhow!(err),
+            })
+        })
+    }
+}
+
+fn dns_resolve(host: String) -> Option<String> {
+    (host.as_str(), 0)
+        .to_socket_addrs()
+        .ok()?
+        .map(|addr| addr.ip())
+        .find(IpAddr::is_ipv4)
+        .map(|ip| ip.to_string())
+}
+
+fn my_ip_address() -> String {
+    // 连接 UDP 套接字并不会发送数据，只用于取得默认路由上的本机地址。
+    UdpSocket::bind("0.0.0.0:0")
+        .and_then(|socket| {
+            socket.connect("8.8.8.8:53")?;
+            socket.local_addr()
+        })
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| "127.0.0.1".to_owned())
+}
+
+/// 解析 `PROXY host:port; SOCKS5 host:port; DIRECT` 形式的结果，取第一个支持的代理。
+fn parse_pac_result(result: &str) -> Option<Url> {
+    for entry in result.split(';') {
+        let mut parts = entry.split_whitespace();
+        let kind = parts.next().unwrap_or_default().to_ascii_uppercase();
+        let scheme = match kind.as_str() {
+            "DIRECT" => return None,
+            "PROXY" | "HTTP" => "http",
+            "HTTPS" => "https",
+            "SOCKS" | "SOCKS5" => "socks5h",
+            _ => continue,
+        };
+        if let Some(url) = parts
+            .next()
+            .and_then(|address| Url::parse(&format!("{scheme}://{address}")).ok())
+        {
+            return Some(url);
+        }
+    }
+    None
+}
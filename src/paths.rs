@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use regex::Regex;
+
+use crate::{cache::CacheSettings, chromium::HistorySource, platform::Platform};
+
+/// `--dir-name` 中可用的占位符。
+const DIR_NAME_PLACEHOLDERS: [&str; 5] = ["browser", "version", "major", "os", "arch"];
+
+/// 安装目录的命名方式。
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, ValueEnum)]
+pub(crate) enum NameBy {
+    /// `chromium-114.0.5735.90`
+    #[default]
+    Version,
+    /// `chromium-M114`，完整版本号保存在 metadata.json 中。
+    Milestone,
+}
+
+/// 安装、解压和缓存使用的目录。
+///
+/// 依次取命令行参数、环境变量（`FETCHBROWSER_OUTPUT_DIR`、`FETCHBROWSER_TEMP_DIR`、
+/// `FETCHBROWSER_CACHE_DIR`）和默认值，相对路径在启动时按当前目录解析，之后不再依赖当前目录。
+#[derive(Debug, Clone)]
+pub(crate) struct Paths {
+    pub(crate) output_dir: PathBuf,
+    pub(crate) temp_dir: PathBuf,
+    pub(crate) cache_dir: PathBuf,
+    pub(crate) name_by: NameBy,
+    /// `--dir-name` 指定的安装目录名模板，优先于 `name_by`。
+    pub(crate) dir_name: Option<String>,
+    /// 请求的平台，用于模板中的 `{os}` 和 `{arch}`。
+    pub(crate) platform: Option<Platform>,
+    /// 版本列表缓存的有效期和签名密钥。
+    pub(crate) cache: CacheSettings,
+    /// `--history-source`。
+    pub(crate) history_source: HistorySource,
+}
+
+impl Paths {
+    pub(crate) fn resolve(
+        output_dir: Option<&Path>,
+        temp_dir: Option<&Path>,
+        cache_dir: Option<&Path>,
+    ) -> Result<Self> {
+        let cur_dir = std::env::current_dir()?;
+        let pick = |flag: Option<&Path>, env: &str| {
+            flag.map(Path::to_path_buf)
+                .or_else(|| std::env::var_os(env).map(PathBuf::from))
+                .map(|path| cur_dir.join(path))
+        };
+        let output_dir = pick(output_dir, "FETCHBROWSER_OUTPUT_DIR").unwrap_or(cur_dir.clone());
+        let temp_dir = pick(temp_dir, "FETCHBROWSER_TEMP_DIR").unwrap_or_else(std::env::temp_dir);
+        let cache_dir = match pick(cache_dir, "FETCHBROWSER_CACHE_DIR") {
+            Some(dir) => dir,
+            None => default_cache_dir()?,
+        };
+        Ok(Self {
+            output_dir,
+            temp_dir,
+            cache_dir,
+            name_by: NameBy::default(),
+            dir_name: None,
+            platform: None,
+            cache: CacheSettings::default(),
+            history_source: HistorySource::default(),
+        })
+    }
+
+    /// 浏览器安装到输出目录下的哪个目录，保留的压缩包和临时目录仍使用完整版本号命名。
+    pub(crate) fn install_dir(&self, browser: &str, version: &str) -> PathBuf {
+        let major = version.split('.').next().unwrap_or(version);
+        let name = match (&self.dir_name, self.name_by) {
+            (Some(template), _) => template
+                .replace("{browser}", browser)
+                .replace("{version}", version)
+                .replace("{major}", major)
+                .replace("{os}", self.platform.map_or("", |p| p.os().as_str()))
+                .replace("{arch}", self.platform.map_or("", |p| p.arch().as_str())),
+            (None, NameBy::Version) => format!("{browser}-{version}"),
+            (None, NameBy::Milestone) => format!("{browser}-M{major}"),
+        };
+        self.output_dir.join(name)
+    }
+
+    /// 安装前解压用的临时目录，与安装目录一样由锁文件保护。
+    pub(crate) fn temp_install_dir(&self, browser: &str, version: &str) -> PathBuf {
+        self.temp_dir.join(format!(".tmp-{browser}-{version}"))
+    }
+
+    pub(crate) fn cached_file(&self, file: &str) -> Result<PathBuf> {
+        if !self.cache_dir.exists() {
+            std::fs::create_dir_all(&self.cache_dir)?;
+        }
+        Ok(self.cache_dir.join(file))
+    }
+}
+
+/// 检查 `--dir-name` 模板：只能使用已知的占位符，且只能是输出目录下的一级目录名。
+/// 不含 `{browser}` 或 `{version}` 时，不同的浏览器或版本会安装到同一个目录，后装的覆盖先装的。
+pub(crate) fn validate_dir_name(template: &str) -> Result<()> {
+    let placeholder = Regex::new(r"\{([^{}]*)\}").unwrap();
+    let names: Vec<_> = placeholder
+        .captures_iter(template)
+        .map(|captures| captures[1].to_owned())
+        .collect();
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !DIR_NAME_PLACEHOLDERS.contains(&name.as_str()))
+    {
+        return Err(anyhow!(
+            "Unknown placeholder {{{unknown}}} in --dir-name, expected one of {}.",
+            DIR_NAME_PLACEHOLDERS
+                .map(|name| format!("{{{name}}}"))
+                .join(", ")
+        ));
+    }
+    if template.contains(['/', '\\']) || template.starts_with('.') {
+        return Err(anyhow!(
+            "--dir-name must be a single directory name, got {template}."
+        ));
+    }
+    Ok(())
+}
+
+/// 改名为 fetchbrowser 之前使用的缓存目录，启动时迁移其中仍然有效的文件后删除。
+pub(crate) fn legacy_cache_dir() -> Result<PathBuf> {
+    let mut path = PathBuf::new();
+    path.push(std::env::var("LOCALAPPDATA").or_else(|_| std::env::var("HOME"))?);
+    path.push("fetchchromium");
+    Ok(path)
+}
+
+pub(crate) fn default_cache_dir() -> Result<PathBuf> {
+    let mut path = PathBuf::new();
+    path.push(std::env::var("LOCALAPPDATA").or_else(|_| std::env::var("HOME"))?);
+    path.push("fetchbrowser");
+    Ok(path)
+}
@@ -1,23 +1,40 @@
 use std::str::FromStr;
 
-use anyhow::anyhow;
+use crate::error::FetchError;
 
+/// 目标平台：操作系统和架构。
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub(crate) struct Platform(Os, Arch);
+pub struct Platform(Os, Arch);
 
 impl Platform {
-    pub(crate) fn new(os: Os, arch: Arch) -> Self {
+    pub fn new(os: Os, arch: Arch) -> Self {
         Self(os, arch)
     }
 
+    pub fn os(&self) -> Os {
+        self.0
+    }
+
+    pub fn arch(&self) -> Arch {
+        self.1
+    }
+
+    /// 快照 bucket 中的目录名，也用于缓存文件名和 metadata。
+    ///
+    /// Linux 没有发布 arm64 的快照，`Linux_Arm64` 只是名称，下载快照前由 [`BuildVariant::prefix`] 拒绝。
+    ///
+    /// [`BuildVariant::prefix`]: crate::common::BuildVariant::prefix
     pub(crate) fn prefix(&self) -> &'static str {
         match (self.0, self.1) {
             (Os::Windows, Arch::X86) => "Win",
             (Os::Windows, Arch::X86_64) => "Win_x64",
+            (Os::Windows, Arch::Arm64) => "Win_Arm64",
             (Os::Linux, Arch::X86) => "Linux",
             (Os::Linux, Arch::X86_64) => "Linux_x64",
+            (Os::Linux, Arch::Arm64) => "Linux_Arm64",
             (Os::Mac, Arch::X86) => "Mac",
             (Os::Mac, Arch::X86_64) => "Mac",
+            (Os::Mac, Arch::Arm64) => "Mac_Arm",
         }
     }
 
@@ -25,10 +42,13 @@ impl Platform {
         match (self.0, self.1) {
             (Os::Windows, Arch::X86) => "win",
             (Os::Windows, Arch::X86_64) => "win64",
+            (Os::Windows, Arch::Arm64) => "win64",
             (Os::Linux, Arch::X86) => "linux",
             (Os::Linux, Arch::X86_64) => "linux",
+            (Os::Linux, Arch::Arm64) => "linux",
             (Os::Mac, Arch::X86) => "mac",
             (Os::Mac, Arch::X86_64) => "mac",
+            (Os::Mac, Arch::Arm64) => "mac",
         }
     }
 
@@ -38,12 +58,24 @@ impl Platform {
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub(crate) enum Os {
+pub enum Os {
     Windows,
     Linux,
     Mac,
 }
 
+impl Os {
+    pub(crate) const ALL: [Os; 3] = [Os::Windows, Os::Linux, Os::Mac];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Os::Windows => "windows",
+            Os::Linux => "linux",
+            Os::Mac => "macos",
+        }
+    }
+}
+
 impl FromStr for Os {
     type Err = anyhow::Error;
 
@@ -52,13 +84,59 @@ impl FromStr for Os {
             "windows" => Ok(Self::Windows),
             "linux" => Ok(Self::Linux),
             "macos" => Ok(Self::Mac),
-            _ => Err(anyhow!("Unsupported OS: {}", s)),
+            _ => Err(FetchError::UnsupportedPlatform(format!("Unsupported OS: {s}")).into()),
         }
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub(crate) enum Arch {
+pub enum Arch {
     X86,
     X86_64,
+    Arm64,
+}
+
+impl Arch {
+    pub(crate) const ALL: [Arch; 3] = [Arch::X86, Arch::X86_64, Arch::Arm64];
+
+    /// 当前主机的架构，其他架构按 x64 处理，可以用 `--arch` 指定。
+    pub fn host() -> Self {
+        match std::env::consts::ARCH {
+            "aarch64" => Arch::Arm64,
+            "x86" => Arch::X86,
+            _ => Arch::X86_64,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Arch::X86 => "x86",
+            Arch::X86_64 => "x64",
+            Arch::Arm64 => "arm64",
+        }
+    }
+
+    /// 找不到当前架构的版本时，依次尝试的其他架构（依赖系统的模拟运行）。
+    pub(crate) fn fallbacks(&self) -> &'static [Arch] {
+        match self {
+            Arch::X86 => &[],
+            Arch::X86_64 => &[Arch::X86],
+            Arch::Arm64 => &[Arch::X86_64, Arch::X86],
+        }
+    }
+}
+
+impl FromStr for Arch {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x86" => Ok(Self::X86),
+            "x64" | "x86_64" => Ok(Self::X86_64),
+            "arm64" | "aarch64" => Ok(Self::Arm64),
+            _ => Err(
+                FetchError::UnsupportedPlatform(format!("Unsupported architecture: {s}")).into(),
+            ),
+        }
+    }
 }
@@ -0,0 +1,166 @@
+use std::{
+    io::Write,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use log::Level;
+
+/// `--quiet`：不显示下载和解压的进度。作为库使用时不向标准输出写进度，命令行按参数关闭。
+static QUIET: AtomicBool = AtomicBool::new(true);
+
+/// 每解压一个文件后暂停的毫秒数，给杀毒软件留出扫描的时间。
+static EXTRACT_THROTTLE_MS: AtomicU64 = AtomicU64::new(0);
+
+/// 逐个列出解压的文件，见 [`crate::summary::init_logger`]。
+fn verbose() -> bool {
+    log::log_enabled!(Level::Debug)
+}
+
+pub(crate) fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_extract_throttle(millis: u64) {
+    EXTRACT_THROTTLE_MS.store(millis, Ordering::Relaxed);
+}
+
+pub(crate) struct ExtractProgress {
+    total: Option<usize>,
+    files: usize,
+    bytes: u64,
+    last_print: Option<Instant>,
+}
+
+impl ExtractProgress {
+    pub(crate) fn new(total: Option<usize>) -> Self {
+        Self {
+            total,
+            files: 0,
+            bytes: 0,
+            last_print: None,
+        }
+    }
+
+    pub(crate) fn file(&mut self, name: &str, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+        match EXTRACT_THROTTLE_MS.load(Ordering::Relaxed) {
+            0 => {}
+            millis => std::thread::sleep(Duration::from_millis(millis)),
+        }
+        if verbose() {
+            log::debug!("==> unzip: {name}");
+        } else if !quiet()
+            && self
+                .last_print
+                .is_none_or(|t| t.elapsed() >= Duration::from_millis(100))
+        {
+            self.last_print = Some(Instant::now());
+            self.print();
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        if !verbose() && !quiet() {
+            self.print();
+            println!();
+        }
+    }
+
+    fn print(&self) {
+        let files = match self.total {
+            Some(total) => format!("{}/{total}", self.files),
+            None => self.files.to_string(),
+        };
+        print!(
+            "\r==> extracting: {files} files, {:.1} MB",
+            self.bytes as f64 / 1024.0 / 1024.0
+        );
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// 读取响应内容时显示已下载的大小、速度和剩余时间。
+pub(crate) struct DownloadProgress {
+    name: String,
+    /// 续传时已有的字节数，不计入速度。
+    resumed: u64,
+    bytes: u64,
+    total: Option<u64>,
+    started: Instant,
+    last_print: Option<Instant>,
+    finished: bool,
+}
+
+impl DownloadProgress {
+    pub(crate) fn new(url: &str, resumed: u64, total: Option<u64>) -> Self {
+        Self {
+            name: url.rsplit('/').next().unwrap_or(url).to_owned(),
+            resumed,
+            bytes: 0,
+            total,
+            started: Instant::now(),
+            last_print: None,
+            finished: false,
+        }
+    }
+
+    fn print(&self) {
+        let mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+        let downloaded = self.resumed + self.bytes;
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let speed = match elapsed > 0.0 {
+            true => self.bytes as f64 / elapsed,
+            false => 0.0,
+        };
+        let size = match self.total {
+            Some(total) => format!(
+                "{:.1}/{:.1} MB ({}%)",
+                mb(downloaded),
+                mb(total),
+                downloaded * 100 / total.max(1)
+            ),
+            None => format!("{:.1} MB", mb(downloaded)),
+        };
+        let eta = match self.total {
+            Some(total) if speed > 0.0 && !self.finished => format!(
+                ", ETA {}s",
+                (total.saturating_sub(downloaded) as f64 / speed).ceil()
+            ),
+            _ => String::new(),
+        };
+        print!(
+            "\r==> downloading {}: {size}, {:.1} MB/s{eta}   ",
+            self.name,
+            speed / 1024.0 / 1024.0
+        );
+        let _ = std::io::stdout().flush();
+    }
+}
+
+impl DownloadProgress {
+    /// 记录新收到的字节数，`len` 为 0 表示下载结束。
+    /// 分段下载时各段共用一个进度，通过这里汇总。
+    pub(crate) fn advance(&mut self, len: usize) {
+        self.bytes += len as u64;
+        if quiet() || self.finished {
+            return;
+        }
+        if len == 0 {
+            self.finished = true;
+            self.print();
+            println!();
+        } else if self
+            .last_print
+            .is_none_or(|t| t.elapsed() >= Duration::from_millis(200))
+        {
+            self.last_print = Some(Instant::now());
+            self.print();
+        }
+    }
+}
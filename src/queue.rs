@@ -0,0 +1,89 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{metadata::is_incomplete, paths::Paths};
+
+/// 缓存目录下保存下载队列的子目录。
+pub(crate) const QUEUE_DIR: &str = "queues";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueState {
+    command: String,
+    /// 已完成的项及其安装目录。
+    finished: BTreeMap<String, PathBuf>,
+}
+
+/// 一次运行中有多项下载（多个 `--os`、多个浏览器、`fb sync`）时记录已完成的项。
+///
+/// 进程被终止后重新运行同样的命令，已完成且安装目录仍然完整的项直接跳过，
+/// 未完成的项重新解析并下载，下载到一半的文件由下载管理器的 `.part` 文件续传。
+/// 全部完成后删除队列文件。
+pub(crate) struct DownloadQueue {
+    path: PathBuf,
+    state: Mutex<QueueState>,
+}
+
+impl DownloadQueue {
+    /// 队列文件以命令行参数和输出目录的摘要命名，只有同样的命令才会续用。
+    pub(crate) fn open(paths: &Paths) -> Self {
+        let command = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+        let key = format!("{command}\n{}", paths.output_dir.display());
+        let name = format!("{:x}", Sha256::digest(key.as_bytes()));
+        let path = paths
+            .cache_dir
+            .join(QUEUE_DIR)
+            .join(format!("{}.json", &name[..16]));
+        let state = std::fs::read(&path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<QueueState>(&content).ok())
+            .filter(|state| state.command == command)
+            .unwrap_or(QueueState {
+                command,
+                finished: BTreeMap::new(),
+            });
+        if !state.finished.is_empty() {
+            status!(
+                "==> resuming an interrupted run, {} item(s) already finished",
+                state.finished.len()
+            );
+        }
+        Self {
+            path,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// 已完成的项返回其安装目录，安装目录已被删除或不完整时按未完成处理。
+    pub(crate) fn finished(&self, item: &str) -> Option<PathBuf> {
+        let state = self.state.lock().unwrap();
+        let path = state.finished.get(item)?;
+        if !path.is_dir() || is_incomplete(path) {
+            return None;
+        }
+        status!("==> {item} already finished: {}", path.display());
+        Some(path.clone())
+    }
+
+    pub(crate) fn mark_finished(&self, item: &str, path: &Path) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.finished.insert(item.to_owned(), path.to_owned());
+        std::fs::create_dir_all(self.path.parent().unwrap())?;
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&*state)?)?;
+        Ok(())
+    }
+
+    /// 全部完成后删除队列文件，下次运行从头开始。
+    pub(crate) fn complete(self) -> Result<()> {
+        if self.path.exists() {
+            std::fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(crate) enum SchemaName {
+    /// 安装目录中的 metadata.json。
+    Metadata,
+    /// 离线包中的 bundle.json。
+    Bundle,
+    /// `--format json` 的运行摘要。
+    Summary,
+    /// `--format json-lines` 运行过程中输出的事件。
+    Events,
+    /// `--dry-run --format json` 输出的解析结果。
+    Resolve,
+}
+
+impl SchemaName {
+    pub(crate) fn content(&self) -> &'static str {
+        match self {
+            SchemaName::Metadata => include_str!("../schemas/metadata.v1.json"),
+            SchemaName::Bundle => include_str!("../schemas/bundle.v1.json"),
+            SchemaName::Summary => include_str!("../schemas/summary.v1.json"),
+            SchemaName::Events => include_str!("../schemas/events.v1.json"),
+            SchemaName::Resolve => include_str!("../schemas/resolve.v1.json"),
+        }
+    }
+}
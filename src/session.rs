@@ -0,0 +1,116 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use reqwest::ClientBuilder;
+
+use crate::{
+    cache::CacheSettings,
+    chromium::HistorySource,
+    config::Config,
+    downloads::{DownloadManager, Downloads},
+    http::{Http, RateLimitedTransport, ReqwestTransport},
+    paths::Paths,
+};
+
+/// 库的使用者创建的下载环境：HTTP 客户端、下载管理器以及安装、临时和缓存目录。
+///
+/// 不读取命令行的配置文件，请求频率限制使用内置的默认值。
+#[derive(Clone)]
+pub struct Session {
+    pub(crate) client: Http,
+    pub(crate) downloads: Downloads,
+    pub(crate) paths: Paths,
+}
+
+/// [`Session`] 的下载和缓存设置，对应命令行的 `--connections`、`--cache-ttl` 等参数。
+#[derive(Debug, Clone)]
+pub struct SessionSettings {
+    pub(crate) connections: usize,
+    pub(crate) cache: CacheSettings,
+    pub(crate) history_source: HistorySource,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        Self {
+            connections: 1,
+            cache: CacheSettings::default(),
+            history_source: HistorySource::default(),
+        }
+    }
+}
+
+impl SessionSettings {
+    pub fn builder() -> SessionSettingsBuilder {
+        SessionSettingsBuilder::default()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SessionSettingsBuilder(SessionSettings);
+
+impl SessionSettingsBuilder {
+    /// 单个文件同时使用的连接数，默认为 1，即不分段下载。
+    pub fn connections(mut self, connections: usize) -> Self {
+        self.0.connections = connections.max(1);
+        self
+    }
+
+    /// 版本列表缓存的有效期，默认为 24 小时，为 0 时永不过期。
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.0.cache.set_ttl(ttl);
+        self
+    }
+
+    /// 用该密钥为缓存文件签名，读取时校验，签名无效的缓存重新获取。
+    pub fn cache_signing_key(mut self, key: impl Into<String>) -> Self {
+        self.0.cache.set_signing_key(Some(key.into()));
+        self
+    }
+
+    /// 获取 Chromium 发布列表的数据源，默认为 [`HistorySource::Auto`]。
+    pub fn history_source(mut self, source: HistorySource) -> Self {
+        self.0.history_source = source;
+        self
+    }
+
+    pub fn build(self) -> SessionSettings {
+        self.0
+    }
+}
+
+impl Session {
+    /// 安装到 `output_dir`，`proxy` 形如 `socks5h://127.0.0.1:10801`。
+    /// 临时目录和缓存目录与命令行相同，可以通过环境变量指定。
+    pub fn new(output_dir: &Path, proxy: Option<&str>) -> Result<Self> {
+        Self::with_settings(output_dir, proxy, SessionSettings::default())
+    }
+
+    /// 与 [`Session::new`] 相同，使用 `settings` 中的下载和缓存设置。
+    pub fn with_settings(
+        output_dir: &Path,
+        proxy: Option<&str>,
+        settings: SessionSettings,
+    ) -> Result<Self> {
+        let builder = match proxy {
+            Some(proxy) => ClientBuilder::new().proxy(reqwest::Proxy::all(proxy)?),
+            None => ClientBuilder::new(),
+        };
+        let client: Http = Arc::new(RateLimitedTransport::new(
+            Arc::new(ReqwestTransport::new(builder.build()?)),
+            &Config::default().rate_limits(),
+        ));
+        let mut paths = Paths::resolve(Some(output_dir), None, None)?;
+        paths.cache = settings.cache;
+        paths.history_source = settings.history_source;
+        let downloads = Arc::new(
+            DownloadManager::new(client.clone(), &paths.temp_dir, 4)
+                .with_connections(settings.connections),
+        );
+        Ok(Self {
+            client,
+            downloads,
+            paths,
+        })
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::metadata::InstallMetadata;
+
+pub(crate) fn ensure_supported() -> Result<()> {
+    if cfg!(windows) {
+        Ok(())
+    } else {
+        Err(anyhow!("--create-shortcut is only supported on Windows."))
+    }
+}
+
+pub(crate) fn create_shortcut(install_dir: &Path) -> Result<()> {
+    let metadata = InstallMetadata::read(install_dir)?;
+    let executable = metadata
+        .executable
+        .as_deref()
+        .ok_or_else(|| anyhow!("No executable recorded for {}", install_dir.display()))?;
+    let target = install_dir.join(executable);
+    let profile = install_dir.join("profile");
+    // 每个版本使用独立的用户目录，避免不同版本之间互相污染配置。
+    let arguments = match metadata.browser.as_str() {
+        "firefox" | "firefox-devedition" => {
+            format!("-no-remote -profile \"{}\"", profile.display())
+        }
+        _ => format!("--user-data-dir=\"{}\"", profile.display()),
+    };
+    let name = format!("{} {}", capitalize(&metadata.browser), metadata.version);
+
+    let script = r#"
+$shell = New-Object -ComObject WScript.Shell
+$startMenu = Join-Path ([Environment]::GetFolderPath('Programs')) 'fetchbrowser'
+New-Item -ItemType Directory -Force -Path $startMenu | Out-Null
+foreach ($dir in @([Environment]::GetFolderPath('Desktop'), $startMenu)) {
+    $lnk = $shell.CreateShortcut((Join-Path $dir ($env:FB_LNK_NAME + '.lnk')))
+    $lnk.TargetPath = $env:FB_LNK_TARGET
+    $lnk.Arguments = $env:FB_LNK_ARGS
+    $lnk.WorkingDirectory = $env:FB_LNK_WORKDIR
+    $lnk.Save()
+}
+"#;
+    let status = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .env("FB_LNK_NAME", &name)
+        .env("FB_LNK_TARGET", &target)
+        .env("FB_LNK_ARGS", &arguments)
+        .env("FB_LNK_WORKDIR", install_dir)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("Creating shortcut failed: {status}"));
+    }
+    status!("==> shortcut created: {name}");
+    Ok(())
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    diagnostic::ResolutionDiagnostic,
+    error::ErrorCategory,
+    http::received_bytes,
+    metadata::{InstallMetadata, SnapshotObject},
+};
+
+/// `--format`：结束时输出的摘要格式。
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, ValueEnum)]
+#[repr(u8)]
+pub(crate) enum OutputFormat {
+    /// 一行 `fetchbrowser-summary key=value ...`。
+    #[default]
+    Text,
+    /// 最后一行输出一个 JSON 文档，运行日志写到标准错误。
+    Json,
+    /// 与 `json` 相同，运行过程中另外按行输出 JSON 事件。
+    JsonLines,
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(OutputFormat::Text as u8);
+
+pub(crate) fn set_format(format: OutputFormat) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+fn format() -> OutputFormat {
+    match FORMAT.load(Ordering::Relaxed) {
+        1 => OutputFormat::Json,
+        2 => OutputFormat::JsonLines,
+        _ => OutputFormat::Text,
+    }
+}
+
+/// 运行日志使用的 `log` target，库的使用者可以按它过滤。
+pub(crate) const LOG_TARGET: &str = "fetchbrowser";
+
+/// 通过 `log` 记录一行运行日志，`==> warning` 和 `==> error` 开头的分别为 Warn 和 Error，其余为 Info。
+/// 库本身不输出，由使用者安装的 logger 决定是否显示；命令行使用 [`StatusLogger`]。
+pub(crate) fn status_line(args: fmt::Arguments) {
+    let line = args.to_string();
+    let level = if line.starts_with("==> warning") {
+        Level::Warn
+    } else if line.starts_with("==> error") {
+        Level::Error
+    } else {
+        Level::Info
+    };
+    log::log!(target: LOG_TARGET, level, "{line}");
+}
+
+/// 命令行的 logger，原样输出本 crate 的日志，忽略依赖的日志。
+/// `--format json`/`json-lines` 时写到标准错误，标准输出只有 JSON，可以直接解析。
+struct StatusLogger;
+
+impl Log for StatusLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let target = metadata.target();
+        target == LOG_TARGET
+            || target
+                .strip_prefix(LOG_TARGET)
+                .is_some_and(|rest| rest.starts_with("::"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match format() {
+            OutputFormat::Text => println!("{}", record.args()),
+            OutputFormat::Json | OutputFormat::JsonLines => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// 安装命令行的 logger，`--verbose` 时同时输出 Debug 级别的日志。
+pub(crate) fn init_logger(verbose: bool) {
+    if log::set_logger(&StatusLogger).is_ok() {
+        log::set_max_level(match verbose {
+            true => LevelFilter::Debug,
+            false => LevelFilter::Info,
+        });
+    }
+}
+
+/// `--format json-lines` 时输出一行事件，例如 `{"event":"download-finished","url":...}`。
+pub(crate) fn emit_event(event: &str, fields: Value) {
+    if format() != OutputFormat::JsonLines {
+        return;
+    }
+    let mut line = json!({ "event": event });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+    println!("{line}");
+}
+
+/// JSON 摘要中的一个安装。
+#[derive(Debug, Serialize)]
+struct InstallReport {
+    browser: String,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    platform: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revision: Option<usize>,
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    executable: Option<PathBuf>,
+    /// Chromium 快照压缩包的 GCS 对象。
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snapshot_object: Option<SnapshotObject>,
+}
+
+/// `--dry-run` 解析出的一个安装，JSON 格式见 `schemas/resolve.v1.json`。
+#[derive(Debug, Serialize)]
+pub(crate) struct PlanReport {
+    pub(crate) browser: String,
+    pub(crate) version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) revision: Option<usize>,
+    pub(crate) path: PathBuf,
+    pub(crate) files: Vec<PlannedFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PlannedFile {
+    pub(crate) url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) size: Option<u64>,
+    pub(crate) cached: bool,
+}
+
+/// `--format json`/`json-lines` 时输出 `--dry-run` 的结果，文本格式已在解析过程中输出。
+pub(crate) fn print_plans(plans: &[PlanReport]) {
+    if format() != OutputFormat::Text {
+        println!("{}", json!({ "schema_version": 1, "plans": plans }));
+    }
+}
+
+/// 结束时按 `--format` 输出摘要。
+pub(crate) fn print_summary(result: &Result<Vec<PathBuf>>, elapsed: Duration) {
+    match format() {
+        OutputFormat::Text => print_text_summary(result, elapsed),
+        OutputFormat::Json | OutputFormat::JsonLines => print_json_summary(result, elapsed),
+    }
+}
+
+/// 输出一行 JSON，格式见 `schemas/summary.v1.json`。
+fn print_json_summary(result: &Result<Vec<PathBuf>>, elapsed: Duration) {
+    let mut document = json!({
+        "schema_version": 1,
+        "duration_secs": elapsed.as_secs_f64(),
+        "bytes": received_bytes(),
+    });
+    match result {
+        Ok(installed) => {
+            let installs: Vec<_> = installed
+                .iter()
+                .map(|path| {
+                    let metadata = InstallMetadata::read(path).unwrap_or_default();
+                    InstallReport {
+                        executable: metadata.executable.as_ref().map(|exe| path.join(exe)),
+                        browser: metadata.browser,
+                        version: metadata.version,
+                        channel: metadata.channel,
+                        platform: metadata.platform,
+                        arch: metadata.arch,
+                        revision: metadata.revision,
+                        path: path.clone(),
+                        snapshot_object: metadata.snapshot_object,
+                    }
+                })
+                .collect();
+            document["status"] = json!("ok");
+            document["installs"] = json!(installs);
+        }
+        Err(err) => {
+            document["status"] = json!("error");
+            document["error"] = json!(err.to_string());
+            document["error_category"] = json!(ErrorCategory::of(err).name());
+            if let Some(diagnostic) = err.downcast_ref::<ResolutionDiagnostic>() {
+                document["diagnostic"] = json!(diagnostic);
+            }
+        }
+    }
+    println!("{document}");
+}
+
+// 输出一行便于日志系统解析的 key=value 摘要。
+fn print_text_summary(result: &Result<Vec<PathBuf>>, elapsed: Duration) {
+    let mut fields = Vec::new();
+    match result {
+        Ok(installed) => {
+            let metadata: Vec<_> = installed
+                .iter()
+                .map(|path| InstallMetadata::read(path).ok())
+                .collect();
+            let join = |f: &dyn Fn(&InstallMetadata) -> Option<String>| {
+                metadata
+                    .iter()
+                    .map(|m| m.as_ref().and_then(f).unwrap_or_default())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            fields.push(("status", "ok".to_owned()));
+            fields.push(("browser", join(&|m| Some(m.browser.clone()))));
+            fields.push(("version", join(&|m| Some(m.version.clone()))));
+            fields.push(("arch", join(&|m| m.arch.clone())));
+            fields.push(("revision", join(&|m| m.revision.map(|r| r.to_string()))));
+            // 路径中可能含有逗号，每个安装目录单独输出一行。
+            for path in installed {
+                println!(
+                    "fetchbrowser-installed path={}",
+                    quote(&path.display().to_string())
+                );
+            }
+        }
+        Err(err) => {
+            fields.push(("status", "error".to_owned()));
+            fields.push(("error", err.to_string()));
+            fields.push(("category", ErrorCategory::of(err).name().to_owned()));
+        }
+    }
+    fields.push(("duration", format!("{:.1}s", elapsed.as_secs_f64())));
+    fields.push(("bytes", received_bytes().to_string()));
+
+    let line = fields
+        .into_iter()
+        .map(|(key, value)| format!("{key}={}", quote(&value)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    println!("fetchbrowser-summary {line}");
+}
+
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains([' ', '"', '=']) {
+        format!("{value:?}")
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::ValueEnum;
+
+    use crate::schema::SchemaName;
+
+    use super::*;
+
+    fn schema(name: SchemaName) -> Value {
+        serde_json::from_str(name.content()).unwrap()
+    }
+
+    /// `value` 的每个字段都必须在 schema 的 `properties` 中声明。
+    fn assert_declared(value: &Value, schema: &Value) {
+        let properties = schema["properties"].as_object().unwrap();
+        for key in value.as_object().unwrap().keys() {
+            assert!(
+                properties.contains_key(key),
+                "{key} is missing from the schema"
+            );
+        }
+    }
+
+    #[test]
+    fn schemas_are_valid_json() {
+        for name in SchemaName::value_variants() {
+            let schema = schema(*name);
+            assert_eq!(
+                schema["$schema"],
+                "https://json-schema.org/draft/2020-12/schema"
+            );
+        }
+    }
+
+    #[test]
+    fn summary_matches_schema() {
+        let summary = schema(SchemaName::Summary);
+        let report = json!(InstallReport {
+            browser: "chromium".to_owned(),
+            version: "114.0.5735.106".to_owned(),
+            channel: Some("Stable".to_owned()),
+            platform: Some("Linux_x64".to_owned()),
+            arch: Some("x64".to_owned()),
+            revision: Some(1135580),
+            path: PathBuf::from("chromium-114.0.5735.106"),
+            executable: Some(PathBuf::from("chromium-114.0.5735.106/chrome")),
+            snapshot_object: None,
+        });
+        assert_declared(&report, &summary["properties"]["installs"]["items"]);
+        let document = json!({
+            "schema_version": 1,
+            "status": "error",
+            "duration_secs": 0.0,
+            "bytes": 0,
+            "error": "",
+            "error_category": ErrorCategory::Other.name(),
+            "diagnostic": {},
+        });
+        assert_declared(&document, &summary);
+    }
+
+    #[test]
+    fn plans_match_schema() {
+        let resolve = schema(SchemaName::Resolve);
+        let plan = json!(PlanReport {
+            browser: "chromium".to_owned(),
+            version: "114.0.5735.106".to_owned(),
+            revision: Some(1135580),
+            path: PathBuf::from("chromium-114.0.5735.106"),
+            files: vec![PlannedFile {
+                url: "https://example.com/chrome-linux.zip".to_owned(),
+                size: Some(1),
+                cached: false,
+            }],
+        });
+        let plans = &resolve["properties"]["plans"]["items"];
+        assert_declared(&plan, plans);
+        assert_declared(&plan["files"][0], &plans["properties"]["files"]["items"]);
+    }
+
+    #[test]
+    fn events_are_declared() {
+        let events = schema(SchemaName::Events);
+        let declared: Vec<_> = events["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["properties"]["event"]["const"].as_str().unwrap())
+            .collect();
+        for source in [include_str!("downloads.rs"), include_str!("cli.rs")] {
+            for (index, _) in source.match_indices("emit_event(") {
+                let name = source[index..].split('"').nth(1).unwrap();
+                assert!(
+                    declared.contains(&name),
+                    "event {name} is missing from the schema"
+                );
+            }
+        }
+    }
+}
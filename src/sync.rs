@@ -0,0 +1,291 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    metadata::{find_installs, InstallMetadata},
+    platform::{Arch, Os, Platform},
+    version::VersionSpec,
+};
+
+/// `fb sync` 使用的清单文件，列出输出目录中应当安装的浏览器。
+///
+/// ```toml
+/// [[browsers]]
+/// browser = "chromium"
+/// version = "114"
+/// os = "windows"
+/// arch = "x64"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct SyncManifest {
+    #[serde(default)]
+    pub(crate) browsers: Vec<SyncEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct SyncEntry {
+    /// `chromium`、`chromium-asan`、`chrome`、`firefox`、`edge` 或配置文件中的自定义 provider。
+    pub(crate) browser: String,
+    /// 版本号或前缀，已安装的版本与之匹配即视为最新。
+    pub(crate) version: String,
+    /// 目标操作系统和架构，未指定时使用 `--os`、`--arch` 或当前主机的。
+    #[serde(default)]
+    pub(crate) os: Option<String>,
+    #[serde(default)]
+    pub(crate) arch: Option<String>,
+}
+
+impl SyncEntry {
+    pub(crate) fn platform(&self, default: Platform) -> Result<Platform> {
+        Ok(Platform::new(
+            self.os.as_deref().map_or(Ok(default.os()), Os::from_str)?,
+            self.arch
+                .as_deref()
+                .map_or(Ok(default.arch()), Arch::from_str)?,
+        ))
+    }
+
+    /// 只比较清单中明确指定的操作系统和架构，metadata 中没有记录的视为匹配。
+    fn matches_platform(&self, platform: Platform, metadata: &InstallMetadata) -> bool {
+        let os_matches = self.os.is_none() || metadata.os().is_none_or(|os| os == platform.os());
+        let arch_matches = self.arch.is_none()
+            || metadata
+                .arch
+                .as_deref()
+                .is_none_or(|arch| arch == platform.arch().as_str());
+        os_matches && arch_matches
+    }
+}
+
+impl SyncManifest {
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("Reading manifest {} failed: {err}", path.display()))?;
+        toml::from_str(&content)
+            .map_err(|err| anyhow!("Invalid manifest {}: {err}", path.display()))
+    }
+}
+
+/// 清单与输出目录中已安装的浏览器的比较结果。
+#[derive(Debug, Default)]
+pub(crate) struct SyncPlan {
+    pub(crate) install: Vec<SyncEntry>,
+    pub(crate) up_to_date: Vec<(SyncEntry, PathBuf)>,
+    /// `--prune` 时清单中没有的安装。
+    pub(crate) remove: Vec<(PathBuf, InstallMetadata)>,
+}
+
+impl SyncPlan {
+    /// 锁定文件中有记录的条目按锁定的版本比较，`platform` 为未指定平台的条目使用的平台。
+    pub(crate) fn new(
+        manifest: &SyncManifest,
+        lock: &SyncLock,
+        platform: Platform,
+        output_dir: &Path,
+        prune: bool,
+    ) -> Result<Self> {
+        let installs = match output_dir.exists() {
+            true => find_installs(output_dir)?,
+            false => Vec::new(),
+        };
+        let mut plan = Self::default();
+        let mut kept: Vec<&InstallMetadata> = Vec::new();
+        for entry in &manifest.browsers {
+            let platform = entry.platform(platform)?;
+            let spec = match lock.find(entry, platform) {
+                Some(locked) => VersionSpec::parse(&format!("={}", locked.resolved_version)),
+                None => VersionSpec::parse(&entry.version),
+            };
+            let matched: Vec<_> = installs
+                .iter()
+                .filter(|(_, metadata)| {
+                    metadata.browser == entry.browser
+                        && spec.matches(&metadata.version)
+                        && entry.matches_platform(platform, metadata)
+                })
+                .collect();
+            match matched.first() {
+                Some((dir, _)) => plan.up_to_date.push((entry.clone(), dir.clone())),
+                None => plan.install.push(entry.clone()),
+            }
+            kept.extend(matched.iter().map(|(_, metadata)| metadata));
+        }
+        if prune {
+            plan.remove = installs
+                .iter()
+                .filter(|(_, metadata)| {
+                    // 与保留的 Chromium 同一版本的 chromedriver 随之保留。
+                    !kept.iter().any(|kept| {
+                        kept.version == metadata.version
+                            && (kept.browser == metadata.browser
+                                || metadata.browser == "chromedriver")
+                    })
+                })
+                .cloned()
+                .collect();
+        }
+        Ok(plan)
+    }
+
+    pub(crate) fn print(&self) {
+        for (entry, dir) in &self.up_to_date {
+            status!(
+                "  = {} {} ({})",
+                entry.browser,
+                entry.version,
+                dir.display()
+            );
+        }
+        for entry in &self.install {
+            status!("  + {} {}", entry.browser, entry.version);
+        }
+        for (dir, metadata) in &self.remove {
+            status!(
+                "  - {} {} ({})",
+                metadata.browser,
+                metadata.version,
+                dir.display()
+            );
+        }
+        status!(
+            "==> plan: {} to install, {} up to date, {} to remove",
+            self.install.len(),
+            self.up_to_date.len(),
+            self.remove.len()
+        );
+    }
+}
+
+/// 清单旁边的锁定文件（`browsers.toml` 对应 `browsers.lock`），记录每个条目解析出的版本、
+/// 快照 revision 以及下载的文件和 SHA-256，其他机器据此下载完全相同的文件。
+///
+/// 条目按浏览器、清单中的版本和目标平台区分，不同平台的机器可以共用同一个锁定文件。
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct SyncLock {
+    #[serde(default)]
+    pub(crate) browsers: Vec<LockedEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct LockedEntry {
+    pub(crate) browser: String,
+    /// 清单中的版本（别名已替换）。
+    pub(crate) version: String,
+    /// 请求的平台。
+    pub(crate) os: String,
+    pub(crate) arch: String,
+    pub(crate) resolved_version: String,
+    /// 回退安装了其他架构时为实际安装的架构。
+    pub(crate) resolved_arch: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) revision: Option<usize>,
+    /// 为空时不校验下载的文件（例如锁定前就已安装的版本）。
+    #[serde(default)]
+    pub(crate) files: Vec<LockedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct LockedFile {
+    pub(crate) url: String,
+    pub(crate) size: u64,
+    pub(crate) sha256: String,
+}
+
+impl LockedEntry {
+    pub(crate) fn new(
+        entry: &SyncEntry,
+        platform: Platform,
+        metadata: &InstallMetadata,
+        files: Vec<LockedFile>,
+    ) -> Self {
+        Self {
+            browser: entry.browser.clone(),
+            version: entry.version.clone(),
+            os: platform.os().as_str().to_owned(),
+            arch: platform.arch().as_str().to_owned(),
+            resolved_version: metadata.version.clone(),
+            resolved_arch: metadata
+                .arch
+                .clone()
+                .unwrap_or_else(|| platform.arch().as_str().to_owned()),
+            revision: metadata.revision,
+            files,
+        }
+    }
+
+    /// 锁定的版本实际安装的平台。
+    pub(crate) fn platform(&self) -> Result<Platform> {
+        Ok(Platform::new(
+            Os::from_str(&self.os)?,
+            Arch::from_str(&self.resolved_arch)?,
+        ))
+    }
+}
+
+impl SyncLock {
+    pub(crate) fn path(manifest: &Path) -> PathBuf {
+        manifest.with_extension("lock")
+    }
+
+    /// 锁定文件不存在时为空。
+    pub(crate) fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("Reading lockfile {} failed: {err}", path.display()))?;
+        toml::from_str(&content)
+            .map_err(|err| anyhow!("Invalid lockfile {}: {err}", path.display()))
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<()> {
+        let content = format!(
+            "# Generated by `fb sync`. Delete an entry to resolve it again.\n\n{}",
+            toml::to_string(self)?
+        );
+        std::fs::write(path, content)
+            .map_err(|err| anyhow!("Writing lockfile {} failed: {err}", path.display()))
+    }
+
+    pub(crate) fn find(&self, entry: &SyncEntry, platform: Platform) -> Option<&LockedEntry> {
+        self.browsers.iter().find(|locked| {
+            locked.browser == entry.browser
+                && locked.version == entry.version
+                && locked.os == platform.os().as_str()
+                && locked.arch == platform.arch().as_str()
+        })
+    }
+
+    /// 去掉清单中已经没有的条目。
+    pub(crate) fn retain_manifest(&mut self, manifest: &SyncManifest) {
+        self.browsers.retain(|locked| {
+            manifest
+                .browsers
+                .iter()
+                .any(|entry| entry.browser == locked.browser && entry.version == locked.version)
+        });
+    }
+
+    /// 替换同一条目、同一平台原有的记录。
+    pub(crate) fn insert(&mut self, locked: LockedEntry) {
+        self.browsers.retain(|existing| {
+            (
+                &existing.browser,
+                &existing.version,
+                &existing.os,
+                &existing.arch,
+            ) != (&locked.browser, &locked.version, &locked.os, &locked.arch)
+        });
+        self.browsers.push(locked);
+    }
+}
@@ -0,0 +1,206 @@
+use std::{collections::HashMap, process::Command};
+
+use regex::{Regex, RegexBuilder};
+
+/// 操作系统中配置的代理。
+#[derive(Debug, Clone)]
+pub(crate) enum SystemProxy {
+    /// 固定的代理地址，例如 `http://proxy.example.com:8080`，以及不经过代理的主机。
+    Fixed(String, ProxyBypass),
+    /// 自动配置（PAC）文件的地址。
+    Pac(String),
+}
+
+/// 设置了这些环境变量时由 reqwest 使用，不再读取系统设置。
+const PROXY_ENV_VARS: [&str; 6] = [
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+pub(crate) fn env_proxy_set() -> bool {
+    PROXY_ENV_VARS
+        .iter()
+        .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()))
+}
+
+/// 读取系统代理设置：Windows 上依次读取 Internet 设置（WinINet）和 WinHTTP，macOS 上读取 `scutil --proxy`。
+pub(crate) fn detect() -> Option<SystemProxy> {
+    match std::env::consts::OS {
+        "windows" => detect_wininet().or_else(detect_winhttp),
+        "macos" => detect_macos(),
+        _ => None,
+    }
+}
+
+/// Windows 代理设置中的例外列表（`ProxyOverride`、WinHTTP 的 Bypass List），
+/// 以 `;` 分隔，如 `*.corp.example.com;10.*;<local>`，`<local>` 表示不含 `.` 的主机名。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ProxyBypass {
+    patterns: Vec<Regex>,
+    local: bool,
+}
+
+impl ProxyBypass {
+    pub(crate) fn parse(list: &str) -> Self {
+        let mut bypass = Self::default();
+        for entry in list
+            .split([';', ' '])
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            if entry.eq_ignore_ascii_case("<local>") {
+                bypass.local = true;
+                continue;
+            }
+            // 条目可以带协议前缀，这里不区分协议。
+            let host = entry.split_once("://").map_or(entry, |(_, host)| host);
+            let pattern = format!("^{}$", regex::escape(host).replace(r"\*", ".*"));
+            if let Ok(pattern) = RegexBuilder::new(&pattern).case_insensitive(true).build() {
+                bypass.patterns.push(pattern);
+            }
+        }
+        bypass
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.patterns.is_empty() && !self.local
+    }
+
+    /// `host` 是否直连，不经过代理。
+    pub(crate) fn matches(&self, host: &str) -> bool {
+        (self.local && !host.contains('.'))
+            || self.patterns.iter().any(|pattern| pattern.is_match(host))
+    }
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn detect_wininet() -> Option<SystemProxy> {
+    let output = command_output(
+        "reg",
+        &[
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings",
+        ],
+    )?;
+    // 每行形如 `    ProxyServer    REG_SZ    proxy:8080`。
+    let values: HashMap<&str, &str> = output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+            let _kind = parts.next().filter(|kind| kind.starts_with("REG_"))?;
+            Some((name, parts.next().unwrap_or_default()))
+        })
+        .collect();
+    if let Some(pac_url) = values.get("AutoConfigURL").filter(|url| !url.is_empty()) {
+        return Some(SystemProxy::Pac(pac_url.to_string()));
+    }
+    let enabled = values
+        .get("ProxyEnable")
+        .is_some_and(|value| *value == "0x1");
+    let server = values
+        .get("ProxyServer")
+        .filter(|server| enabled && !server.is_empty())?;
+    let bypass = ProxyBypass::parse(values.get("ProxyOverride").copied().unwrap_or_default());
+    Some(SystemProxy::Fixed(windows_proxy_url(server), bypass))
+}
+
+fn detect_winhttp() -> Option<SystemProxy> {
+    let output = command_output("netsh", &["winhttp", "show", "proxy"])?;
+    // 直连时输出 `Direct access (no proxy server).`，没有 `Proxy Server(s)` 一行。
+    let value = |wanted: &str| {
+        output.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            (name.trim() == wanted).then(|| value.trim())
+        })
+    };
+    let server = value("Proxy Server(s)")?;
+    let bypass = ProxyBypass::parse(value("Bypass List").unwrap_or_default());
+    Some(SystemProxy::Fixed(windows_proxy_url(server), bypass))
+}
+
+/// `ProxyServer` 可以是 `host:port`，也可以按协议分别设置，如 `http=a:80;https=b:443;socks=c:1080`。
+fn windows_proxy_url(server: &str) -> String {
+    let per_scheme: HashMap<&str, &str> = server
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .collect();
+    let (scheme, address) = ["https", "http", "socks"]
+        .iter()
+        .find_map(|scheme| Some((*scheme, per_scheme.get(scheme).copied()?)))
+        .unwrap_or(("http", server));
+    match (address.contains("://"), scheme) {
+        (true, _) => address.to_owned(),
+        (false, "socks") => format!("socks5://{address}"),
+        (false, _) => format!("http://{address}"),
+    }
+}
+
+fn detect_macos() -> Option<SystemProxy> {
+    let output = command_output("scutil", &["--proxy"])?;
+    // 每行形如 `  HTTPSProxy : proxy.example.com`。
+    let values: HashMap<&str, &str> = output
+        .lines()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(" : ")?;
+            Some((name.trim(), value.trim()))
+        })
+        .collect();
+    let enabled = |name: &str| values.get(name).is_some_and(|value| *value == "1");
+    if enabled("ProxyAutoConfigEnable") {
+        if let Some(pac_url) = values.get("ProxyAutoConfigURLString") {
+            return Some(SystemProxy::Pac(pac_url.to_string()));
+        }
+    }
+    // HTTPS 请求通过 HTTP 代理的 CONNECT 转发，两者的代理地址都使用 http://。
+    for (scheme, prefix) in [("http", "HTTPS"), ("http", "HTTP"), ("socks5h", "SOCKS")] {
+        if !enabled(&format!("{prefix}Enable")) {
+            continue;
+        }
+        let host = values.get(format!("{prefix}Proxy").as_str())?;
+        let url = match values.get(format!("{prefix}Port").as_str()) {
+            Some(port) => format!("{scheme}://{host}:{port}"),
+            None => format!("{scheme}://{host}"),
+        };
+        return Some(SystemProxy::Fixed(url, ProxyBypass::default()));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_scheme_servers_prefer_https() {
+        assert_eq!(windows_proxy_url("proxy:8080"), "http://proxy:8080");
+        assert_eq!(
+            windows_proxy_url("http=a:80;https=b:443;socks=c:1080"),
+            "http://b:443"
+        );
+        assert_eq!(windows_proxy_url("socks=c:1080"), "socks5://c:1080");
+    }
+
+    #[test]
+    fn bypass_list_matches_wildcards_and_local() {
+        let bypass = ProxyBypass::parse("*.corp.example.com;10.*; <local>;http://exact.org");
+        assert!(bypass.matches("build.CORP.example.com"));
+        assert!(bypass.matches("10.1.2.3"));
+        assert!(bypass.matches("intranet"));
+        assert!(bypass.matches("exact.org"));
+        assert!(!bypass.matches("corp.example.com"));
+        assert!(!bypass.matches("storage.googleapis.com"));
+        assert!(ProxyBypass::parse("").is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    History,
+    Builds,
+    Detail,
+    Download,
+    Extract,
+}
+
+impl Phase {
+    fn name(&self) -> &'static str {
+        match self {
+            Phase::History => "history fetch",
+            Phase::Builds => "builds crawl",
+            Phase::Detail => "detail fetch",
+            Phase::Download => "download",
+            Phase::Extract => "extraction",
+        }
+    }
+}
+
+static TIMINGS: Mutex<Vec<(Phase, Duration)>> = Mutex::new(Vec::new());
+
+pub(crate) fn record(phase: Phase, elapsed: Duration) {
+    let mut timings = TIMINGS.lock().unwrap();
+    match timings.iter_mut().find(|(p, _)| *p == phase) {
+        Some((_, total)) => *total += elapsed,
+        None => timings.push((phase, elapsed)),
+    }
+}
+
+pub(crate) fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(phase, start.elapsed());
+    result
+}
+
+pub(crate) async fn time_async<T>(phase: Phase, f: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let result = f.await;
+    record(phase, start.elapsed());
+    result
+}
+
+pub(crate) fn report() {
+    let timings = TIMINGS.lock().unwrap();
+    status!("==> timings:");
+    for (phase, elapsed) in timings.iter() {
+        status!("    {:<14} {:>8.2}s", phase.name(), elapsed.as_secs_f64());
+    }
+    let total: Duration = timings.iter().map(|(_, elapsed)| *elapsed).sum();
+    status!("    {:<14} {:>8.2}s", "total", total.as_secs_f64());
+}
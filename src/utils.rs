@@ -1,18 +1,228 @@
-use std::path::PathBuf;
+use std::{
+    fs::File,
+    future::Future,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
 
-pub(crate) fn get_cached_file_path(file: &str) -> Result<PathBuf> {
-    let mut path = PathBuf::new();
-    path.push(std::env::var("LOCALAPPDATA").or_else(|_| std::env::var("HOME"))?);
-    path.push("fetchbrowser");
-    if !path.exists() {
-        std::fs::create_dir_all(&path)?;
-    }
-    path.push(file);
-    Ok(path)
-}
+use crate::metadata::INCOMPLETE_MARKER;
 
 pub(crate) fn find_sequence<T: PartialEq>(haystack: &[T], needle: &[T]) -> Option<usize> {
     (0..haystack.len() - needle.len() + 1).find(|&i| haystack[i..i + needle.len()] == needle[..])
 }
+
+pub(crate) fn matches_version_prefix(candidate: &str, version: &str) -> bool {
+    candidate == version
+        || (candidate.chars().nth(version.len()) == Some('.') && candidate.starts_with(version))
+}
+
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let (pattern, text): (Vec<_>, Vec<_>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp + 1;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+/// 同时最多执行 `jobs` 个 `f`，全部结束后返回第一个错误，结果与 `items` 的顺序一致。
+pub(crate) async fn run_bounded<T, R, F>(
+    items: Vec<T>,
+    jobs: usize,
+    f: impl FnMut(T) -> F,
+) -> Result<Vec<R>>
+where
+    F: Future<Output = Result<R>>,
+{
+    // 先创建全部的 future（创建时不会执行），`f` 不必跨越 `await` 保存。
+    let futures: Vec<F> = items.into_iter().map(f).collect();
+    futures::stream::iter(futures)
+        .buffered(jobs.max(1))
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// 在阻塞线程中执行解压、计算摘要等耗时的同步操作，期间同一任务中的其他下载照常进行。
+pub(crate) async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+pub(crate) fn format_date(timestamp_ms: u64) -> String {
+    // 将 Unix 时间戳换算为公历日期（UTC）。
+    let days = (timestamp_ms / 1000 / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// 解析 `90s`、`10m`、`1h` 形式的时长，不带单位时按秒计算。
+pub(crate) fn parse_duration(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let (number, unit) = text.split_at(
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len()),
+    );
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid duration: {text}"))?;
+    let seconds = match unit {
+        "" | "s" => number,
+        "m" => number * 60,
+        "h" => number * 60 * 60,
+        _ => {
+            return Err(anyhow!(
+                "Invalid duration unit in {text}, expected s, m or h"
+            ))
+        }
+    };
+    Ok(Duration::from_secs(seconds))
+}
+
+/// 递归列出目录中的普通文件，不跟随也不列出符号链接，符号链接见 [`walk_symlinks`]。
+pub(crate) fn walk_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    Ok(walk(dir)?.0)
+}
+
+/// 递归列出目录中的符号链接（包括指向目录的，例如 macOS 应用中的 `Versions/Current`）。
+pub(crate) fn walk_symlinks(dir: &Path) -> Result<Vec<PathBuf>> {
+    Ok(walk(dir)?.1)
+}
+
+fn walk(dir: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut files = Vec::new();
+    let mut links = Vec::new();
+    let mut pending = vec![dir.to_owned()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let file_type = std::fs::symlink_metadata(&path)?.file_type();
+            if file_type.is_symlink() {
+                links.push(path);
+            } else if file_type.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    links.sort();
+    Ok((files, links))
+}
+
+/// 遇到共享冲突时的重试次数。
+const LOCKED_RETRIES: u32 = 5;
+
+/// 杀毒软件扫描刚写入的文件时会短暂独占它，此时打开、重命名会因共享冲突失败，稍等后重试。
+pub(crate) fn retry_locked<T>(mut op: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Err(err) if is_sharing_violation(&err) && attempt < LOCKED_RETRIES => {
+                attempt += 1;
+                std::thread::sleep(Duration::from_millis(100 << attempt));
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(windows)]
+fn is_sharing_violation(err: &std::io::Error) -> bool {
+    // ERROR_ACCESS_DENIED、ERROR_SHARING_VIOLATION、ERROR_LOCK_VIOLATION
+    matches!(err.raw_os_error(), Some(5 | 32 | 33))
+}
+
+#[cfg(not(windows))]
+fn is_sharing_violation(_err: &std::io::Error) -> bool {
+    false
+}
+
+/// 移动目录，两者不在同一个文件系统时改为复制后删除。
+pub(crate) fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    match retry_locked(|| std::fs::rename(from, to)) {
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+            // 先复制未完成标记，复制过程中被中断时目标目录也会被识别为未完成的安装。
+            let marker = from.join(INCOMPLETE_MARKER);
+            if marker.exists() {
+                std::fs::create_dir_all(to)?;
+                std::fs::copy(&marker, to.join(INCOMPLETE_MARKER))?;
+            }
+            copy_dir(from, to)?;
+            std::fs::remove_dir_all(from)?;
+            Ok(())
+        }
+        result => Ok(result?),
+    }
+}
+
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else {
+            retry_locked(|| std::fs::copy(entry.path(), &target))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    std::fs::copy(from, to)?;
+    Ok(())
+}
+
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,264 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use regex::Regex;
+
+use crate::utils::matches_version_prefix;
+
+/// 浏览器版本号：数字部分加上可选的标签，如 `115.0b3`、`102.1.0esr`。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BrowserVersion {
+    components: Vec<usize>,
+    tag: String,
+}
+
+impl BrowserVersion {
+    /// 不带标签的正式版本。
+    pub(crate) fn is_release(&self) -> bool {
+        self.tag.is_empty()
+    }
+
+    /// `102.1` 匹配 `102.1.0`、`102.1.0esr`；`115.0b` 匹配 `115.0b3`。
+    fn is_prefix_of(&self, version: &BrowserVersion) -> bool {
+        if self.is_release() {
+            version.components.starts_with(&self.components)
+        } else {
+            version.components == self.components && version.tag.starts_with(&self.tag)
+        }
+    }
+
+    /// 测试版等预发布标签（`a1`、`b3`、`rc1`）排在正式版本之前，其余标签（如 `esr`）排在之后。
+    fn tag_key(&self) -> (u8, &str, usize) {
+        let digits = self
+            .tag
+            .find(|ch: char| ch.is_ascii_digit())
+            .unwrap_or(self.tag.len());
+        let (name, number) = self.tag.split_at(digits);
+        let rank = match name {
+            "" => 1,
+            "a" | "b" | "rc" => 0,
+            _ => 2,
+        };
+        (rank, name, number.parse().unwrap_or_default())
+    }
+}
+
+impl FromStr for BrowserVersion {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = Vec::new();
+        let mut parts = s.split('.').peekable();
+        while let Some(part) = parts.next() {
+            let digits = part
+                .find(|ch: char| !ch.is_ascii_digit())
+                .unwrap_or(part.len());
+            components.push(part[..digits].parse().map_err(|_| "无效的版本号。")?);
+            let tag = &part[digits..];
+            if !tag.is_empty() {
+                return match parts.peek() {
+                    None => Ok(Self {
+                        components,
+                        tag: tag.to_owned(),
+                    }),
+                    Some(_) => Err("无效的版本号。"),
+                };
+            }
+        }
+        Ok(Self {
+            components,
+            tag: String::new(),
+        })
+    }
+}
+
+impl fmt::Display for BrowserVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let components: Vec<_> = self.components.iter().map(usize::to_string).collect();
+        write!(f, "{}{}", components.join("."), self.tag)
+    }
+}
+
+impl Ord for BrowserVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.components
+            .cmp(&other.components)
+            .then_with(|| self.tag_key().cmp(&other.tag_key()))
+    }
+}
+
+impl PartialOrd for BrowserVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// 命令行中指定的版本：前缀（默认，`102.1`）、精确（`=102.1.0`）或区间（`100..102`，两端包含）。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum VersionSpec {
+    Prefix(String),
+    Exact(String),
+    Range(Option<BrowserVersion>, Option<BrowserVersion>),
+}
+
+impl VersionSpec {
+    pub(crate) fn parse(spec: &str) -> Self {
+        if let Some(exact) = spec.strip_prefix('=') {
+            return Self::Exact(exact.to_owned());
+        }
+        if let Some((low, high)) = spec.split_once("..") {
+            let bound = |s: &str| match s {
+                "" => Some(None),
+                s => BrowserVersion::from_str(s)
+                    .ok()
+                    .filter(BrowserVersion::is_release)
+                    .map(Some),
+            };
+            if let (Some(low), Some(high)) = (bound(low), bound(high)) {
+                return Self::Range(low, high);
+            }
+        }
+        Self::Prefix(spec.to_owned())
+    }
+
+    pub(crate) fn matches(&self, candidate: &str) -> bool {
+        match self {
+            Self::Exact(version) => candidate == version,
+            Self::Prefix(prefix) => match (prefix.parse::<BrowserVersion>(), candidate.parse()) {
+                (Ok(wanted), Ok(version)) => wanted.is_prefix_of(&version),
+                _ => matches_version_prefix(candidate, prefix),
+            },
+            Self::Range(low, high) => {
+                let Ok(version) = candidate.parse::<BrowserVersion>() else {
+                    return false;
+                };
+                // 只比较两边都有的部分，缺少的部分视为通配：`110` 落在 `110.0.5..` 中。
+                let cmp = |bound: &BrowserVersion| {
+                    let len = version.components.len().min(bound.components.len());
+                    version.components[..len].cmp(&bound.components[..len])
+                };
+                low.as_ref().is_none_or(|low| cmp(low) != Ordering::Less)
+                    && high
+                        .as_ref()
+                        .is_none_or(|high| cmp(high) != Ordering::Greater)
+            }
+        }
+    }
+}
+
+/// 从 `chrome://version` 等处复制的版本字符串中取出版本号，
+/// 例如 `114.0.5735.199 (Official Build) (64-bit)`、`Google Chrome 114.0.5735.199`。
+///
+/// 只处理含有空白或括号的输入，普通的版本号、前缀和区间原样使用，返回 `None`。
+pub(crate) fn extract_version(input: &str) -> Option<String> {
+    if !input.contains(|ch: char| ch.is_whitespace() || ch == '(' || ch == ')') {
+        return None;
+    }
+    let version = Regex::new(r"\b\d+(?:\.\d+)+(?:[a-z]+\d*)?\b").unwrap();
+    version
+        .find(input)
+        .or_else(|| Regex::new(r"\b\d+\b").unwrap().find(input))
+        .map(|found| found.as_str().to_owned())
+}
+
+/// 从 `candidates` 中找出与 `wanted` 最接近的 `limit` 个版本：
+/// 相同的前缀越长越接近，其次比较第一个不同的部分相差多少。
+pub(crate) fn nearest_versions<'a>(
+    wanted: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    limit: usize,
+) -> Vec<String> {
+    let Ok(wanted) = wanted.trim_start_matches('=').parse::<BrowserVersion>() else {
+        return Vec::new();
+    };
+    let mut scored: Vec<_> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let version = candidate.parse::<BrowserVersion>().ok()?;
+            let common = wanted
+                .components
+                .iter()
+                .zip(&version.components)
+                .take_while(|(a, b)| a == b)
+                .count();
+            let distance = match (
+                wanted.components.get(common),
+                version.components.get(common),
+            ) {
+                (Some(a), Some(b)) => a.abs_diff(*b),
+                _ => 0,
+            };
+            Some((std::cmp::Reverse(common), distance, version))
+        })
+        .collect();
+    scored.sort_by(|a, b| (&a.0, a.1, &b.2).cmp(&(&b.0, b.1, &a.2)));
+    let mut nearest: Vec<String> = Vec::new();
+    for (_, _, version) in scored {
+        let version = version.to_string();
+        if !nearest.contains(&version) {
+            nearest.push(version);
+        }
+        if nearest.len() == limit {
+            break;
+        }
+    }
+    nearest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(spec: &str, candidate: &str) -> bool {
+        VersionSpec::parse(spec).matches(candidate)
+    }
+
+    #[test]
+    fn exact() {
+        assert!(matches("=114.0.5735.199", "114.0.5735.199"));
+        assert!(!matches("=114.0.5735", "114.0.5735.199"));
+    }
+
+    #[test]
+    fn prefix() {
+        assert!(matches("114", "114.0.5735.199"));
+        assert!(matches("102.1", "102.1.0esr"));
+        assert!(matches("115.0b", "115.0b3"));
+        assert!(!matches("11", "114.0.5735.199"));
+        assert!(!matches("115.0b", "115.0"));
+    }
+
+    #[test]
+    fn closed_range() {
+        assert!(matches("100..102", "100.0.4896.60"));
+        assert!(matches("100..102", "102.0.5005.61"));
+        assert!(!matches("100..102", "103.0.5060.53"));
+        assert!(!matches("100..102", "99.0.4844.51"));
+    }
+
+    #[test]
+    fn open_ended_range() {
+        assert!(matches("110..", "120.0.6099.109"));
+        assert!(!matches("110..", "109.0.5414.119"));
+        assert!(matches("..110", "90.0.4430.93"));
+        assert!(!matches("..110", "111.0.5563.64"));
+    }
+
+    #[test]
+    fn partial_bounds() {
+        assert!(matches("110.0.5..", "110"));
+        assert!(matches("110.0.5..", "110.0"));
+        assert!(matches("110.0.5..", "110.0.5481.77"));
+        assert!(!matches("110.0.4..110.0.4", "110.0.5481.77"));
+        assert!(!matches("110.0.5..", "110.0.4"));
+        assert!(matches("..110.0.5", "110"));
+        assert!(!matches("..110.0.5", "110.0.6"));
+    }
+
+    #[test]
+    fn invalid_range_falls_back_to_prefix() {
+        assert_eq!(
+            VersionSpec::parse("115.0b1.."),
+            VersionSpec::Prefix("115.0b1..".to_owned())
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    common::sort_newest_first,
+    events::recorded_installs,
+    metadata::{find_installs, InstallMetadata},
+    paths::Paths,
+    version::VersionSpec,
+};
+
+/// 在输出目录和安装日志记录的目录中查找匹配的安装，打印最新版本的可执行文件路径。
+pub(crate) fn print_which(browser: &str, version: &str, paths: &Paths) -> Result<()> {
+    let mut installs: Vec<(PathBuf, InstallMetadata)> = match paths.output_dir.exists() {
+        true => find_installs(&paths.output_dir)?,
+        false => Vec::new(),
+    };
+    for dir in recorded_installs(paths) {
+        if installs.iter().any(|(path, _)| *path == dir) {
+            continue;
+        }
+        // 已删除或未完成的安装读取 metadata 会失败。
+        if let Ok(metadata) = InstallMetadata::read(&dir) {
+            installs.push((dir, metadata));
+        }
+    }
+
+    let spec = VersionSpec::parse(version);
+    let mut matched: Vec<_> = installs
+        .into_iter()
+        .filter(|(_, metadata)| metadata.browser == browser && spec.matches(&metadata.version))
+        .collect();
+    sort_newest_first(&mut matched, |(_, metadata)| metadata.version.as_str());
+    let (dir, metadata) = matched
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No installed {browser} matches {version}."))?;
+    let path = match &metadata.executable {
+        Some(executable) => dir.join(executable),
+        None => dir,
+    };
+    println!("{}", path.display());
+    Ok(())
+}